@@ -0,0 +1,91 @@
+//! The Poseidon permutation of `poseidon_gl` on `Avx2GoldilocksField`, the state being
+//! three vectors of four elements.
+//!
+//! The packed arithmetic works on canonical values rather than on the Montgomery forms of
+//! `Fr`, so the state is converted on the way in and out, and the constants are used as
+//! they are.
+use super::avx2_field_gl::Avx2GoldilocksField;
+use crate::ff::PrimeField;
+use crate::field_gl::{Fr, FrRepr as GoldilocksField, MODULUS};
+use crate::packed::PackedField;
+use crate::poseidon_gl::{
+    is_full_round, ALL_ROUND_CONSTANTS, MDS_MATRIX_CIRC, MDS_MATRIX_DIAG, N_ROUNDS, SPONGE_WIDTH,
+};
+use core::arch::x86_64::*;
+
+type State = [Avx2GoldilocksField; SPONGE_WIDTH / 4];
+
+#[inline(always)]
+fn sbox(x: Avx2GoldilocksField) -> Avx2GoldilocksField {
+    let x2 = x.square();
+    let x4 = x2.square();
+    x4 * x2 * x
+}
+
+#[inline(always)]
+fn constant_layer(state: &mut State, round: usize) {
+    for (k, v) in state.iter_mut().enumerate() {
+        let i = round * SPONGE_WIDTH + 4 * k;
+        let rc: [u64; 4] = ALL_ROUND_CONSTANTS[i..i + 4].try_into().unwrap();
+        *v += Avx2GoldilocksField(rc.map(|c| GoldilocksField([c])));
+    }
+}
+
+/// Row `r` of the result is the sum over `i` of `circ[i] * state[(i + r) % 12]`, so the
+/// rows `4k..4k + 4` take, for each `i`, the four elements from `i + 4k` of the state
+/// repeated twice.
+#[inline(always)]
+fn mds_layer(state: &mut State) {
+    let mut twice = [GoldilocksField([0]); 2 * SPONGE_WIDTH];
+    for (k, v) in state.iter().enumerate() {
+        twice[4 * k..4 * k + 4].copy_from_slice(v.as_slice());
+        twice[SPONGE_WIDTH + 4 * k..SPONGE_WIDTH + 4 * k + 4].copy_from_slice(v.as_slice());
+    }
+    let mut res = [Avx2GoldilocksField::ZEROS; SPONGE_WIDTH / 4];
+    for (k, res) in res.iter_mut().enumerate() {
+        for (i, c) in MDS_MATRIX_CIRC.iter().enumerate() {
+            let v = *Avx2GoldilocksField::from_slice(&twice[i + 4 * k..i + 4 * k + 4]);
+            *res += v * GoldilocksField([*c]);
+        }
+        let diag: [u64; 4] = MDS_MATRIX_DIAG[4 * k..4 * k + 4].try_into().unwrap();
+        *res += state[k] * Avx2GoldilocksField(diag.map(|c| GoldilocksField([c])));
+    }
+    *state = res;
+}
+
+/// The Poseidon permutation, `poseidon_gl::permute_scalar` on packed vectors.
+pub fn permute(state: &mut [Fr; SPONGE_WIDTH]) {
+    let mut s: State = [Avx2GoldilocksField::ZEROS; SPONGE_WIDTH / 4];
+    for (v, x) in s.iter_mut().zip(state.chunks(4)) {
+        for (l, x) in v.as_slice_mut().iter_mut().zip(x) {
+            *l = x.into_repr();
+        }
+    }
+
+    for round in 0..N_ROUNDS {
+        constant_layer(&mut s, round);
+        if is_full_round(round) {
+            for v in s.iter_mut() {
+                *v = sbox(*v);
+            }
+        } else {
+            // the S-box of the first element only, blended in from the whole vector's
+            let x = sbox(s[0]);
+            s[0] = Avx2GoldilocksField::new(unsafe {
+                _mm256_blend_epi32::<0b0000_0011>(s[0].get(), x.get())
+            });
+        }
+        mds_layer(&mut s);
+    }
+
+    for (v, x) in s.iter().zip(state.chunks_mut(4)) {
+        for (l, x) in v.as_slice().iter().zip(x) {
+            // the packed arithmetic may leave values in [p, 2^64)
+            let mut l = *l;
+            if l.0[0] >= MODULUS.0[0] {
+                l.0[0] -= MODULUS.0[0];
+            }
+            *x = Fr::from_repr(l).unwrap();
+        }
+    }
+}
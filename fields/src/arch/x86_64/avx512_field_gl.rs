@@ -47,6 +47,12 @@ impl Avx512GoldilocksField {
     pub fn square(&self) -> Avx512GoldilocksField {
         Self::new(unsafe { square(self.get()) })
     }
+    /// The product of two `Fr` in their Montgomery form, `x * R` and `y * R`, as the Montgomery
+    /// form `x * y * R` (the `Mul` impl is the product of canonical values).
+    #[inline]
+    pub fn mont_mul(&self, rhs: Self) -> Avx512GoldilocksField {
+        Self::new(unsafe { mont_mul(self.get(), rhs.get()) })
+    }
 }
 
 unsafe impl PackedField for Avx512GoldilocksField {
@@ -381,6 +387,16 @@ unsafe fn square(x: __m512i) -> __m512i {
     reduce128(square64(x))
 }
 
+/// Montgomery multiplication, `x * y * 2^-128` modulo FIELD_ORDER. As 2^192 = 1, 2^-128 = 2^64
+/// = 2^32 - 1 and 2^128 = -2^32, the product `hi * 2^64 + lo` becomes `(lo - hi) * 2^32 - lo`.
+#[inline]
+unsafe fn mont_mul(x: __m512i, y: __m512i) -> __m512i {
+    let (hi, lo) = mul64_64(x, y);
+    let d = sub(lo, hi);
+    let d_shifted = reduce128((_mm512_srli_epi64::<32>(d), _mm512_slli_epi64::<32>(d)));
+    sub(d_shifted, lo)
+}
+
 #[inline]
 unsafe fn interleave1(x: __m512i, y: __m512i) -> (__m512i, __m512i) {
     let a = _mm512_unpacklo_epi64(x, y);
@@ -506,6 +522,20 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mont_mul() {
+        let a_arr = test_vals_a();
+        let b_arr = test_vals_b();
+        // the raw values are taken as Montgomery forms
+        let packed_a = *Avx512GoldilocksField::from_slice(&a_arr);
+        let packed_b = *Avx512GoldilocksField::from_slice(&b_arr);
+        let packed_res = packed_a.mont_mul(packed_b);
+        for ((a, b), res) in a_arr.iter().zip(b_arr).zip(packed_res.as_slice()) {
+            let expected = Fr::from_raw_repr(*a).unwrap() * Fr::from_raw_repr(b).unwrap();
+            assert_eq!(*res, expected.into_raw_repr());
+        }
+    }
+
     #[test]
     fn test_div() {
         let a_arr = test_vals_a();
@@ -0,0 +1,190 @@
+//! Radix-2 number-theoretic transform over Goldilocks.
+//!
+//! The evaluation domains are the ones pilcom and pil-stark use: the subgroup of order
+//! 2^n is generated by `root(n)`, and the extended domain of a STARK is its coset by
+//! `SHIFT`. Transforms work in place on natural-order slices whose length is a power of
+//! two.
+use crate::ff::{Field, PrimeField};
+use crate::field_gl::Fr;
+use rayon::prelude::*;
+
+// 2^S is the largest power of two dividing p - 1.
+const S: usize = <Fr as PrimeField>::S as usize;
+
+/// Generator of the subgroup of order 2^32 used by pilcom and pil-stark. It is a different
+/// primitive root than `Fr::root_of_unity()`, and connection maps and constant trees
+/// are only compatible with this one.
+pub const ROOT_2_32: u64 = 7277203076849721926;
+
+/// Coset shift of the extended domain, pil-stark's `F.shift`.
+pub const SHIFT: u64 = 7;
+
+// Below this many elements the transforms run sequentially.
+const PAR_THRESHOLD: usize = 1 << 10;
+
+/// `roots()[i]` generates the subgroup of order 2^i.
+pub fn roots() -> Vec<Fr> {
+    let mut roots = vec![Fr::ZERO; S + 1];
+    roots[S] = Fr::from(ROOT_2_32);
+    for i in (0..S).rev() {
+        roots[i] = roots[i + 1] * roots[i + 1];
+    }
+    roots
+}
+
+/// Generator of the subgroup of order 2^`n_bits`.
+pub fn root(n_bits: usize) -> Fr {
+    assert!(n_bits <= S, "no subgroup of order 2^{}", n_bits);
+    let mut w = Fr::from(ROOT_2_32);
+    for _ in n_bits..S {
+        w.square();
+    }
+    w
+}
+
+fn log2(n: usize) -> usize {
+    assert!(n.is_power_of_two(), "length {} is not a power of two", n);
+    n.trailing_zeros() as usize
+}
+
+fn powers(base: Fr, n: usize) -> Vec<Fr> {
+    let mut res = Vec::with_capacity(n);
+    let mut acc = Fr::ONE;
+    for _ in 0..n {
+        res.push(acc);
+        acc = acc * base;
+    }
+    res
+}
+
+fn bit_reverse(a: &mut [Fr]) {
+    let n = a.len();
+    if n <= 1 {
+        return;
+    }
+    let shift = usize::BITS - n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> shift;
+        if i < j {
+            a.swap(i, j);
+        }
+    }
+}
+
+/// Iterative Cooley-Tukey: bit reversal, then log2(n) layers of butterflies. Layers with
+/// many small blocks run the blocks in parallel, the last few split each block instead.
+fn radix2(a: &mut [Fr], w: Fr) {
+    let n = a.len();
+    if n <= 1 {
+        return;
+    }
+    bit_reverse(a);
+    let twiddles = powers(w, n / 2);
+    let parallel = n >= PAR_THRESHOLD;
+
+    let mut m = 1;
+    while m < n {
+        let step = n / (2 * m);
+        let butterfly = |(j, (lo, hi)): (usize, (&mut Fr, &mut Fr))| {
+            let t = *hi * twiddles[j * step];
+            *hi = *lo - t;
+            *lo = *lo + t;
+        };
+        if !parallel {
+            for block in a.chunks_mut(2 * m) {
+                let (lo, hi) = block.split_at_mut(m);
+                lo.iter_mut()
+                    .zip(hi.iter_mut())
+                    .enumerate()
+                    .for_each(butterfly);
+            }
+        } else if step >= rayon::current_num_threads() {
+            a.par_chunks_mut(2 * m).for_each(|block| {
+                let (lo, hi) = block.split_at_mut(m);
+                lo.iter_mut()
+                    .zip(hi.iter_mut())
+                    .enumerate()
+                    .for_each(butterfly);
+            });
+        } else {
+            for block in a.chunks_mut(2 * m) {
+                let (lo, hi) = block.split_at_mut(m);
+                lo.par_iter_mut()
+                    .zip(hi.par_iter_mut())
+                    .enumerate()
+                    .for_each(butterfly);
+            }
+        }
+        m *= 2;
+    }
+}
+
+fn scale(a: &mut [Fr], c: Fr) {
+    if a.len() >= PAR_THRESHOLD {
+        a.par_iter_mut().for_each(|x| *x = *x * c);
+    } else {
+        a.iter_mut().for_each(|x| *x = *x * c);
+    }
+}
+
+// a[i] *= c^i
+fn scale_powers(a: &mut [Fr], c: Fr) {
+    let mut acc = Fr::ONE;
+    for x in a.iter_mut() {
+        *x = *x * acc;
+        acc = acc * c;
+    }
+}
+
+/// Coefficients to evaluations on the subgroup of order `a.len()`: afterwards `a[i]` is
+/// the polynomial evaluated at `w^i`.
+pub fn ntt(a: &mut [Fr]) {
+    let w = root(log2(a.len()));
+    radix2(a, w);
+}
+
+/// Evaluations on the subgroup of order `a.len()` to coefficients.
+pub fn intt(a: &mut [Fr]) {
+    let n = a.len();
+    let w_inv = root(log2(n)).inverse().unwrap();
+    radix2(a, w_inv);
+    scale(a, Fr::from(n as u64).inverse().unwrap());
+}
+
+/// Coefficients to evaluations on the coset `shift * H`: `a[i]` becomes the polynomial
+/// evaluated at `shift * w^i`.
+pub fn coset_ntt(a: &mut [Fr], shift: Fr) {
+    scale_powers(a, shift);
+    ntt(a);
+}
+
+/// Evaluations on the coset `shift * H` to coefficients.
+pub fn coset_intt(a: &mut [Fr], shift: Fr) {
+    intt(a);
+    scale_powers(a, shift.inverse().unwrap());
+}
+
+/// Low-degree extension: from the evaluations of a polynomial on the subgroup of order
+/// `evals.len()` to its evaluations on the coset `SHIFT * H` of order 2^`n_bits_ext`.
+pub fn extend_pol(evals: &[Fr], n_bits_ext: usize) -> Vec<Fr> {
+    let n_bits = log2(evals.len());
+    assert!(
+        n_bits_ext >= n_bits,
+        "cannot extend a domain of 2^{} to 2^{}",
+        n_bits,
+        n_bits_ext
+    );
+    let mut res = evals.to_vec();
+    intt(&mut res);
+    res.resize(1 << n_bits_ext, Fr::ZERO);
+    coset_ntt(&mut res, Fr::from(SHIFT));
+    res
+}
+
+/// Degree of the polynomial interpolating `evals` on the subgroup of order `evals.len()`,
+/// `None` for the zero polynomial.
+pub fn degree(evals: &[Fr]) -> Option<usize> {
+    let mut coefs = evals.to_vec();
+    intt(&mut coefs);
+    coefs.iter().rposition(|c| !c.is_zero())
+}
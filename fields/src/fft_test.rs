@@ -0,0 +1,122 @@
+#[cfg(test)]
+mod tests {
+    use crate::ff::*;
+    use crate::fft::*;
+    use crate::field_gl::*;
+    use crate::rand::Rand;
+    use proptest::prelude::*;
+
+    /// Horner evaluation of `coefs` at `x`.
+    fn eval_pol(coefs: &[Fr], x: Fr) -> Fr {
+        coefs.iter().rev().fold(Fr::ZERO, |acc, c| acc * x + *c)
+    }
+
+    /// O(n^2) DFT on the coset `shift * H`.
+    fn naive_dft(coefs: &[Fr], shift: Fr) -> Vec<Fr> {
+        let n = coefs.len();
+        let w = root(n.trailing_zeros() as usize);
+        (0..n)
+            .map(|i| eval_pol(coefs, shift * w.exp(i as u64)))
+            .collect()
+    }
+
+    fn random_vec(n: usize) -> Vec<Fr> {
+        let mut rng = rand::thread_rng();
+        (0..n).map(|_| Fr::rand(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_roots() {
+        let roots = roots();
+        assert_eq!(roots[0], Fr::one());
+        assert_eq!(roots[1], -Fr::one());
+        for (i, r) in roots.iter().enumerate() {
+            assert_eq!(*r, root(i));
+            if i > 0 {
+                assert_eq!(r.exp(1 << (i - 1)), -Fr::one());
+            }
+        }
+    }
+
+    #[test]
+    fn test_ntt_small() {
+        for n_bits in 0..6 {
+            let coefs = random_vec(1 << n_bits);
+            let mut a = coefs.clone();
+            ntt(&mut a);
+            assert_eq!(a, naive_dft(&coefs, Fr::one()));
+            intt(&mut a);
+            assert_eq!(a, coefs);
+        }
+    }
+
+    #[test]
+    fn test_ntt_parallel() {
+        // large enough to go through both parallel layer strategies
+        let coefs = random_vec(1 << 12);
+        let mut a = coefs.clone();
+        ntt(&mut a);
+        let x = Fr::from(123456789u64);
+        let w = root(12);
+        for i in [0usize, 1, 777, 4095] {
+            assert_eq!(a[i], eval_pol(&coefs, w.exp(i as u64)));
+        }
+        assert_ne!(a, coefs);
+        intt(&mut a);
+        assert_eq!(a, coefs);
+        assert_eq!(eval_pol(&a, x), eval_pol(&coefs, x));
+    }
+
+    #[test]
+    fn test_coset_ntt() {
+        let shift = Fr::from(SHIFT);
+        let coefs = random_vec(16);
+        let mut a = coefs.clone();
+        coset_ntt(&mut a, shift);
+        assert_eq!(a, naive_dft(&coefs, shift));
+        coset_intt(&mut a, shift);
+        assert_eq!(a, coefs);
+    }
+
+    #[test]
+    fn test_extend_pol() {
+        // degree 3 polynomial given on a domain of 8
+        let coefs: Vec<Fr> = [5u64, 0, 2, 9, 0, 0, 0, 0]
+            .iter()
+            .map(|c| Fr::from(*c))
+            .collect();
+        let mut evals = coefs.clone();
+        ntt(&mut evals);
+        assert_eq!(degree(&evals), Some(3));
+        assert_eq!(degree(&[Fr::zero(); 8]), None);
+
+        let ext = extend_pol(&evals, 5);
+        assert_eq!(ext.len(), 32);
+        let shift = Fr::from(SHIFT);
+        let w = root(5);
+        for (i, e) in ext.iter().enumerate() {
+            assert_eq!(*e, eval_pol(&coefs, shift * w.exp(i as u64)));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "not a power of two")]
+    fn test_ntt_not_power_of_two() {
+        ntt(&mut random_vec(6));
+    }
+
+    // RANDOMIZED TESTS
+    // ================================================================================================
+
+    proptest! {
+        #[test]
+        fn ntt_proptest(v in prop::collection::vec(any::<u64>(), 32)) {
+            let coefs: Vec<Fr> = v.iter().map(|x| Fr::from(*x)).collect();
+            let mut a = coefs.clone();
+            ntt(&mut a);
+            prop_assert_eq!(&a, &naive_dft(&coefs, Fr::one()));
+            intt(&mut a);
+            prop_assert_eq!(a, coefs);
+        }
+    }
+}
@@ -6,6 +6,7 @@ use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
 
 #[derive(Eq, Serialize, Deserialize)]
+#[repr(transparent)]
 pub struct Fr(pub FrRepr);
 
 /// This is the modulus m of the prime field
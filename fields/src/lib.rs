@@ -2,11 +2,20 @@
 extern crate rand;
 
 pub mod arch;
+pub mod fft;
+#[cfg(test)]
+mod fft_test;
 pub mod field_gl;
 #[cfg(test)]
 mod field_gl_test;
 pub mod packable;
 pub mod packed;
+pub mod poseidon_bn128;
+#[cfg(test)]
+mod poseidon_bn128_test;
+pub mod poseidon_gl;
+#[cfg(test)]
+mod poseidon_gl_test;
 
 pub use crate::ff::*;
 pub use bellman_ce::pairing::ff;
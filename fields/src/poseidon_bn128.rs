@@ -0,0 +1,262 @@
+//! Poseidon over the BN254 scalar field with the parameters of circomlib, the hash of
+//! pil-stark's `MerkleHashBN128` and so of the STARKs that are wrapped in a SNARK.
+//!
+//! The state is the capacity followed by 1 to `MAX_INPUTS` inputs, the S-box is x^5, and
+//! there are `N_ROUNDS_F` full rounds, half before and half after the `N_ROUNDS_P` partial
+//! ones of the width. The round constants and the Cauchy MDS matrix are the ones the
+//! reference implementation draws from its Grain LFSR, which circomlib ships, so they are
+//! derived here the same way, security checks of the matrix included, on first use of each
+//! width.
+use crate::bellman_ce::pairing::bn256::{Fr, FrRepr};
+use crate::ff::{Field, PrimeField, PrimeFieldRepr};
+use crate::field_gl::Fr as FGL;
+use std::sync::OnceLock;
+
+/// Largest number of inputs of a permutation, the arity of the Merkle trees.
+pub const MAX_INPUTS: usize = 16;
+
+pub const N_ROUNDS_F: usize = 8;
+/// Partial rounds for 1 to `MAX_INPUTS` inputs.
+pub const N_ROUNDS_P: [usize; MAX_INPUTS] = [
+    56, 57, 56, 60, 60, 63, 64, 63, 60, 66, 60, 65, 70, 60, 64, 68,
+];
+
+struct Params {
+    constants: Vec<Fr>,
+    mds: Vec<Vec<Fr>>,
+}
+
+/// The 80-bit Grain LFSR of the reference implementation, bit `i` of the register being
+/// the `i`-th oldest.
+struct Grain(u128);
+
+impl Grain {
+    fn new(t: usize) -> Self {
+        let fields = [
+            // field, sbox, field size, t, full and partial rounds, padding
+            (1, 2),
+            (0, 4),
+            (254, 12),
+            (t, 12),
+            (N_ROUNDS_F, 10),
+            (N_ROUNDS_P[t - 2], 10),
+            ((1 << 30) - 1, 30),
+        ];
+        let mut bits = 0u128;
+        let mut pos = 0;
+        for (value, width) in fields {
+            for i in (0..width).rev() {
+                bits |= (((value >> i) & 1) as u128) << pos;
+                pos += 1;
+            }
+        }
+        let mut grain = Grain(bits);
+        for _ in 0..160 {
+            grain.next_bit();
+        }
+        grain
+    }
+
+    fn next_bit(&mut self) -> u64 {
+        let s = self.0;
+        let b = (s ^ (s >> 13) ^ (s >> 23) ^ (s >> 38) ^ (s >> 51) ^ (s >> 62)) & 1;
+        self.0 = (s >> 1) | (b << 79);
+        b as u64
+    }
+
+    /// Output bits go in pairs, and a pair only gives its second bit when the first is 1.
+    fn random_bit(&mut self) -> u64 {
+        loop {
+            let (b1, b2) = (self.next_bit(), self.next_bit());
+            if b1 == 1 {
+                return b2;
+            }
+        }
+    }
+
+    /// 254 random bits, most significant first.
+    fn random_repr(&mut self) -> FrRepr {
+        let mut r = FrRepr::default();
+        for i in (0..254).rev() {
+            r.as_mut()[i / 64] |= self.random_bit() << (i % 64);
+        }
+        r
+    }
+
+    /// Round constants are drawn until they are below the modulus.
+    fn random_constant(&mut self) -> Fr {
+        loop {
+            if let Ok(c) = Fr::from_repr(self.random_repr()) {
+                return c;
+            }
+        }
+    }
+
+    /// The values of the matrix are reduced instead. 254 bits are less than twice the
+    /// modulus.
+    fn random_reduced(&mut self) -> Fr {
+        let mut r = self.random_repr();
+        if let Ok(v) = Fr::from_repr(r) {
+            return v;
+        }
+        r.sub_noborrow(&Fr::char());
+        Fr::from_repr(r).unwrap()
+    }
+}
+
+fn generate_params(t: usize) -> Params {
+    let mut grain = Grain::new(t);
+    let constants = (0..(N_ROUNDS_F + N_ROUNDS_P[t - 2]) * t)
+        .map(|_| grain.random_constant())
+        .collect();
+    // M[i][j] = 1 / (x[i] + y[j]), all of x and y distinct, drawn again until it is secure
+    let mds = loop {
+        let xy = loop {
+            let xy: Vec<Fr> = (0..2 * t).map(|_| grain.random_reduced()).collect();
+            if (1..xy.len()).all(|i| !xy[..i].contains(&xy[i])) {
+                break xy;
+            }
+        };
+        let mds: Vec<Vec<Fr>> = (0..t)
+            .map(|i| {
+                (0..t)
+                    .map(|j| {
+                        let mut s = xy[i];
+                        s.add_assign(&xy[t + j]);
+                        s.inverse().unwrap()
+                    })
+                    .collect()
+            })
+            .collect();
+        if is_secure(&mds) {
+            break mds;
+        }
+    };
+    Params { constants, mds }
+}
+
+fn mat_vec(m: &[Vec<Fr>], v: &[Fr]) -> Vec<Fr> {
+    m.iter()
+        .map(|row| {
+            let mut acc = Fr::zero();
+            for (a, b) in row.iter().zip(v) {
+                let mut p = *a;
+                p.mul_assign(b);
+                acc.add_assign(&p);
+            }
+            acc
+        })
+        .collect()
+}
+
+/// Whether `vectors` are linearly independent.
+fn independent<'a>(vectors: impl Iterator<Item = &'a Vec<Fr>>) -> bool {
+    // an echelon form, each vector with its pivot, which is 1
+    let mut basis: Vec<(usize, Vec<Fr>)> = vec![];
+    for v in vectors {
+        let mut w = v.clone();
+        for (pivot, b) in &basis {
+            let f = w[*pivot];
+            for (x, y) in w.iter_mut().zip(b) {
+                let mut p = *y;
+                p.mul_assign(&f);
+                x.sub_assign(&p);
+            }
+        }
+        let Some(pivot) = w.iter().position(|x| !x.is_zero()) else {
+            return false;
+        };
+        let inv = w[pivot].inverse().unwrap();
+        w.iter_mut().for_each(|x| x.mul_assign(&inv));
+        basis.push((pivot, w));
+    }
+    true
+}
+
+/// The reference generator's check of the matrix against invariant subspace trails, its
+/// `algorithm_2` on M and `algorithm_3` on M^2 to M^4t with one S-box: the images of the
+/// first unit vector by the powers of each of these matrices must span the whole space.
+pub(crate) fn is_secure(mds: &[Vec<Fr>]) -> bool {
+    let t = mds.len();
+    // M^j times the unit vector, for every j the powers up to M^4t need
+    let mut images = vec![vec![Fr::zero(); t]];
+    images[0][0] = Fr::one();
+    for j in 1..=4 * t * (t - 1) {
+        images.push(mat_vec(mds, &images[j - 1]));
+    }
+    (1..=4 * t).all(|r| independent((0..t).map(|k| &images[k * r])))
+}
+
+fn params(t: usize) -> &'static Params {
+    static PARAMS: [OnceLock<Params>; MAX_INPUTS] = [const { OnceLock::new() }; MAX_INPUTS];
+    PARAMS[t - 2].get_or_init(|| generate_params(t))
+}
+
+#[inline(always)]
+fn sbox(x: &mut Fr) {
+    let mut x4 = *x;
+    x4.square();
+    x4.square();
+    x.mul_assign(&x4);
+}
+
+/// The Poseidon permutation of `state`, of 2 to `MAX_INPUTS` + 1 elements.
+pub fn permute(state: &mut [Fr]) {
+    let t = state.len();
+    assert!(
+        (2..=MAX_INPUTS + 1).contains(&t),
+        "no Poseidon of width {}",
+        t
+    );
+    let p = params(t);
+    let n_rounds_p = N_ROUNDS_P[t - 2];
+    for round in 0..N_ROUNDS_F + n_rounds_p {
+        for (x, c) in state.iter_mut().zip(&p.constants[round * t..]) {
+            x.add_assign(c);
+        }
+        if !(N_ROUNDS_F / 2..N_ROUNDS_F / 2 + n_rounds_p).contains(&round) {
+            state.iter_mut().for_each(sbox);
+        } else {
+            sbox(&mut state[0]);
+        }
+        let mixed = mat_vec(&p.mds, state);
+        state.copy_from_slice(&mixed);
+    }
+}
+
+/// circomlib's `poseidon(inputs, initState)`: the first element of the permutation of
+/// `init` followed by `inputs`.
+pub fn hash(inputs: &[Fr], init: Fr) -> Fr {
+    let mut state = Vec::with_capacity(inputs.len() + 1);
+    state.push(init);
+    state.extend_from_slice(inputs);
+    permute(&mut state);
+    state[0]
+}
+
+/// Three Goldilocks values as one element, the first one in the low bits.
+pub fn pack(values: &[FGL]) -> Fr {
+    assert!(
+        values.len() <= 3,
+        "{} values do not fit an element",
+        values.len()
+    );
+    let mut r = FrRepr::default();
+    for (limb, v) in r.as_mut().iter_mut().zip(values) {
+        *limb = v.as_int();
+    }
+    Fr::from_repr(r).unwrap()
+}
+
+/// pil-stark's `LinearHashBN`, the hash of a row of a Merkle tree. The values are packed
+/// three by three and absorbed `MAX_INPUTS` at a time, the last group being as long as what
+/// is left, with the output of each permutation as the capacity of the next one. No values
+/// hash to zero.
+pub fn linear_hash(values: &[FGL]) -> Fr {
+    let packed: Vec<Fr> = values.chunks(3).map(pack).collect();
+    let mut st = Fr::zero();
+    for group in packed.chunks(MAX_INPUTS) {
+        st = hash(group, st);
+    }
+    st
+}
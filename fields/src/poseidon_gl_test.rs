@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use crate::field_gl::*;
+    use crate::poseidon_gl::*;
+    #[cfg(target_feature = "avx2")]
+    use crate::rand::Rand;
+
+    const P: u64 = 0xffffffff00000001;
+
+    fn state(v: [u64; SPONGE_WIDTH]) -> [Fr; SPONGE_WIDTH] {
+        v.map(Fr::from)
+    }
+
+    // the vectors of plonky2's Goldilocks Poseidon tests
+    #[test]
+    fn test_vectors() {
+        let vectors: [([u64; SPONGE_WIDTH], [u64; SPONGE_WIDTH]); 4] = [
+            (
+                [0; SPONGE_WIDTH],
+                [
+                    0x3c18a9786cb0b359,
+                    0xc4055e3364a246c3,
+                    0x7953db0ab48808f4,
+                    0xc71603f33a1144ca,
+                    0xd7709673896996dc,
+                    0x46a84e87642f44ed,
+                    0xd032648251ee0b3c,
+                    0x1c687363b207df62,
+                    0xdf8565563e8045fe,
+                    0x40f5b37ff4254dae,
+                    0xd070f637b431067c,
+                    0x1792b1c4342109d7,
+                ],
+            ),
+            (
+                [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+                [
+                    0xd64e1e3efc5b8e9e,
+                    0x53666633020aaa47,
+                    0xd40285597c6a8825,
+                    0x613a4f81e81231d2,
+                    0x414754bfebd051f0,
+                    0xcb1f8980294a023f,
+                    0x6eb2a9e4d54a9d0f,
+                    0x1902bc3af467e056,
+                    0xf045d5eafdc6021f,
+                    0xe4150f77caaa3be5,
+                    0xc9bfd01d39b50cce,
+                    0x5c0a27fcb0e1459b,
+                ],
+            ),
+            (
+                [P - 1; SPONGE_WIDTH],
+                [
+                    0xbe0085cfc57a8357,
+                    0xd95af71847d05c09,
+                    0xcf55a13d33c1c953,
+                    0x95803a74f4530e82,
+                    0xfcd99eb30a135df1,
+                    0xe095905e913a3029,
+                    0xde0392461b42919b,
+                    0x7d3260e24e81d031,
+                    0x10d3d0465d9deaa0,
+                    0xa87571083dfc2a47,
+                    0xe18263681e9958f8,
+                    0xe28e96f1ae5e60d3,
+                ],
+            ),
+            (
+                [
+                    0x8ccbbbea4fe5d2b7,
+                    0xc2af59ee9ec49970,
+                    0x90f7e1a9e658446a,
+                    0xdcc0630a3ab8b1b8,
+                    0x7ff8256bca20588c,
+                    0x5d99a7ca0c44ecfb,
+                    0x48452b17a70fbee3,
+                    0xeb09d654690b6c88,
+                    0x4a55d3a39c676a88,
+                    0xc0407a38d2285139,
+                    0xa234bac9356386d1,
+                    0xe1633f2bad98a52f,
+                ],
+                [
+                    0xa89280105650c4ec,
+                    0xab542d53860d12ed,
+                    0x5704148e9ccab94f,
+                    0xd3a826d4b62da9f5,
+                    0x8a7a6ca87892574f,
+                    0xc7017e1cad1a674e,
+                    0x1f06668922318e34,
+                    0xa3b203bc8102676f,
+                    0xfcc781b0ce382bf2,
+                    0x934c69ff3ed14ba5,
+                    0x504688a5996e8f13,
+                    0x401f3f2ed524a2ba,
+                ],
+            ),
+        ];
+        for (input, output) in vectors {
+            let mut s = state(input);
+            permute_scalar(&mut s);
+            assert_eq!(s, state(output));
+            let mut s = state(input);
+            permute(&mut s);
+            assert_eq!(s, state(output));
+        }
+    }
+
+    #[test]
+    fn test_linear_hash() {
+        let values: Vec<Fr> = (1..=20u64).map(Fr::from).collect();
+        let zero = Fr::ZERO;
+
+        // short rows are their own hash
+        assert_eq!(linear_hash(&[]), [zero; 4]);
+        assert_eq!(
+            linear_hash(&values[..3]),
+            [values[0], values[1], values[2], zero]
+        );
+
+        let first: [Fr; SPONGE_RATE] = values[..8].try_into().unwrap();
+        assert_eq!(linear_hash(&values[..8]), hash(&first, &[zero; 4]));
+        let mut state = [zero; SPONGE_WIDTH];
+        state[..8].copy_from_slice(&values[..8]);
+        permute_scalar(&mut state);
+        assert_eq!(hash(&first, &[zero; 4]), state[..4]);
+
+        // the last chunk is padded with zeros
+        let mut second = [zero; SPONGE_RATE];
+        second[..5].copy_from_slice(&values[8..13]);
+        assert_eq!(
+            linear_hash(&values[..13]),
+            hash(&second, &hash(&first, &[zero; 4]))
+        );
+        assert_ne!(linear_hash(&values[..13]), linear_hash(&values[..14]));
+    }
+
+    // `permute` is `permute_scalar` unless the AVX2 path is built
+    #[cfg(target_feature = "avx2")]
+    #[test]
+    fn test_permute_matches_scalar() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let mut a = [Fr::ZERO; SPONGE_WIDTH];
+            for x in a.iter_mut() {
+                *x = Fr::rand(&mut rng);
+            }
+            let mut b = a;
+            permute(&mut a);
+            permute_scalar(&mut b);
+            assert_eq!(a, b);
+        }
+    }
+
+    #[cfg(all(
+        target_arch = "x86_64",
+        target_feature = "avx2",
+        not(all(
+            target_feature = "avx512bw",
+            target_feature = "avx512cd",
+            target_feature = "avx512dq",
+            target_feature = "avx512f",
+            target_feature = "avx512vl"
+        ))
+    ))]
+    #[test]
+    fn test_avx2_matches_scalar() {
+        use crate::arch::x86_64::avx2_poseidon_gl;
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..100 {
+            let mut a = [Fr::ZERO; SPONGE_WIDTH];
+            for x in a.iter_mut() {
+                *x = Fr::rand(&mut rng);
+            }
+            let mut b = a;
+            avx2_poseidon_gl::permute(&mut a);
+            permute_scalar(&mut b);
+            assert_eq!(a, b);
+        }
+        let mut s = [-Fr::ONE; SPONGE_WIDTH];
+        avx2_poseidon_gl::permute(&mut s);
+        assert_eq!(s[0], Fr::from(0xbe0085cfc57a8357u64));
+    }
+}
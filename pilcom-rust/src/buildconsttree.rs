@@ -0,0 +1,42 @@
+use anyhow::Result;
+use clap::Parser;
+
+use pilcom_rust::utils::const_tree::ConstTree;
+use pilcom_rust::utils::polarray::{PolKind, PolsArray};
+use pilcom_rust::utils::types::{load_json, StarkStruct, PIL};
+
+/// Build the Merkle tree of the extended constant polynomials, and the verification key
+/// with its root, as pil-stark's buildconsttree.
+#[derive(Parser, Debug)]
+#[command(name = "buildconsttree", version, about)]
+struct Cli {
+    /// pil.json produced by pilcom
+    #[arg(short = 'p', long = "pil")]
+    pil: String,
+    /// Constant polynomials file
+    #[arg(short = 'c', long = "constant")]
+    constant: String,
+    /// starkstruct.json
+    #[arg(short = 's', long = "starkstruct")]
+    stark_struct: String,
+    /// Tree file to write
+    #[arg(short = 't', long = "consttree")]
+    const_tree: String,
+    /// Verification key file to write
+    #[arg(short = 'v', long = "verkey")]
+    ver_key: String,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let pil = load_json::<PIL>(&cli.pil)?;
+    let stark_struct = load_json::<StarkStruct>(&cli.stark_struct)?;
+
+    let mut const_pols = PolsArray::new(&pil, PolKind::Constant)?;
+    const_pols.load(&cli.constant)?;
+
+    let tree = ConstTree::build(&const_pols, &stark_struct)?;
+    tree.save(&cli.const_tree)?;
+    std::fs::write(&cli.ver_key, tree.verification_key())?;
+    Ok(())
+}
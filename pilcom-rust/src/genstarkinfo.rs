@@ -0,0 +1,39 @@
+use anyhow::Result;
+use clap::Parser;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use pilcom_rust::utils::stark_info::StarkInfo;
+use pilcom_rust::utils::types::{load_json, StarkStruct, PIL};
+
+/// Generate the STARK setup of a PIL, as pil-stark's main_genstarkinfo.
+#[derive(Parser, Debug)]
+#[command(name = "genstarkinfo", version, about)]
+struct Cli {
+    /// pil.json produced by pilcom
+    #[arg(short = 'p', long = "pil")]
+    pil: String,
+    /// starkstruct.json
+    #[arg(short = 's', long = "starkstruct")]
+    stark_struct: String,
+    /// starkinfo.json to write
+    #[arg(short = 'i', long = "starkinfo")]
+    stark_info: String,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let pil = load_json::<PIL>(&cli.pil)?;
+    let stark_struct = load_json::<StarkStruct>(&cli.stark_struct)?;
+    let stark_info = StarkInfo::new(&pil, &stark_struct)?;
+
+    // indented by one space, as pil-stark writes it
+    let mut w = BufWriter::new(File::create(&cli.stark_info)?);
+    let formatter = serde_json::ser::PrettyFormatter::with_indent(b" ");
+    stark_info.serialize(&mut serde_json::Serializer::with_formatter(
+        &mut w, formatter,
+    ))?;
+    w.flush()?;
+    Ok(())
+}
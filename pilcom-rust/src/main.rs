@@ -1,3 +1,112 @@
-fn main() {
-    println!("Hello, world!");
+use anyhow::Result;
+use clap::Parser;
+use env_logger::{Builder, Env};
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use pilcom_rust::utils::element_type::ElementType;
+use pilcom_rust::utils::pil_verify::{PilVerify, Pols};
+use pilcom_rust::utils::polarray::{PolKind, PolsArray};
+use pilcom_rust::utils::types::{load_json, PIL};
+use pilcom_rust::utils::vcd::{write_vcd, Signal};
+
+/// Verify a witness (constant and committed polynomials) against a compiled PIL.
+#[derive(Parser, Debug)]
+#[command(name = "pilverify", version, about)]
+struct Cli {
+    /// pil.json produced by pilcom
+    #[arg(short = 'p', long = "pil")]
+    pil: String,
+    /// Constant polynomials file
+    #[arg(short = 'c', long = "constant")]
+    constant: String,
+    /// Committed polynomials file
+    #[arg(short = 'm', long = "commit")]
+    commit: String,
+    /// Log level: error, warn, info, debug or trace
+    #[arg(short = 'l', long = "log-level", default_value = "info")]
+    log_level: String,
+    /// Stop after this many failures
+    #[arg(short = 'e', long = "max-errors")]
+    max_errors: Option<usize>,
+    /// Write the verification report as JSON to this file
+    #[arg(short = 'j', long = "json")]
+    json: Option<String>,
+    /// Map the polynomial files instead of reading them into memory
+    #[arg(short = 'M', long = "mmap")]
+    mmap: bool,
+    /// Check that every value fits the elementType of its polynomial
+    #[arg(short = 't', long = "check-types")]
+    check_types: bool,
+    /// Write the trace as a VCD waveform to this file
+    #[arg(short = 'w', long = "vcd")]
+    vcd: Option<String>,
+    /// Include the intermediate polynomials in the VCD
+    #[arg(short = 'i', long = "vcd-intermediates", requires = "vcd")]
+    vcd_intermediates: bool,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    Builder::from_env(Env::default().default_filter_or(cli.log_level.as_str())).init();
+
+    let pil = load_json::<PIL>(&cli.pil)?;
+    let (const_pols, cm_pols) = if cli.mmap {
+        (
+            PolsArray::new_mapped(&pil, PolKind::Constant, &cli.constant)?,
+            PolsArray::new_mapped(&pil, PolKind::Commit, &cli.commit)?,
+        )
+    } else {
+        let mut const_pols = PolsArray::new(&pil, PolKind::Constant)?;
+        const_pols.load(&cli.constant)?;
+        let mut cm_pols = PolsArray::new(&pil, PolKind::Commit)?;
+        cm_pols.load(&cli.commit)?;
+        (const_pols, cm_pols)
+    };
+    if cli.check_types {
+        const_pols.check_types()?;
+        cm_pols.check_types()?;
+    }
+
+    if let Some(vcd) = &cli.vcd {
+        let mut signals = cm_pols.signals(&[])?;
+        signals.extend(const_pols.signals(&[])?);
+        if cli.vcd_intermediates {
+            let mut pols: Pols = Default::default();
+            let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+            signals.extend(
+                pil_verifier
+                    .intermediates()?
+                    .into_iter()
+                    .map(|(name, values)| Signal {
+                        name,
+                        elementType: ElementType::Field,
+                        values: Cow::Owned(values),
+                    }),
+            );
+        }
+        let n = signals.iter().map(|s| s.values.len()).max().unwrap_or(0);
+        let mut w = BufWriter::new(File::create(vcd)?);
+        write_vcd(&mut w, &signals, 0..n)?;
+        w.flush()?;
+    }
+
+    let mut pols: Pols = Default::default();
+    let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+    pil_verifier.set_max_errors(cli.max_errors);
+    let report = pil_verifier.verify_pil()?;
+
+    if let Some(json) = &cli.json {
+        let mut f = File::create(json)?;
+        f.write_all(serde_json::to_string_pretty(&report)?.as_bytes())?;
+    }
+
+    if report.is_empty() {
+        println!("PIL OK!!");
+        return Ok(());
+    }
+    print!("{}", report);
+    println!("PIL does not match: {} failures", report.len());
+    std::process::exit(1);
 }
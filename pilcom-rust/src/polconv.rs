@@ -0,0 +1,114 @@
+use anyhow::{bail, Result};
+use clap::Parser;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::ops::Range;
+use std::path::Path;
+
+use pilcom_rust::utils::pol_text::Selection;
+use pilcom_rust::utils::polarray::{FileFormat, PolKind, PolsArray};
+use pilcom_rust::utils::types::{load_json, PIL};
+
+/// Convert polynomial files between the binary formats, CSV and JSON, picked by the
+/// `.csv` and `.json` extensions. Outputs can also be `.vcd` waveforms. Binary outputs
+/// hold every polynomial and row.
+#[derive(Parser, Debug)]
+#[command(name = "polconv", version, about)]
+struct Cli {
+    /// pil.json produced by pilcom
+    #[arg(short = 'p', long = "pil")]
+    pil: String,
+    /// The polynomials are constant ones, committed otherwise
+    #[arg(short = 'c', long = "constant")]
+    constant: bool,
+    /// File to read
+    #[arg(short = 'i', long = "input")]
+    input: String,
+    /// File to write
+    #[arg(short = 'o', long = "output")]
+    output: String,
+    /// Namespaces, polynomials or array elements to export, all of them by default
+    #[arg(short = 's', long = "select", value_delimiter = ',')]
+    select: Vec<String>,
+    /// Rows to export, as start..end, start.. or ..end
+    #[arg(short = 'r', long = "rows", value_parser = parse_rows)]
+    rows: Option<Range<usize>>,
+    /// Binary format to write: raw, versioned, packed or packed-zstd
+    #[arg(short = 'f', long = "format", default_value = "raw")]
+    format: String,
+}
+
+fn parse_rows(s: &str) -> Result<Range<usize>, String> {
+    let (start, end) = s
+        .split_once("..")
+        .ok_or_else(|| format!("{} is not a range", s))?;
+    let bound = |b: &str, default| match b {
+        "" => Ok(default),
+        _ => b.parse::<usize>().map_err(|e| format!("{}: {}", b, e)),
+    };
+    Ok(bound(start, 0)?..bound(end, usize::MAX)?)
+}
+
+fn extension(file_name: &str) -> Option<String> {
+    Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let output = extension(&cli.output);
+    if !matches!(output.as_deref(), Some("csv" | "json" | "vcd"))
+        && (!cli.select.is_empty() || cli.rows.is_some())
+    {
+        bail!("--select and --rows only apply to csv, json and vcd outputs");
+    }
+    let pil = load_json::<PIL>(&cli.pil)?;
+    let kind = if cli.constant {
+        PolKind::Constant
+    } else {
+        PolKind::Commit
+    };
+
+    let mut pols = PolsArray::new(&pil, kind)?;
+    match extension(&cli.input).as_deref() {
+        Some("csv") => pols.import_csv(BufReader::new(File::open(&cli.input)?))?,
+        Some("json") => pols.import_json(BufReader::new(File::open(&cli.input)?))?,
+        _ => pols.load(&cli.input)?,
+    }
+
+    let selection = Selection {
+        pols: cli.select,
+        rows: cli.rows,
+    };
+    // fail on unknown names before creating the output
+    pols.select(&selection.pols)?;
+    match output.as_deref() {
+        Some("csv") => {
+            let mut w = BufWriter::new(File::create(&cli.output)?);
+            pols.export_csv(&mut w, &selection)?;
+            w.flush()?;
+        }
+        Some("json") => {
+            let mut w = BufWriter::new(File::create(&cli.output)?);
+            pols.export_json(&mut w, &selection)?;
+            w.flush()?;
+        }
+        Some("vcd") => {
+            let mut w = BufWriter::new(File::create(&cli.output)?);
+            pols.export_vcd(&mut w, &selection)?;
+            w.flush()?;
+        }
+        _ => {
+            let format = match cli.format.as_str() {
+                "raw" => FileFormat::Raw,
+                "versioned" => FileFormat::Versioned,
+                "packed" => FileFormat::Packed,
+                "packed-zstd" => FileFormat::PackedZstd { level: 3 },
+                f => bail!("unknown format {}", f),
+            };
+            pols.save_as(&cli.output, format)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,331 @@
+#![allow(non_snake_case)]
+//! Generation of typed witness structs from a PIL, the Rust counterpart of the
+//! `cmPols.Main.A[7][row]` proxies pilcom gives JS generators.
+//!
+//! For each kind of polynomial the PIL has, the code holds a struct per namespace with a
+//! column slice per polynomial, and fixed-size arrays of them for arrays, plus a struct of
+//! the namespaces that borrows the columns of a `PolsArray`:
+//!
+//! ```text
+//! let mut cm = CommitPols::new(&mut cm_pols)?;
+//! cm.Main.A[7][row] = FGL::from(3u64);
+//! ```
+//!
+//! so that a misspelled polynomial does not compile. `new` fails when the `PolsArray` is
+//! not of the PIL the code was generated from. A build script generates the code with
+//! `write_witness_structs`:
+//!
+//! ```text
+//! // build.rs
+//! let out = Path::new(&env::var("OUT_DIR").unwrap()).join("pols.rs");
+//! pilcom_rust::utils::codegen::write_witness_structs("pil/main.pil.json", &out).unwrap();
+//!
+//! // src/pols.rs
+//! include!(concat!(env!("OUT_DIR"), "/pols.rs"));
+//! ```
+use std::fmt::Write;
+use std::path::Path;
+
+use super::error::{PilError, Result};
+use super::polarray::{PolKind, PolsArray};
+use super::types::{load_json, PIL};
+
+const FR: &str = "fields::field_gl::Fr";
+
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// `name` as a Rust identifier, raw for keywords.
+fn ident(name: &str) -> Result<String> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid || ["_", "self", "Self", "super", "crate"].contains(&name) {
+        return Err(PilError::MalformedPil(format!(
+            "{} is not a valid Rust identifier",
+            name
+        )));
+    }
+    if KEYWORDS.contains(&name) {
+        Ok(format!("r#{}", name))
+    } else {
+        Ok(name.to_string())
+    }
+}
+
+/// A polynomial of a namespace: its name and the ids of its elements, one unless it is an
+/// array.
+struct Field {
+    name: String,
+    ids: Vec<usize>,
+    isArray: bool,
+}
+
+/// The namespaces of `pols`, each with its polynomials, in order of their first id.
+fn namespaces(pols: &PolsArray) -> Vec<(String, Vec<Field>)> {
+    let mut namespaces: Vec<(String, Vec<Field>)> = vec![];
+    for pol in pols.defArray.iter() {
+        let (ns, name) = pol.name.split_once('.').unwrap();
+        let fields = match namespaces.iter().position(|(n, _)| n == ns) {
+            Some(i) => &mut namespaces[i].1,
+            None => {
+                namespaces.push((ns.to_string(), vec![]));
+                &mut namespaces.last_mut().unwrap().1
+            }
+        };
+        match fields.iter_mut().find(|f| f.name == name) {
+            Some(f) => f.ids.push(pol.id),
+            None => fields.push(Field {
+                name: name.to_string(),
+                ids: vec![pol.id],
+                isArray: pol.idx.is_some(),
+            }),
+        }
+    }
+    namespaces
+}
+
+fn generate_kind(out: &mut String, pil: &PIL, kind: PolKind, cratePath: &str) -> Result<()> {
+    let pols = PolsArray::describe(pil, kind)?;
+    if pols.nPols == 0 {
+        return Ok(());
+    }
+    let (suffix, what) = match kind {
+        PolKind::Commit => ("Commit", "committed"),
+        PolKind::Constant => ("Constant", "constant"),
+    };
+    let namespaces = namespaces(&pols);
+
+    // `write!` to a `String` does not fail
+    for (ns, fields) in namespaces.iter() {
+        writeln!(out, "/// The {} polynomials of namespace `{}`.", what, ns).unwrap();
+        writeln!(out, "#[allow(non_snake_case)]").unwrap();
+        writeln!(out, "pub struct {}{}Pols<'a> {{", ident(ns)?, suffix).unwrap();
+        for f in fields {
+            let ty = if f.isArray {
+                format!("[&'a mut [{}]; {}]", FR, f.ids.len())
+            } else {
+                format!("&'a mut [{}]", FR)
+            };
+            writeln!(out, "    pub {}: {},", ident(&f.name)?, ty).unwrap();
+        }
+        writeln!(out, "}}\n").unwrap();
+    }
+
+    writeln!(out, "/// The {} polynomials, by namespace.", what).unwrap();
+    writeln!(out, "#[allow(non_snake_case)]").unwrap();
+    writeln!(out, "pub struct {}Pols<'a> {{", suffix).unwrap();
+    for (ns, _) in namespaces.iter() {
+        writeln!(
+            out,
+            "    pub {}: {}{}Pols<'a>,",
+            ident(ns)?,
+            ident(ns)?,
+            suffix
+        )
+        .unwrap();
+    }
+    writeln!(out, "}}\n").unwrap();
+
+    writeln!(
+        out,
+        "/// Name and degree of every {} polynomial, by id.",
+        what
+    )
+    .unwrap();
+    let columns = format!("{}_COLUMNS", suffix.to_uppercase());
+    writeln!(out, "pub const {}: &[(&str, usize)] = &[", columns).unwrap();
+    for c in pols.columns() {
+        writeln!(out, "    ({:?}, {}),", c.name, c.polDeg).unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+
+    writeln!(out, "impl<'a> {}Pols<'a> {{", suffix).unwrap();
+    writeln!(
+        out,
+        "    /// Borrow the columns of `pols`, which must hold the {} polynomials of the PIL",
+        what
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    /// this was generated from. Fails unless its layout stores them contiguously."
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    pub fn new(\n        pols: &'a mut {p}::utils::polarray::PolsArray,\n    ) -> {p}::utils::error::Result<Self> {{",
+        p = cratePath
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        pols.check_columns({}::utils::polarray::PolKind::{}, {})?;",
+        cratePath, suffix, columns
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        let mut columns: Vec<Option<&'a mut [{fr}]>> = pols
+            .columns_mut()
+            .ok_or_else(|| {{
+                {p}::utils::error::PilError::Layout(
+                    \"the columns are not stored contiguously\".to_string(),
+                )
+            }})?
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut take = |id: usize| columns[id].take().unwrap();",
+        fr = FR,
+        p = cratePath
+    )
+    .unwrap();
+    writeln!(out, "        Ok({}Pols {{", suffix).unwrap();
+    for (ns, fields) in namespaces.iter() {
+        writeln!(
+            out,
+            "            {}: {}{}Pols {{",
+            ident(ns)?,
+            ident(ns)?,
+            suffix
+        )
+        .unwrap();
+        for f in fields {
+            let takes: Vec<String> = f.ids.iter().map(|id| format!("take({})", id)).collect();
+            let value = if f.isArray {
+                format!("[{}]", takes.join(", "))
+            } else {
+                takes[0].clone()
+            };
+            writeln!(out, "                {}: {},", ident(&f.name)?, value).unwrap();
+        }
+        writeln!(out, "            }},").unwrap();
+    }
+    writeln!(out, "        }})\n    }}\n}}\n").unwrap();
+    Ok(())
+}
+
+/// The typed witness structs of `pil`, as Rust source. `cratePath` is the path of this
+/// crate where the code is compiled, `pilcom_rust` out of it.
+pub fn witness_structs(pil: &PIL, cratePath: &str) -> Result<String> {
+    let mut out = String::from("// Generated by pilcom-rust from a PIL, do not edit.\n\n");
+    generate_kind(&mut out, pil, PolKind::Commit, cratePath)?;
+    generate_kind(&mut out, pil, PolKind::Constant, cratePath)?;
+    out.truncate(out.trim_end().len() + 1);
+    Ok(out)
+}
+
+/// Write the typed witness structs of the PIL in `pilFile` to `outFile`, and have cargo
+/// run the build script again when the PIL changes. For build scripts.
+pub fn write_witness_structs(pilFile: &str, outFile: impl AsRef<Path>) -> anyhow::Result<()> {
+    println!("cargo:rerun-if-changed={}", pilFile);
+    let pil = load_json::<PIL>(pilFile)?;
+    std::fs::write(outFile, witness_structs(&pil, "pilcom_rust")?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polarray::Layout;
+    use crate::utils::types::read_json;
+    use fields::field_gl::Fr as FGL;
+
+    // not every field is used here
+    #[allow(dead_code)]
+    mod generated {
+        include!("codegen_expected.rs");
+    }
+
+    // namespace Global(4);
+    //     pol constant L1;
+    // namespace Main(4);
+    //     pol commit a, in;
+    //     pol commit b[2];
+    // namespace Small(2);
+    //     pol commit c;
+    const PIL_JSON: &str = r#"{
+        "nCommitments": 5, "nQ": 0, "nIm": 0, "nConstants": 1, "publics": [],
+        "references": {
+            "Global.L1": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.in": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": false },
+            "Main.b": { "type": "cmP", "id": 2, "polDeg": 4, "isArray": true, "len": 2 },
+            "Small.c": { "type": "cmP", "id": 4, "polDeg": 2, "isArray": false }
+        },
+        "expressions": [], "polIdentities": [], "plookupIdentities": []
+    }"#;
+
+    fn pil() -> PIL {
+        read_json::<PIL>(PIL_JSON.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_generated_is_current() {
+        assert_eq!(
+            witness_structs(&pil(), "crate").unwrap(),
+            include_str!("codegen_expected.rs")
+        );
+    }
+
+    #[test]
+    fn test_generated_structs() {
+        let pil = pil();
+        let mut cm_pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        let cm = generated::CommitPols::new(&mut cm_pols).unwrap();
+        cm.Main.b[1][3] = FGL::from(7u64);
+        cm.Main.r#in[0] = FGL::from(1u64);
+        assert_eq!(cm.Small.c.len(), 2);
+        assert_eq!(cm_pols.value(3, 3), FGL::from(7u64));
+        assert_eq!(cm_pols.value(1, 0), FGL::from(1u64));
+
+        let mut const_pols =
+            PolsArray::new_with_layout(&pil, PolKind::Constant, Layout::ColMajor).unwrap();
+        generated::ConstantPols::new(&mut const_pols)
+            .unwrap()
+            .Global
+            .L1[0] = FGL::ONE;
+        assert_eq!(const_pols.value(0, 0), FGL::ONE);
+
+        // another PIL
+        let other = read_json::<PIL>(PIL_JSON.replace("Small.c", "Small.d")).unwrap();
+        let mut cm_pols = PolsArray::new(&other, PolKind::Commit).unwrap();
+        assert_eq!(
+            generated::CommitPols::new(&mut cm_pols)
+                .err()
+                .unwrap()
+                .to_string(),
+            "witness of a different PIL: pol 4 is Small.c of 2 rows, Small.d of 2 rows in the PIL"
+        );
+        let mut const_pols = PolsArray::new(&other, PolKind::Constant).unwrap();
+        assert!(generated::CommitPols::new(&mut const_pols).is_err());
+
+        let mut const_pols =
+            PolsArray::new_with_layout(&pil, PolKind::Constant, Layout::RowMajor).unwrap();
+        assert!(matches!(
+            generated::ConstantPols::new(&mut const_pols),
+            Err(PilError::Layout(_))
+        ));
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(ident("A").unwrap(), "A");
+        assert_eq!(ident("type").unwrap(), "r#type");
+        assert!(ident("self").is_err());
+        assert!(ident("1a").is_err());
+        let bad = read_json::<PIL>(PIL_JSON.replace("Small.c", "Small.c-d")).unwrap();
+        assert!(matches!(
+            witness_structs(&bad, "crate"),
+            Err(PilError::MalformedPil(_))
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+// Generated by pilcom-rust from a PIL, do not edit.
+
+/// The committed polynomials of namespace `Main`.
+#[allow(non_snake_case)]
+pub struct MainCommitPols<'a> {
+    pub a: &'a mut [fields::field_gl::Fr],
+    pub r#in: &'a mut [fields::field_gl::Fr],
+    pub b: [&'a mut [fields::field_gl::Fr]; 2],
+}
+
+/// The committed polynomials of namespace `Small`.
+#[allow(non_snake_case)]
+pub struct SmallCommitPols<'a> {
+    pub c: &'a mut [fields::field_gl::Fr],
+}
+
+/// The committed polynomials, by namespace.
+#[allow(non_snake_case)]
+pub struct CommitPols<'a> {
+    pub Main: MainCommitPols<'a>,
+    pub Small: SmallCommitPols<'a>,
+}
+
+/// Name and degree of every committed polynomial, by id.
+pub const COMMIT_COLUMNS: &[(&str, usize)] = &[
+    ("Main.a", 4),
+    ("Main.in", 4),
+    ("Main.b[0]", 4),
+    ("Main.b[1]", 4),
+    ("Small.c", 2),
+];
+
+impl<'a> CommitPols<'a> {
+    /// Borrow the columns of `pols`, which must hold the committed polynomials of the PIL
+    /// this was generated from. Fails unless its layout stores them contiguously.
+    pub fn new(
+        pols: &'a mut crate::utils::polarray::PolsArray,
+    ) -> crate::utils::error::Result<Self> {
+        pols.check_columns(crate::utils::polarray::PolKind::Commit, COMMIT_COLUMNS)?;
+        let mut columns: Vec<Option<&'a mut [fields::field_gl::Fr]>> = pols
+            .columns_mut()
+            .ok_or_else(|| {
+                crate::utils::error::PilError::Layout(
+                    "the columns are not stored contiguously".to_string(),
+                )
+            })?
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut take = |id: usize| columns[id].take().unwrap();
+        Ok(CommitPols {
+            Main: MainCommitPols {
+                a: take(0),
+                r#in: take(1),
+                b: [take(2), take(3)],
+            },
+            Small: SmallCommitPols {
+                c: take(4),
+            },
+        })
+    }
+}
+
+/// The constant polynomials of namespace `Global`.
+#[allow(non_snake_case)]
+pub struct GlobalConstantPols<'a> {
+    pub L1: &'a mut [fields::field_gl::Fr],
+}
+
+/// The constant polynomials, by namespace.
+#[allow(non_snake_case)]
+pub struct ConstantPols<'a> {
+    pub Global: GlobalConstantPols<'a>,
+}
+
+/// Name and degree of every constant polynomial, by id.
+pub const CONSTANT_COLUMNS: &[(&str, usize)] = &[
+    ("Global.L1", 4),
+];
+
+impl<'a> ConstantPols<'a> {
+    /// Borrow the columns of `pols`, which must hold the constant polynomials of the PIL
+    /// this was generated from. Fails unless its layout stores them contiguously.
+    pub fn new(
+        pols: &'a mut crate::utils::polarray::PolsArray,
+    ) -> crate::utils::error::Result<Self> {
+        pols.check_columns(crate::utils::polarray::PolKind::Constant, CONSTANT_COLUMNS)?;
+        let mut columns: Vec<Option<&'a mut [fields::field_gl::Fr]>> = pols
+            .columns_mut()
+            .ok_or_else(|| {
+                crate::utils::error::PilError::Layout(
+                    "the columns are not stored contiguously".to_string(),
+                )
+            })?
+            .into_iter()
+            .map(Some)
+            .collect();
+        let mut take = |id: usize| columns[id].take().unwrap();
+        Ok(ConstantPols {
+            Global: GlobalConstantPols {
+                L1: take(0),
+            },
+        })
+    }
+}
@@ -0,0 +1,170 @@
+//! Element-wise operations on whole columns, the kernels of `PilVerify::eval`.
+//!
+//! When the crate is built for x86_64 with AVX2 or AVX-512 enabled (e.g.
+//! `RUSTFLAGS='-C target-feature=+avx2'`) they go through the packing `fields` recommends
+//! for Goldilocks, `<FrRepr as Packable>::Packing`; otherwise they are plain scalar loops.
+//! The packed path is tested by running the tests with the same `RUSTFLAGS`.
+use fields::field_gl::Fr as FGL;
+
+#[cfg(all(target_arch = "x86_64", target_feature = "avx2"))]
+mod packed {
+    use super::FGL;
+    use fields::field_gl::{FrRepr, MODULUS};
+    use fields::packable::Packable;
+    use fields::packed::PackedField;
+    use fields::PrimeField;
+
+    type P = <FrRepr as Packable>::Packing;
+
+    // `Fr` is a transparent wrapper over `FrRepr`, and the packed types work on `FrRepr`.
+    fn as_repr(a: &[FGL]) -> &[FrRepr] {
+        unsafe { std::slice::from_raw_parts(a.as_ptr().cast(), a.len()) }
+    }
+
+    fn as_repr_mut(a: &mut [FGL]) -> &mut [FrRepr] {
+        unsafe { std::slice::from_raw_parts_mut(a.as_mut_ptr().cast(), a.len()) }
+    }
+
+    /// The packed arithmetic, as in plonky2, may leave values in [p, 2^64), which `Fr`
+    /// does not expect.
+    #[inline(always)]
+    fn canonical(mut x: P) -> P {
+        for v in x.as_slice_mut() {
+            if v.0[0] >= MODULUS.0[0] {
+                v.0[0] -= MODULUS.0[0];
+            }
+        }
+        x
+    }
+
+    /// Run `op` over packed chunks of `a` and `b`, and `scalar` over the remainder.
+    fn zip_with(
+        a: &[FGL],
+        b: &[FGL],
+        op: impl Fn(P, P) -> P,
+        scalar: impl Fn(FGL, FGL) -> FGL,
+    ) -> Vec<FGL> {
+        assert_eq!(a.len(), b.len());
+        let mut r = vec![FGL::ZERO; a.len()];
+        let split = a.len() - a.len() % P::WIDTH;
+        let rp = P::pack_slice_mut(as_repr_mut(&mut r[..split]));
+        let ap = P::pack_slice(as_repr(&a[..split]));
+        let bp = P::pack_slice(as_repr(&b[..split]));
+        for ((r, a), b) in rp.iter_mut().zip(ap).zip(bp) {
+            *r = canonical(op(*a, *b));
+        }
+        for i in split..a.len() {
+            r[i] = scalar(a[i], b[i]);
+        }
+        r
+    }
+
+    fn map(a: &[FGL], op: impl Fn(P) -> P, scalar: impl Fn(FGL) -> FGL) -> Vec<FGL> {
+        let mut r = vec![FGL::ZERO; a.len()];
+        let split = a.len() - a.len() % P::WIDTH;
+        let rp = P::pack_slice_mut(as_repr_mut(&mut r[..split]));
+        let ap = P::pack_slice(as_repr(&a[..split]));
+        for (r, a) in rp.iter_mut().zip(ap) {
+            *r = canonical(op(*a));
+        }
+        for i in split..a.len() {
+            r[i] = scalar(a[i]);
+        }
+        r
+    }
+
+    pub fn add(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        zip_with(a, b, |x, y| x + y, |x, y| x + y)
+    }
+
+    pub fn sub(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        zip_with(a, b, |x, y| x - y, |x, y| x - y)
+    }
+
+    pub fn mul(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        // `Fr` holds Montgomery forms, which `*` of the packed types would take as canonical
+        zip_with(a, b, |x, y| x.mont_mul(y), |x, y| x * y)
+    }
+
+    pub fn neg(a: &[FGL]) -> Vec<FGL> {
+        map(a, |x| -x, |x| -x)
+    }
+
+    pub fn add_scalar(a: &[FGL], c: FGL) -> Vec<FGL> {
+        let cp = P::from(c.0);
+        map(a, |x| x + cp, |x| x + c)
+    }
+
+    pub fn mul_scalar(a: &[FGL], c: FGL) -> Vec<FGL> {
+        // one operand in canonical form keeps the product in Montgomery form
+        let cp = P::from(c.into_repr());
+        map(a, |x| x * cp, |x| x * c)
+    }
+}
+
+#[cfg(not(all(target_arch = "x86_64", target_feature = "avx2")))]
+mod packed {
+    use super::FGL;
+
+    pub fn add(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        assert_eq!(a.len(), b.len());
+        a.iter().zip(b).map(|(x, y)| *x + *y).collect()
+    }
+
+    pub fn sub(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        assert_eq!(a.len(), b.len());
+        a.iter().zip(b).map(|(x, y)| *x - *y).collect()
+    }
+
+    pub fn mul(a: &[FGL], b: &[FGL]) -> Vec<FGL> {
+        assert_eq!(a.len(), b.len());
+        a.iter().zip(b).map(|(x, y)| *x * *y).collect()
+    }
+
+    pub fn neg(a: &[FGL]) -> Vec<FGL> {
+        a.iter().map(|x| -*x).collect()
+    }
+
+    pub fn add_scalar(a: &[FGL], c: FGL) -> Vec<FGL> {
+        a.iter().map(|x| *x + c).collect()
+    }
+
+    pub fn mul_scalar(a: &[FGL], c: FGL) -> Vec<FGL> {
+        a.iter().map(|x| *x * c).collect()
+    }
+}
+
+pub use packed::{add, add_scalar, mul, mul_scalar, neg, sub};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rand;
+
+    fn random_column(n: usize) -> Vec<FGL> {
+        let mut rng = rand::thread_rng();
+        (0..n).map(|_| FGL::rand(&mut rng)).collect()
+    }
+
+    #[test]
+    fn test_column_ops() {
+        // odd length, so that both the packed chunks and the scalar tail are exercised
+        let n = 37;
+        let mut a = random_column(n);
+        let b = random_column(n);
+        a[0] = FGL::ZERO;
+        a[1] = -FGL::ONE;
+        let c = FGL::from(18446744069414584320u64);
+
+        let (ra, rs, rm, rn) = (add(&a, &b), sub(&a, &b), mul(&a, &b), neg(&a));
+        let (rac, rmc) = (add_scalar(&a, c), mul_scalar(&a, c));
+        for i in 0..n {
+            assert_eq!(ra[i], a[i] + b[i]);
+            assert_eq!(rs[i], a[i] - b[i]);
+            assert_eq!(rm[i], a[i] * b[i]);
+            assert_eq!(rn[i], -a[i]);
+            assert_eq!(rac[i], a[i] + c);
+            assert_eq!(rmc[i], a[i] * c);
+        }
+    }
+}
@@ -0,0 +1,312 @@
+#![allow(non_snake_case)]
+//! The Merkle tree of the constant polynomials, pil-stark's `buildconsttree`.
+//!
+//! Every constant polynomial is extended from `2^nBits` to `2^nBitsExt` rows with the
+//! coset LDE of `fields::fft::extend_pol`, and the tree of the `verificationHashType` of the
+//! `StarkStruct` commits to the rows of the extended polynomials. Its root is the
+//! verification key, `{"constRoot": ...}`, and the tree is saved in the `.consttree`
+//! layout pil-stark reads.
+use fields::bellman_ce::pairing::bn256::Fr;
+use fields::ff::PrimeField;
+use fields::fft::extend_pol;
+use rayon::prelude::*;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+
+use super::error::{PilError, Result};
+use super::merklehash::MerkleTreeGL;
+use super::merklehash_bn128::MerkleTreeBN128;
+use super::polarray::PolsArray;
+use super::traits::MerkleTree;
+use super::types::StarkStruct;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstTree {
+    GL(MerkleTreeGL),
+    BN128(MerkleTreeBN128),
+}
+
+/// The decimal digits of an element.
+fn to_decimal(v: &Fr) -> String {
+    let mut limbs = v.into_repr().as_ref().to_vec();
+    let mut digits = vec![];
+    // 10^19 is the largest power of 10 below 2^64
+    const TEN19: u128 = 10_000_000_000_000_000_000;
+    while limbs.iter().any(|l| *l != 0) {
+        let mut rem = 0u128;
+        for l in limbs.iter_mut().rev() {
+            let cur = (rem << 64) | *l as u128;
+            *l = (cur / TEN19) as u64;
+            rem = cur % TEN19;
+        }
+        digits.push(rem as u64);
+    }
+    match digits.split_last() {
+        None => "0".to_string(),
+        Some((first, rest)) => {
+            let mut s = first.to_string();
+            for d in rest.iter().rev() {
+                s.push_str(&format!("{:019}", d));
+            }
+            s
+        }
+    }
+}
+
+impl ConstTree {
+    /// Extend `constPols` to `2^nBitsExt` rows and build their tree. Every polynomial must
+    /// have `2^nBits` rows.
+    pub fn build(constPols: &PolsArray, starkStruct: &StarkStruct) -> Result<Self> {
+        let (nBits, nBitsExt) = (starkStruct.nBits, starkStruct.nBitsExt);
+        if nBitsExt < nBits {
+            return Err(PilError::StarkStruct(format!(
+                "nBitsExt {} is below nBits {}",
+                nBitsExt, nBits
+            )));
+        }
+        if !["GL", "BN128"].contains(&starkStruct.verificationHashType.as_str()) {
+            return Err(PilError::StarkStruct(format!(
+                "unknown verificationHashType {}",
+                starkStruct.verificationHashType
+            )));
+        }
+        for c in constPols.columns() {
+            if c.polDeg != 1 << nBits {
+                return Err(PilError::SizeMismatch {
+                    expected: 1 << nBits,
+                    actual: c.polDeg,
+                });
+            }
+        }
+
+        let nPols = constPols.nPols;
+        let nExt = 1 << nBitsExt;
+        let extended: Vec<_> = (0..nPols)
+            .into_par_iter()
+            .map(|id| extend_pol(&constPols.column(id), nBitsExt))
+            .collect();
+        let mut elements = vec![Default::default(); nPols * nExt];
+        elements
+            .par_chunks_mut(nPols.max(1))
+            .enumerate()
+            .for_each(|(j, row)| {
+                for (v, pol) in row.iter_mut().zip(extended.iter()) {
+                    *v = pol[j];
+                }
+            });
+
+        match starkStruct.verificationHashType.as_str() {
+            "GL" => Ok(ConstTree::GL(MerkleTreeGL::merkelize(
+                elements, nPols, nExt,
+            )?)),
+            _ => Ok(ConstTree::BN128(MerkleTreeBN128::merkelize(
+                elements, nPols, nExt,
+            )?)),
+        }
+    }
+
+    /// The verification key, as pil-stark's `JSONbig.stringify(verKey, null, 1)`: the
+    /// four elements of a GL root, or the decimal value of a BN128 one.
+    pub fn verification_key(&self) -> String {
+        let root = match self {
+            ConstTree::GL(tree) => {
+                let values: Vec<String> = tree
+                    .root()
+                    .iter()
+                    .map(|v| format!("  {}", v.as_int()))
+                    .collect();
+                format!("[\n{}\n ]", values.join(",\n"))
+            }
+            ConstTree::BN128(tree) => to_decimal(&tree.root()),
+        };
+        format!("{{\n \"constRoot\": {}\n}}", root)
+    }
+
+    pub fn save(&self, fileName: &str) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(fileName)?);
+        match self {
+            ConstTree::GL(tree) => tree.write(&mut writer)?,
+            ConstTree::BN128(tree) => tree.write(&mut writer)?,
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a tree `save` wrote, of the hash `verificationHashType`.
+    pub fn load(fileName: &str, verificationHashType: &str) -> Result<Self> {
+        let mut reader = BufReader::new(File::open(fileName)?);
+        match verificationHashType {
+            "GL" => Ok(ConstTree::GL(MerkleTreeGL::read(&mut reader)?)),
+            "BN128" => Ok(ConstTree::BN128(MerkleTreeBN128::read(&mut reader)?)),
+            t => Err(PilError::StarkStruct(format!(
+                "unknown verificationHashType {}",
+                t
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polarray::PolKind;
+    use crate::utils::types::{read_json, PIL};
+    use crate::utils::witness_file::hex;
+    use fields::field_gl::Fr as FGL;
+    use fields::Field;
+    use sha2::{Digest, Sha256};
+
+    // namespace Global(4);
+    //     pol constant L1, LINE;
+    fn const_pols() -> PolsArray {
+        let pil = read_json::<PIL>(
+            r#"{
+            "nCommitments": 0, "nQ": 0, "nIm": 0, "nConstants": 2, "publics": [],
+            "references": {
+                "Global.L1": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+                "Global.LINE": { "type": "constP", "id": 1, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Constant).unwrap();
+        pols.set_value(0, 0, FGL::ONE);
+        // 3x + 1 on the subgroup
+        for j in 0..4 {
+            let x = fields::fft::root(2).pow([j as u64]);
+            pols.set_value(1, j, FGL::from(3u64) * x + FGL::ONE);
+        }
+        pols
+    }
+
+    fn stark_struct(verificationHashType: &str) -> StarkStruct {
+        StarkStruct {
+            nBits: 2,
+            nBitsExt: 4,
+            nQueries: 8,
+            verificationHashType: verificationHashType.to_string(),
+            steps: vec![],
+        }
+    }
+
+    #[test]
+    fn test_build() {
+        let pols = const_pols();
+        let tree = match ConstTree::build(&pols, &stark_struct("GL")).unwrap() {
+            ConstTree::GL(tree) => tree,
+            _ => panic!("a GL tree expected"),
+        };
+        assert_eq!((tree.width, tree.height), (2, 16));
+        let column: Vec<FGL> = (0..16).map(|j| tree.get_element(j, 1)).collect();
+        assert_eq!(column, extend_pol(&pols.column(1), 4));
+
+        // and on the coset
+        let x = FGL::from(fields::fft::SHIFT) * fields::fft::root(4).pow([5u64]);
+        assert_eq!(tree.get_element(5, 1), FGL::from(3u64) * x + FGL::ONE);
+
+        let mut bad = stark_struct("GL");
+        bad.nBits = 3;
+        assert!(matches!(
+            ConstTree::build(&pols, &bad),
+            Err(PilError::SizeMismatch { .. })
+        ));
+        bad = stark_struct("GL");
+        bad.nBitsExt = 1;
+        assert!(matches!(
+            ConstTree::build(&pols, &bad),
+            Err(PilError::StarkStruct(_))
+        ));
+        assert!(matches!(
+            ConstTree::build(&pols, &stark_struct("SHA")),
+            Err(PilError::StarkStruct(_))
+        ));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let pols = const_pols();
+        for hashType in ["GL", "BN128"] {
+            let tree = ConstTree::build(&pols, &stark_struct(hashType)).unwrap();
+            let file = std::env::temp_dir().join(format!(
+                "const_tree_{}_{}.consttree",
+                hashType,
+                std::process::id()
+            ));
+            let fileName = file.to_str().unwrap();
+            tree.save(fileName).unwrap();
+            assert_eq!(ConstTree::load(fileName, hashType).unwrap(), tree);
+            std::fs::remove_file(fileName).unwrap();
+        }
+    }
+
+    // computed with a Python transcription of pil-stark's buildconsttree (extendPol,
+    // merkelize, writeToFile and the verkey JSON) from this constant polynomials file and
+    // starkstruct.json
+    #[test]
+    fn test_pil_stark_const_tree() {
+        let values: [u64; 8] = [1, 5, 0, 0xffffffff00000000, 0, 3, 0, 7];
+        let dir = std::env::temp_dir();
+        let constFile = dir.join(format!("const_tree_{}.const", std::process::id()));
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        std::fs::write(&constFile, bytes).unwrap();
+        let mut pols = const_pols();
+        pols.load(constFile.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&constFile).unwrap();
+
+        let vectors = [
+            (
+                "GL",
+                1264,
+                "3c13f8f11ed3f7fa86bb41a6b0035cbea0f5e2f46225a3ee1ead16a991355a5a",
+                "{\n \"constRoot\": [\n  4250891907932665160,\n  6650708172828096593,\n  \
+                 9196870549293537514,\n  6858702421293631753\n ]\n}",
+            ),
+            (
+                "BN128",
+                816,
+                "1267d02eff6c9508d547d8542b41f1d7c8de3f8dcea0df97c31af1623425d24a",
+                "{\n \"constRoot\": \
+                 16042147523162108663475664114088134632478854806556093896127756680604639677050\n}",
+            ),
+        ];
+        for (hashType, len, sha, verKey) in vectors {
+            let starkStruct = read_json::<StarkStruct>(format!(
+                r#"{{ "nBits": 2, "nBitsExt": 4, "nQueries": 8, "verificationHashType": "{}",
+                "steps": [{{ "nBits": 4 }}, {{ "nBits": 2 }}] }}"#,
+                hashType
+            ))
+            .unwrap();
+            let tree = ConstTree::build(&pols, &starkStruct).unwrap();
+            assert_eq!(tree.verification_key(), verKey);
+
+            let file = dir.join(format!(
+                "const_tree_fixture_{}_{}.consttree",
+                hashType,
+                std::process::id()
+            ));
+            let fileName = file.to_str().unwrap();
+            tree.save(fileName).unwrap();
+            let buff = std::fs::read(fileName).unwrap();
+            std::fs::remove_file(fileName).unwrap();
+            assert_eq!(buff.len(), len);
+            assert_eq!(hex(&Sha256::digest(&buff)), sha);
+        }
+    }
+
+    #[test]
+    fn test_verification_key() {
+        let tree = ConstTree::GL(MerkleTreeGL::merkelize(vec![], 0, 0).unwrap());
+        assert_eq!(
+            tree.verification_key(),
+            "{\n \"constRoot\": [\n  0,\n  0,\n  0,\n  0\n ]\n}"
+        );
+
+        assert_eq!(to_decimal(&Fr::from_str("0").unwrap()), "0");
+        let q1 = "21888242871839275222246405745257275088548364400416034343698204186575808495616";
+        assert_eq!(to_decimal(&Fr::from_str(q1).unwrap()), q1);
+        let v = "10000000000000000000000000000000000000";
+        assert_eq!(to_decimal(&Fr::from_str(v).unwrap()), v);
+    }
+}
@@ -0,0 +1,130 @@
+#![allow(non_snake_case)]
+use fields::field_gl::{Fr as FGL, MODULUS};
+use std::fmt;
+use std::str::FromStr;
+
+use super::error::PilError;
+
+/// The range a polynomial declares for its values, pilcom's `elementType`.
+///
+/// Signed types take the negative values from the top of the field, `-x` being `p - x`, as
+/// the state machines write them.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum ElementType {
+    #[default]
+    Field,
+    U8,
+    U16,
+    U32,
+    S8,
+    S16,
+    S32,
+    S64,
+}
+
+impl ElementType {
+    /// Whether the field element `v` is in the range of this type.
+    #[inline(always)]
+    pub fn contains(&self, v: FGL) -> bool {
+        let v = v.as_int();
+        let (max, min) = match self {
+            ElementType::Field => return true,
+            ElementType::U8 => return v <= u8::MAX as u64,
+            ElementType::U16 => return v <= u16::MAX as u64,
+            ElementType::U32 => return v <= u32::MAX as u64,
+            ElementType::S8 => (i8::MAX as u64, i8::MIN.unsigned_abs() as u64),
+            ElementType::S16 => (i16::MAX as u64, i16::MIN.unsigned_abs() as u64),
+            ElementType::S32 => (i32::MAX as u64, i32::MIN.unsigned_abs() as u64),
+            ElementType::S64 => (i64::MAX as u64, i64::MIN.unsigned_abs()),
+        };
+        v <= max || v >= MODULUS.0[0] - min
+    }
+}
+
+impl FromStr for ElementType {
+    type Err = PilError;
+
+    fn from_str(s: &str) -> Result<Self, PilError> {
+        match s {
+            "field" => Ok(ElementType::Field),
+            "u8" => Ok(ElementType::U8),
+            "u16" => Ok(ElementType::U16),
+            "u32" => Ok(ElementType::U32),
+            "s8" => Ok(ElementType::S8),
+            "s16" => Ok(ElementType::S16),
+            "s32" => Ok(ElementType::S32),
+            "s64" => Ok(ElementType::S64),
+            _ => Err(PilError::MalformedPil(format!(
+                "Unknown element type: {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ElementType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            ElementType::Field => "field",
+            ElementType::U8 => "u8",
+            ElementType::U16 => "u16",
+            ElementType::U32 => "u32",
+            ElementType::S8 => "s8",
+            ElementType::S16 => "s16",
+            ElementType::S32 => "s32",
+            ElementType::S64 => "s64",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A value out of the range of the `elementType` of its polynomial.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeViolation {
+    pub pol: usize,
+    /// Reference name of the polynomial, with its index for arrays.
+    pub name: String,
+    pub row: usize,
+    /// The value, in canonical form.
+    pub value: u64,
+    pub elementType: ElementType,
+}
+
+impl fmt::Display for TypeViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} w={}: {} is not a {}",
+            self.name, self.row, self.value, self.elementType
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_type_ranges() {
+        let neg = |x: u64| -FGL::from(x);
+        assert!(ElementType::U8.contains(FGL::from(255u64)));
+        assert!(!ElementType::U8.contains(FGL::from(256u64)));
+        assert!(!ElementType::U8.contains(neg(1)));
+        assert!(ElementType::U16.contains(FGL::from(65535u64)));
+        assert!(!ElementType::U32.contains(FGL::from(1u64 << 32)));
+
+        assert!(ElementType::S8.contains(FGL::from(127u64)));
+        assert!(!ElementType::S8.contains(FGL::from(128u64)));
+        assert!(ElementType::S8.contains(neg(128)));
+        assert!(!ElementType::S8.contains(neg(129)));
+        assert!(ElementType::S32.contains(neg(1 << 31)));
+        assert!(!ElementType::S32.contains(FGL::from(1u64 << 31)));
+        // every field element is an s64
+        assert!(ElementType::S64.contains(neg(1 << 63)));
+        assert!(ElementType::S64.contains(FGL::from(1u64 << 63)));
+        assert!(ElementType::Field.contains(neg(1)));
+
+        assert_eq!("s16".parse::<ElementType>().unwrap(), ElementType::S16);
+        assert!("u128".parse::<ElementType>().is_err());
+    }
+}
@@ -0,0 +1,106 @@
+use std::fmt;
+
+use super::element_type::TypeViolation;
+
+/// Errors raised while loading PIL artifacts and verifying witnesses against them.
+#[derive(Debug)]
+pub enum PilError {
+    /// The PIL, or one of its references, expressions or identities, is not well formed.
+    MalformedPil(String),
+    /// An expression uses an operation the evaluator does not know.
+    UnknownOp(String),
+    /// An id points past the end of the table it indexes.
+    OutOfRange {
+        kind: &'static str,
+        id: usize,
+        len: usize,
+    },
+    Io(std::io::Error),
+    /// A buffer or file does not have the size the PIL implies.
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    /// Values out of the range of their `elementType`.
+    ElementType(Vec<TypeViolation>),
+    /// A witness file was written for another PIL than the one it is loaded with.
+    WrongPil(String),
+    /// A witness file is damaged: bad header or content hash.
+    CorruptFile(String),
+    /// A polynomial name that is not in the PIL.
+    UnknownPol(String),
+    /// A `StarkStruct` that cannot be used with the PIL, or at all.
+    StarkStruct(String),
+    /// A CSV or JSON text of polynomial values that cannot be read.
+    Parse(String),
+    /// An operation the storage layout of a `PolsArray` does not support.
+    Layout(String),
+    /// A Merkle tree file is damaged: truncated, or with values that are not elements.
+    CorruptTree(String),
+}
+
+pub type Result<T> = std::result::Result<T, PilError>;
+
+impl fmt::Display for PilError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PilError::MalformedPil(msg) => write!(f, "malformed PIL: {}", msg),
+            PilError::UnknownOp(op) => write!(f, "unknown operation: {}", op),
+            PilError::OutOfRange { kind, id, len } => {
+                write!(f, "{} id {} out of range (len {})", kind, id, len)
+            }
+            PilError::Io(e) => write!(f, "I/O error: {}", e),
+            PilError::SizeMismatch { expected, actual } => {
+                write!(f, "size mismatch: expected {}, got {}", expected, actual)
+            }
+            PilError::ElementType(violations) => {
+                write!(f, "{} values out of range", violations.len())?;
+                for v in violations.iter().take(10) {
+                    write!(f, "\n  {}", v)?;
+                }
+                if violations.len() > 10 {
+                    write!(f, "\n  ...")?;
+                }
+                Ok(())
+            }
+            PilError::WrongPil(msg) => write!(f, "witness of a different PIL: {}", msg),
+            PilError::CorruptFile(msg) => write!(f, "corrupt witness file: {}", msg),
+            PilError::UnknownPol(name) => write!(f, "unknown polynomial: {}", name),
+            PilError::StarkStruct(msg) => write!(f, "invalid stark struct: {}", msg),
+            PilError::Parse(msg) => write!(f, "parse error: {}", msg),
+            PilError::Layout(msg) => write!(f, "unsupported layout: {}", msg),
+            PilError::CorruptTree(msg) => write!(f, "corrupt tree file: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PilError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PilError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for PilError {
+    fn from(e: std::io::Error) -> Self {
+        PilError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for PilError {
+    fn from(e: serde_json::Error) -> Self {
+        PilError::MalformedPil(e.to_string())
+    }
+}
+
+/// Look `id` up in `v`, reporting an out of range `kind` id instead of panicking.
+#[inline(always)]
+pub fn get_checked<'a, T>(v: &'a [T], id: usize, kind: &'static str) -> Result<&'a T> {
+    v.get(id).ok_or(PilError::OutOfRange {
+        kind,
+        id,
+        len: v.len(),
+    })
+}
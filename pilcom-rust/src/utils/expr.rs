@@ -0,0 +1,296 @@
+#![allow(non_snake_case)]
+use fields::field_gl::Fr as FGL;
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+
+use super::error::{PilError, Result};
+use super::types::{parse_pil_number, Expression};
+
+/// A PIL expression node, checked when it is built from its JSON shape (`Expression`).
+///
+/// Besides the operation it keeps the annotations pilcom and pil-stark attach to every
+/// node, and how the node was spelled where `expr` does not imply it, so converting back
+/// gives the same JSON.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(try_from = "Expression", into = "Expression")]
+pub struct TypedExpression {
+    pub expr: Expr,
+    pub deg: usize,
+    pub keep: Option<bool>,
+    pub keep2ns: Option<bool>,
+    pub idQ: Option<usize>,
+    /// A `cm`, `const` or `exp` reference written without `next`, which reads as `false`.
+    pub omit_next: bool,
+    /// The `value` of a number as written, e.g. negative or hexadecimal, when it is not
+    /// the canonical decimal string of the element it is reduced to.
+    pub raw_value: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Expr {
+    Add(Box<TypedExpression>, Box<TypedExpression>),
+    Sub(Box<TypedExpression>, Box<TypedExpression>),
+    Mul(Box<TypedExpression>, Box<TypedExpression>),
+    /// `e + c`, as produced by pil-stark's simplification pass.
+    AddC(Box<TypedExpression>, i64),
+    /// `e * c`, as produced by pil-stark's simplification pass.
+    MulC(Box<TypedExpression>, i64),
+    Neg(Box<TypedExpression>),
+    Cm {
+        id: usize,
+        next: bool,
+    },
+    Const {
+        id: usize,
+        next: bool,
+    },
+    Exp {
+        id: usize,
+        next: bool,
+    },
+    Number(FGL),
+    Public(usize),
+}
+
+impl TypedExpression {
+    pub fn new(expr: Expr, deg: usize) -> Self {
+        TypedExpression {
+            expr,
+            deg,
+            keep: None,
+            keep2ns: None,
+            idQ: None,
+            omit_next: false,
+            raw_value: None,
+        }
+    }
+
+    /// The JSON `op` of this node.
+    pub fn op(&self) -> &'static str {
+        match &self.expr {
+            Expr::Add(..) => "add",
+            Expr::Sub(..) => "sub",
+            Expr::Mul(..) => "mul",
+            Expr::AddC(..) => "addc",
+            Expr::MulC(..) => "mulc",
+            Expr::Neg(..) => "neg",
+            Expr::Cm { .. } => "cm",
+            Expr::Const { .. } => "const",
+            Expr::Exp { .. } => "exp",
+            Expr::Number(..) => "number",
+            Expr::Public(..) => "public",
+        }
+    }
+
+    /// The direct operands of this node, empty for leaves.
+    pub fn operands(&self) -> Vec<&TypedExpression> {
+        match &self.expr {
+            Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) => vec![a, b],
+            Expr::AddC(a, _) | Expr::MulC(a, _) | Expr::Neg(a) => vec![a],
+            _ => vec![],
+        }
+    }
+}
+
+fn operands(exp: &mut Expression, n: usize) -> Result<Vec<TypedExpression>> {
+    match exp.values.take() {
+        Some(values) if values.len() == n => {
+            values.into_iter().map(TypedExpression::try_from).collect()
+        }
+        _ => Err(PilError::MalformedPil(format!(
+            "{} expression expects {} operands",
+            exp.op, n
+        ))),
+    }
+}
+
+fn binary(exp: &mut Expression) -> Result<(Box<TypedExpression>, Box<TypedExpression>)> {
+    let mut values = operands(exp, 2)?;
+    let b = values.pop().unwrap();
+    let a = values.pop().unwrap();
+    Ok((Box::new(a), Box::new(b)))
+}
+
+fn unary(exp: &mut Expression) -> Result<Box<TypedExpression>> {
+    Ok(Box::new(operands(exp, 1)?.pop().unwrap()))
+}
+
+fn id(exp: &Expression) -> Result<usize> {
+    exp.id
+        .ok_or_else(|| PilError::MalformedPil(format!("{} expression without id", exp.op)))
+}
+
+fn constant(exp: &Expression) -> Result<i64> {
+    exp.const_
+        .ok_or_else(|| PilError::MalformedPil(format!("{} expression without const", exp.op)))
+}
+
+impl TryFrom<Expression> for TypedExpression {
+    type Error = PilError;
+
+    fn try_from(mut exp: Expression) -> Result<Self> {
+        let mut raw_value = None;
+        let expr = match exp.op.as_str() {
+            "add" => {
+                let (a, b) = binary(&mut exp)?;
+                Expr::Add(a, b)
+            }
+            "sub" => {
+                let (a, b) = binary(&mut exp)?;
+                Expr::Sub(a, b)
+            }
+            "mul" => {
+                let (a, b) = binary(&mut exp)?;
+                Expr::Mul(a, b)
+            }
+            "addc" => Expr::AddC(unary(&mut exp)?, constant(&exp)?),
+            "mulc" => Expr::MulC(unary(&mut exp)?, constant(&exp)?),
+            "neg" => Expr::Neg(unary(&mut exp)?),
+            "cm" => Expr::Cm {
+                id: id(&exp)?,
+                next: exp.next(),
+            },
+            "const" => Expr::Const {
+                id: id(&exp)?,
+                next: exp.next(),
+            },
+            "exp" => Expr::Exp {
+                id: id(&exp)?,
+                next: exp.next(),
+            },
+            "number" => {
+                let raw_val = exp.value.as_ref().ok_or_else(|| {
+                    PilError::MalformedPil("number expression without value".to_string())
+                })?;
+                let v = parse_pil_number(raw_val)?;
+                if *raw_val != v.to_string() {
+                    raw_value = exp.value.clone();
+                }
+                Expr::Number(FGL::from(v))
+            }
+            "public" => Expr::Public(id(&exp)?),
+            _ => return Err(PilError::UnknownOp(exp.op)),
+        };
+
+        Ok(TypedExpression {
+            expr,
+            deg: exp.deg,
+            keep: exp.keep,
+            keep2ns: exp.keep2ns,
+            idQ: exp.idQ,
+            omit_next: matches!(exp.op.as_str(), "cm" | "const" | "exp") && exp.next.is_none(),
+            raw_value,
+        })
+    }
+}
+
+impl From<TypedExpression> for Expression {
+    fn from(typed: TypedExpression) -> Self {
+        let mut exp = Expression::new(typed.op().to_string(), typed.deg, None, None, None);
+        exp.keep = typed.keep;
+        exp.keep2ns = typed.keep2ns;
+        exp.idQ = typed.idQ;
+
+        let values =
+            |v: Vec<Box<TypedExpression>>| Some(v.into_iter().map(|e| (*e).into()).collect());
+        match typed.expr {
+            Expr::Add(a, b) | Expr::Sub(a, b) | Expr::Mul(a, b) => exp.values = values(vec![a, b]),
+            Expr::AddC(a, c) | Expr::MulC(a, c) => {
+                exp.values = values(vec![a]);
+                exp.const_ = Some(c);
+            }
+            Expr::Neg(a) => exp.values = values(vec![a]),
+            Expr::Cm { id, next } | Expr::Const { id, next } | Expr::Exp { id, next } => {
+                exp.id = Some(id);
+                if next || !typed.omit_next {
+                    exp.next = Some(next);
+                }
+            }
+            Expr::Number(v) => {
+                // the spelling only stands while it still denotes the value
+                exp.value = match typed.raw_value {
+                    Some(raw) if parse_pil_number(&raw).ok() == Some(v.as_int()) => Some(raw),
+                    _ => Some(v.as_int().to_string()),
+                }
+            }
+            Expr::Public(id) => exp.id = Some(id),
+        }
+        exp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expression_round_trip() {
+        let json = r#"{"op":"sub","deg":2,"values":[{"op":"mul","deg":2,"values":[{"op":"cm","deg":1,"id":0,"next":true},{"op":"number","deg":0,"value":"3"}]},{"op":"addc","deg":1,"values":[{"op":"exp","deg":1,"id":2,"next":false}],"const_":-5}],"idQ":0,"keep":true}"#;
+        let typed: TypedExpression = serde_json::from_str(json).unwrap();
+        assert_eq!(typed.op(), "sub");
+        assert_eq!(typed.idQ, Some(0));
+        match &typed.expr {
+            Expr::Sub(a, b) => {
+                assert!(matches!(a.expr, Expr::Mul(..)));
+                assert_eq!(a.operands()[0].expr, Expr::Cm { id: 0, next: true });
+                assert_eq!(a.operands()[1].expr, Expr::Number(FGL::from(3u64)));
+                assert!(matches!(b.expr, Expr::AddC(_, -5)));
+            }
+            _ => panic!("expected a sub, got {:?}", typed),
+        }
+
+        let back: serde_json::Value = serde_json::to_value(&typed).unwrap();
+        let orig: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(back, orig);
+    }
+
+    #[test]
+    fn test_spelling_round_trip() {
+        // pilcom omits `next` on some references, and writes numbers as given in the source
+        let json = r#"{"op":"add","deg":1,"values":[{"op":"cm","deg":1,"id":1},{"op":"add","deg":0,"values":[{"op":"number","deg":0,"value":"-1"},{"op":"number","deg":0,"value":"0x10"}]}]}"#;
+        let typed: TypedExpression = serde_json::from_str(json).unwrap();
+        assert_eq!(typed.operands()[0].expr, Expr::Cm { id: 1, next: false });
+        let numbers = typed.operands()[1].operands();
+        assert_eq!(numbers[0].expr, Expr::Number(-FGL::ONE));
+        assert_eq!(numbers[1].expr, Expr::Number(FGL::from(16u64)));
+        let back: serde_json::Value = serde_json::to_value(&typed).unwrap();
+        assert_eq!(
+            back,
+            serde_json::from_str::<serde_json::Value>(json).unwrap()
+        );
+
+        // a changed value drops the old spelling
+        let mut number = numbers[0].clone();
+        number.expr = Expr::Number(FGL::from(2u64));
+        let back: Expression = number.into();
+        assert_eq!(back.value.as_deref(), Some("2"));
+    }
+
+    #[test]
+    fn test_malformed_expressions() {
+        let parse = |json: &str| {
+            TypedExpression::try_from(serde_json::from_str::<Expression>(json).unwrap())
+        };
+        assert!(matches!(
+            parse(r#"{"op":"pow","deg":1}"#),
+            Err(PilError::UnknownOp(op)) if op == "pow"
+        ));
+        // the nodes pil-stark adds are not PIL
+        assert!(matches!(
+            parse(r#"{"op":"challenge","deg":0,"id":3}"#),
+            Err(PilError::UnknownOp(op)) if op == "challenge"
+        ));
+        assert!(matches!(
+            parse(r#"{"op":"cm","deg":1}"#),
+            Err(PilError::MalformedPil(_))
+        ));
+        assert!(matches!(
+            parse(r#"{"op":"add","deg":1,"values":[{"op":"cm","deg":1,"id":0}]}"#),
+            Err(PilError::MalformedPil(_))
+        ));
+        assert!(matches!(
+            parse(r#"{"op":"neg","deg":1,"values":[{"op":"number","deg":0,"value":"x"}]}"#),
+            Err(PilError::MalformedPil(_))
+        ));
+    }
+}
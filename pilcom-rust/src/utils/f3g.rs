@@ -0,0 +1,402 @@
+#![allow(non_snake_case)]
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+use fields::field_gl::{Fr as FGL, MODULUS};
+use fields::Field;
+use rand::{Rand, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use super::traits::FieldExtension;
+
+/// Element of the cubic extension GF(p^3) = GF(p)[x] / (x^3 - x - 1) of Goldilocks, as used by
+/// pil-stark.
+///
+/// `dim` is 1 for elements of the base field, so that base and extension values can share
+/// the same buffers, and 3 otherwise. Arithmetic on two base elements stays in the base
+/// field.
+#[repr(C)]
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct F3G {
+    pub cube: [FGL; 3],
+    pub dim: usize,
+}
+
+impl F3G {
+    #[inline(always)]
+    pub const fn new(a: FGL, b: FGL, c: FGL) -> Self {
+        F3G {
+            cube: [a, b, c],
+            dim: 3,
+        }
+    }
+
+    #[inline(always)]
+    fn from_base(a: FGL) -> Self {
+        F3G {
+            cube: [a, FGL::ZERO, FGL::ZERO],
+            dim: 1,
+        }
+    }
+
+    #[inline(always)]
+    fn max_dim(&self, rhs: &Self) -> usize {
+        std::cmp::max(self.dim, rhs.dim)
+    }
+}
+
+impl fmt::Debug for F3G {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "F3G({})", self)
+    }
+}
+
+impl fmt::Display for F3G {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.dim == 1 {
+            write!(f, "{}", self.cube[0].as_int())
+        } else {
+            write!(
+                f,
+                "[{}, {}, {}]",
+                self.cube[0].as_int(),
+                self.cube[1].as_int(),
+                self.cube[2].as_int()
+            )
+        }
+    }
+}
+
+impl PartialEq for F3G {
+    fn eq(&self, rhs: &Self) -> bool {
+        self.cube == rhs.cube
+    }
+}
+
+impl Eq for F3G {}
+
+impl Hash for F3G {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.cube.hash(state);
+    }
+}
+
+impl Default for F3G {
+    fn default() -> Self {
+        Self::ZERO
+    }
+}
+
+impl From<FGL> for F3G {
+    fn from(a: FGL) -> Self {
+        F3G::from_base(a)
+    }
+}
+
+impl From<u64> for F3G {
+    fn from(a: u64) -> Self {
+        F3G::from_base(FGL::from(a))
+    }
+}
+
+impl From<i32> for F3G {
+    fn from(a: i32) -> Self {
+        let v = FGL::from(a.unsigned_abs() as u64);
+        if a < 0 {
+            F3G::from_base(-v)
+        } else {
+            F3G::from_base(v)
+        }
+    }
+}
+
+impl From<usize> for F3G {
+    fn from(a: usize) -> Self {
+        F3G::from_base(FGL::from(a as u64))
+    }
+}
+
+impl Rand for F3G {
+    fn rand<R: Rng>(rng: &mut R) -> Self {
+        F3G::new(FGL::rand(rng), FGL::rand(rng), FGL::rand(rng))
+    }
+}
+
+impl Add for F3G {
+    type Output = Self;
+
+    #[inline]
+    fn add(self, rhs: Self) -> Self {
+        F3G {
+            cube: [
+                self.cube[0] + rhs.cube[0],
+                self.cube[1] + rhs.cube[1],
+                self.cube[2] + rhs.cube[2],
+            ],
+            dim: self.max_dim(&rhs),
+        }
+    }
+}
+
+impl AddAssign for F3G {
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl Sub for F3G {
+    type Output = Self;
+
+    #[inline]
+    fn sub(self, rhs: Self) -> Self {
+        F3G {
+            cube: [
+                self.cube[0] - rhs.cube[0],
+                self.cube[1] - rhs.cube[1],
+                self.cube[2] - rhs.cube[2],
+            ],
+            dim: self.max_dim(&rhs),
+        }
+    }
+}
+
+impl SubAssign for F3G {
+    #[inline]
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl Neg for F3G {
+    type Output = Self;
+
+    #[inline]
+    fn neg(self) -> Self {
+        F3G {
+            cube: [-self.cube[0], -self.cube[1], -self.cube[2]],
+            dim: self.dim,
+        }
+    }
+}
+
+impl Mul for F3G {
+    type Output = Self;
+
+    /// Karatsuba-like product reduced by x^3 = x + 1, the same sequence as pil-stark's
+    /// `F3g.mul`.
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        if self.dim == 1 && rhs.dim == 1 {
+            return F3G::from_base(self.cube[0] * rhs.cube[0]);
+        }
+        if self.dim == 1 || rhs.dim == 1 {
+            let (s, e) = if self.dim == 1 {
+                (self.cube[0], rhs)
+            } else {
+                (rhs.cube[0], self)
+            };
+            return F3G::new(s * e.cube[0], s * e.cube[1], s * e.cube[2]);
+        }
+
+        let [a0, a1, a2] = self.cube;
+        let [b0, b1, b2] = rhs.cube;
+        let A = (a0 + a1) * (b0 + b1);
+        let B = (a0 + a2) * (b0 + b2);
+        let C = (a1 + a2) * (b1 + b2);
+        let D = a0 * b0;
+        let E = a1 * b1;
+        let F = a2 * b2;
+        let G = D - E;
+
+        F3G::new((C + G) - F, (((A + C) - E) - E) - D, B - G)
+    }
+}
+
+impl MulAssign for F3G {
+    #[inline]
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl Div for F3G {
+    type Output = Self;
+
+    #[inline]
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, rhs: Self) -> Self {
+        self * rhs.inv()
+    }
+}
+
+impl DivAssign for F3G {
+    #[inline]
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+impl Field for F3G {
+    fn zero() -> Self {
+        Self::ZERO
+    }
+    fn one() -> Self {
+        Self::ONE
+    }
+    fn is_zero(&self) -> bool {
+        self.cube.iter().all(|c| c.is_zero())
+    }
+    fn square(&mut self) {
+        *self = *self * *self;
+    }
+    fn double(&mut self) {
+        *self = *self + *self;
+    }
+    fn negate(&mut self) {
+        *self = -*self;
+    }
+    fn add_assign(&mut self, other: &Self) {
+        *self += *other;
+    }
+    fn sub_assign(&mut self, other: &Self) {
+        *self -= *other;
+    }
+    fn mul_assign(&mut self, other: &Self) {
+        *self *= *other;
+    }
+    fn inverse(&self) -> Option<Self> {
+        if Field::is_zero(self) {
+            None
+        } else {
+            Some(self.inv())
+        }
+    }
+    fn frobenius_map(&mut self, power: usize) {
+        for _ in 0..(power % 3) {
+            *self = Field::pow(self, MODULUS.0);
+        }
+    }
+}
+
+impl FieldExtension for F3G {
+    const ELEMENT_BYTES: usize = std::mem::size_of::<Self>();
+    const ZERO: Self = F3G {
+        cube: [FGL::ZERO, FGL::ZERO, FGL::ZERO],
+        dim: 1,
+    };
+    const ONE: Self = F3G {
+        cube: [FGL::ONE, FGL::ZERO, FGL::ZERO],
+        dim: 1,
+    };
+    const ZEROS: Self = F3G::new(FGL::ZERO, FGL::ZERO, FGL::ZERO);
+    const ONES: Self = F3G::new(FGL::ONE, FGL::ZERO, FGL::ZERO);
+
+    #[inline(always)]
+    fn dim(&self) -> usize {
+        self.dim
+    }
+
+    fn from_vec(values: Vec<FGL>) -> Self {
+        match values.len() {
+            1 => F3G::from_base(values[0]),
+            3 => F3G::new(values[0], values[1], values[2]),
+            n => panic!("F3G::from_vec expects 1 or 3 elements, got {}", n),
+        }
+    }
+
+    #[inline(always)]
+    fn to_be(&self) -> FGL {
+        self.cube[0]
+    }
+
+    fn as_elements(&self) -> Vec<FGL> {
+        self.cube[..self.dim].to_vec()
+    }
+
+    fn mul_scalar(&self, b: usize) -> Self {
+        let b = FGL::from(b as u64);
+        F3G {
+            cube: [self.cube[0] * b, self.cube[1] * b, self.cube[2] * b],
+            dim: self.dim,
+        }
+    }
+
+    fn _eq(&self, rhs: &Self) -> bool {
+        self == rhs
+    }
+
+    // The comparisons only make sense on the base field, as in pil-stark.
+    fn gt(&self, rhs: &Self) -> bool {
+        self.as_int() > rhs.as_int()
+    }
+
+    fn geq(&self, rhs: &Self) -> bool {
+        self.as_int() >= rhs.as_int()
+    }
+
+    fn lt(&self, rhs: &Self) -> bool {
+        self.as_int() < rhs.as_int()
+    }
+
+    fn leq(&self, rhs: &Self) -> bool {
+        self.as_int() <= rhs.as_int()
+    }
+
+    fn exp(&self, e_: usize) -> Self {
+        let mut res = Self::ONE;
+        let mut base = *self;
+        let mut e = e_;
+        while e > 0 {
+            if e & 1 == 1 {
+                res *= base;
+            }
+            base *= base;
+            e >>= 1;
+        }
+        res
+    }
+
+    /// Inverse through the adjugate of the multiplication matrix of `self`,
+    ///
+    /// ```text
+    /// | a0  a2       a1      |
+    /// | a1  a0 + a2  a1 + a2 |
+    /// | a2  a1       a0 + a2 |
+    /// ```
+    ///
+    /// the inverse being the solution of `M * b = (1, 0, 0)`.
+    fn inv(&self) -> Self {
+        if self.dim == 1 {
+            return F3G::from_base(self.cube[0].inverse().unwrap());
+        }
+        let [a0, a1, a2] = self.cube;
+        let a02 = a0 + a2;
+        let c0 = a02 * a02 - (a1 + a2) * a1;
+        let c1 = a2 * a2 - a0 * a1;
+        let c2 = a1 * a1 - a02 * a2;
+
+        let det = a0 * c0 + a2 * c1 + a1 * c2;
+        let det_inv = det.inverse().unwrap();
+
+        F3G::new(c0 * det_inv, c1 * det_inv, c2 * det_inv)
+    }
+
+    #[inline(always)]
+    fn as_int(&self) -> u64 {
+        self.cube[0].as_int()
+    }
+
+    fn elements_as_bytes(elements: &[Self]) -> &[u8] {
+        unsafe {
+            std::slice::from_raw_parts(
+                elements.as_ptr() as *const u8,
+                elements.len() * Self::ELEMENT_BYTES,
+            )
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self as *const Self as *const u8, Self::ELEMENT_BYTES) }
+    }
+}
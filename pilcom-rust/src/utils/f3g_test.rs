@@ -0,0 +1,186 @@
+#[cfg(test)]
+mod tests {
+    use crate::utils::f3g::F3G;
+    use crate::utils::traits::FieldExtension;
+    use fields::field_gl::Fr as FGL;
+    use fields::Field;
+    use proptest::prelude::*;
+    use rand::Rand;
+
+    fn f3g(a: u64, b: u64, c: u64) -> F3G {
+        F3G::new(FGL::from(a), FGL::from(b), FGL::from(c))
+    }
+
+    /// Schoolbook product reduced by x^3 = x + 1.
+    fn mul_reference(a: &F3G, b: &F3G) -> F3G {
+        let mut c = [FGL::zero(); 5];
+        for i in 0..3 {
+            for j in 0..3 {
+                c[i + j] = c[i + j] + a.cube[i] * b.cube[j];
+            }
+        }
+        // x^4 = x^2 + x, x^3 = x + 1
+        F3G::new(c[0] + c[3], c[1] + c[3] + c[4], c[2] + c[4])
+    }
+
+    #[test]
+    fn add() {
+        let mut rng = rand::thread_rng();
+        let r = F3G::rand(&mut rng);
+        assert_eq!(r, r + F3G::ZEROS);
+        assert_eq!(f3g(5, 7, 9), f3g(2, 3, 4) + f3g(3, 4, 5));
+
+        // base elements stay in the base field
+        let a = F3G::from(2u64) + F3G::from(3u64);
+        assert_eq!(a.dim(), 1);
+        assert_eq!(a, F3G::from(5u64));
+        assert_eq!((a + r).dim(), 3);
+    }
+
+    #[test]
+    fn sub() {
+        let mut rng = rand::thread_rng();
+        let r = F3G::rand(&mut rng);
+        assert_eq!(r, r - F3G::ZEROS);
+        assert_eq!(F3G::ZEROS, r - r);
+        assert_eq!(f3g(2, 3, 4), f3g(5, 7, 9) - f3g(3, 4, 5));
+    }
+
+    #[test]
+    fn neg() {
+        assert_eq!(F3G::ZERO, -F3G::ZERO);
+        assert_eq!(F3G::from(-1i32), -F3G::ONE);
+
+        let mut rng = rand::thread_rng();
+        let r = F3G::rand(&mut rng);
+        assert_eq!(r, -(-r));
+        assert_eq!(F3G::ZEROS, r + (-r));
+    }
+
+    #[test]
+    fn mul() {
+        let mut rng = rand::thread_rng();
+        let r = F3G::rand(&mut rng);
+        assert_eq!(F3G::ZEROS, r * F3G::ZERO);
+        assert_eq!(r, r * F3G::ONE);
+        assert_eq!(r, r * F3G::ONES);
+
+        // x * x^2 = x^3 = x + 1
+        assert_eq!(f3g(1, 1, 0), f3g(0, 1, 0) * f3g(0, 0, 1));
+        assert_eq!(F3G::from(15u64), F3G::from(5u64) * F3G::from(3u64));
+        assert_eq!(r.mul_scalar(3), r * F3G::from(3u64));
+    }
+
+    #[test]
+    fn exp_test() {
+        let a = F3G::ZEROS;
+        assert_eq!(a.exp(0), F3G::ONE);
+        assert_eq!(a.exp(1), F3G::ZERO);
+
+        let mut rng = rand::thread_rng();
+        let a = F3G::rand(&mut rng);
+        assert_eq!(a.exp(3), a * a * a);
+
+        // the multiplicative group has order p^3 - 1, so a^(p^3) = a. Frobenius applied
+        // three times is the identity.
+        let mut b = a;
+        b.frobenius_map(1);
+        assert_ne!(a, b);
+        b.frobenius_map(2);
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn inv() {
+        assert_eq!(F3G::ONE, F3G::ONE.inv());
+        assert_eq!(F3G::ONES, F3G::ONES.inv());
+        assert_eq!(None, F3G::ZEROS.inverse());
+    }
+
+    #[test]
+    fn conversions() {
+        let r = f3g(1, 2, 3);
+        assert_eq!(r, F3G::from_vec(r.as_elements()));
+        assert_eq!(r.as_elements().len(), 3);
+        assert_eq!(F3G::from(7u64).as_elements(), vec![FGL::from(7u64)]);
+        assert_eq!(F3G::from(7usize).to_be(), FGL::from(7u64));
+        assert_eq!(F3G::from(-7i32).as_int(), 18446744069414584321 - 7);
+        assert!(F3G::from(7u64).gt(&F3G::from(3u64)));
+        assert!(F3G::from(3u64).leq(&F3G::from(3u64)));
+        assert_eq!(r.as_bytes().len(), F3G::ELEMENT_BYTES);
+        assert_eq!(
+            F3G::elements_as_bytes(&[r, r]).len(),
+            2 * F3G::ELEMENT_BYTES
+        );
+        assert_eq!(r.to_string(), "[1, 2, 3]");
+    }
+
+    #[test]
+    fn test_serde_and_deserde() {
+        let mut rng = rand::thread_rng();
+        let data = F3G::rand(&mut rng);
+        let serialized = serde_json::to_string(&data).unwrap();
+        let expect: F3G = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(data, expect);
+        assert_eq!(data.dim(), expect.dim());
+    }
+
+    // RANDOMIZED TESTS
+    // ================================================================================================
+
+    proptest! {
+        #[test]
+        fn mul_proptest(a in any::<[u64; 3]>(), b in any::<[u64; 3]>()) {
+            let v1 = f3g(a[0], a[1], a[2]);
+            let v2 = f3g(b[0], b[1], b[2]);
+            prop_assert_eq!(mul_reference(&v1, &v2), v1 * v2);
+            prop_assert_eq!(v1 * v2, v2 * v1);
+        }
+
+        #[test]
+        fn distributive_proptest(a in any::<[u64; 3]>(), b in any::<[u64; 3]>(), c in any::<[u64; 3]>()) {
+            let v1 = f3g(a[0], a[1], a[2]);
+            let v2 = f3g(b[0], b[1], b[2]);
+            let v3 = f3g(c[0], c[1], c[2]);
+            prop_assert_eq!(v1 * (v2 + v3), v1 * v2 + v1 * v3);
+            prop_assert_eq!((v1 * v2) * v3, v1 * (v2 * v3));
+        }
+
+        #[test]
+        fn mixed_dim_proptest(a in any::<[u64; 3]>(), s in any::<u64>()) {
+            let v = f3g(a[0], a[1], a[2]);
+            let base = F3G::from(s);
+            let lifted = f3g(s, 0, 0);
+            prop_assert_eq!(v * base, v * lifted);
+            prop_assert_eq!(base * v, lifted * v);
+        }
+
+        #[test]
+        fn inv_proptest(a in any::<[u64; 3]>()) {
+            let v = f3g(a[0], a[1], a[2]);
+            if !v.is_zero() {
+                prop_assert_eq!(F3G::ONE, v * v.inv());
+                prop_assert_eq!(v, F3G::ONE / (F3G::ONE / v));
+            }
+        }
+
+        #[test]
+        fn div_proptest(a in any::<[u64; 3]>(), b in any::<[u64; 3]>()) {
+            let v1 = f3g(a[0], a[1], a[2]);
+            let v2 = f3g(b[0], b[1], b[2]);
+            if !v2.is_zero() {
+                prop_assert_eq!(v1, (v1 / v2) * v2);
+            }
+        }
+
+        #[test]
+        fn exp_proptest(a in any::<[u64; 3]>(), e in 0usize..1000) {
+            let v = f3g(a[0], a[1], a[2]);
+            let mut expected = F3G::ONE;
+            for _ in 0..e {
+                expected *= v;
+            }
+            prop_assert_eq!(expected, v.exp(e));
+        }
+    }
+}
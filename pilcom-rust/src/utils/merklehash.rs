@@ -0,0 +1,490 @@
+#![allow(non_snake_case)]
+//! Merkle trees of Goldilocks Poseidon hashes, pil-stark's `MerkleHashGL`.
+//!
+//! The leaves are the rows of a `width` x `height` buffer of field elements, each one
+//! hashed with `poseidon_gl::linear_hash`. Every level above hashes pairs of nodes, the
+//! left one first, a missing right one being zero, until a single node is left: the root.
+//! The nodes are stored level by level from the leaves, 4 elements each, so that trees
+//! can be cached and read back in pil-stark's layout.
+use fields::field_gl::{Fr as FGL, MODULUS};
+use fields::poseidon_gl::{hash, linear_hash, SPONGE_CAPACITY, SPONGE_RATE};
+use rayon::prelude::*;
+use std::io::{Read, Write};
+
+use super::error::{PilError, Result};
+use super::polarray::PolsArray;
+use super::traits::MerkleTree;
+
+/// A node of the tree.
+pub type ElementDigest = [FGL; SPONGE_CAPACITY];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MerkleTreeGL {
+    /// The committed values, row by row.
+    pub elements: Vec<FGL>,
+    /// The nodes, level by level from the hashes of the rows.
+    pub nodes: Vec<FGL>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Number of nodes of a tree of `height` leaves.
+fn n_nodes(height: usize) -> usize {
+    let mut n = height;
+    let mut total = height;
+    while n > 1 {
+        n = n.div_ceil(2);
+        total += n;
+    }
+    total
+}
+
+/// The parent of `left` and `right`.
+#[inline]
+fn hash_node(left: &[FGL], right: &[FGL]) -> ElementDigest {
+    let mut input = [FGL::ZERO; SPONGE_RATE];
+    input[..SPONGE_CAPACITY].copy_from_slice(left);
+    input[SPONGE_CAPACITY..].copy_from_slice(right);
+    hash(&input, &[FGL::ZERO; SPONGE_CAPACITY])
+}
+
+/// Values are written and read this many at a time.
+const IO_CHUNK: usize = 1 << 16;
+
+pub(crate) fn write_u64s(w: &mut impl Write, values: impl Iterator<Item = u64>) -> Result<()> {
+    let mut buff = Vec::with_capacity(IO_CHUNK * 8);
+    for v in values {
+        buff.extend_from_slice(&v.to_le_bytes());
+        if buff.len() == IO_CHUNK * 8 {
+            w.write_all(&buff)?;
+            buff.clear();
+        }
+    }
+    w.write_all(&buff)?;
+    Ok(())
+}
+
+pub(crate) fn read_u64s(r: &mut impl Read, n: usize, what: &str) -> Result<Vec<u64>> {
+    // a corrupt header must not allocate more than the file holds
+    let mut values = Vec::with_capacity(n.min(IO_CHUNK));
+    let mut buff = vec![0u8; IO_CHUNK.min(n) * 8];
+    while values.len() < n {
+        let len = IO_CHUNK.min(n - values.len()) * 8;
+        r.read_exact(&mut buff[..len])
+            .map_err(|_| PilError::CorruptTree(format!("truncated, reading {}", what)))?;
+        values.extend(
+            buff[..len]
+                .chunks(8)
+                .map(|c| u64::from_le_bytes(c.try_into().unwrap())),
+        );
+    }
+    Ok(values)
+}
+
+pub(crate) fn read_gl(r: &mut impl Read, n: usize, what: &str) -> Result<Vec<FGL>> {
+    read_u64s(r, n, what)?
+        .into_iter()
+        .map(|v| {
+            if v >= MODULUS.0[0] {
+                return Err(PilError::CorruptTree(format!(
+                    "{} is not a field element, reading {}",
+                    v, what
+                )));
+            }
+            Ok(FGL::from(v))
+        })
+        .collect()
+}
+
+/// Write the header and the elements of a tree.
+pub(crate) fn write_elements(
+    w: &mut impl Write,
+    elements: &[FGL],
+    width: usize,
+    height: usize,
+) -> Result<()> {
+    write_u64s(w, [width as u64, height as u64].into_iter())?;
+    write_u64s(w, elements.iter().map(|v| v.as_int()))
+}
+
+/// Read the header and the elements of a tree: elements, width and height.
+pub(crate) fn read_elements(r: &mut impl Read) -> Result<(Vec<FGL>, usize, usize)> {
+    let header = read_u64s(r, 2, "header")?;
+    let (width, height) = (header[0] as usize, header[1] as usize);
+    let n = width.checked_mul(height).ok_or_else(|| {
+        PilError::CorruptTree(format!("a tree of {} x {} elements", width, height))
+    })?;
+    Ok((read_gl(r, n, "elements")?, width, height))
+}
+
+impl MerkleTreeGL {
+    /// Build the tree of `elements`, `height` rows of `width` values.
+    pub fn merkelize(elements: Vec<FGL>, width: usize, height: usize) -> Result<Self> {
+        if elements.len() != width * height {
+            return Err(PilError::SizeMismatch {
+                expected: width * height,
+                actual: elements.len(),
+            });
+        }
+        let mut nodes = vec![FGL::ZERO; n_nodes(height) * SPONGE_CAPACITY];
+
+        // a tree of no columns has zero leaves
+        nodes[..height * SPONGE_CAPACITY]
+            .par_chunks_mut(SPONGE_CAPACITY)
+            .zip(elements.par_chunks(width.max(1)))
+            .for_each(|(leaf, row)| leaf.copy_from_slice(&linear_hash(row)));
+
+        let mut offset = 0;
+        let mut n = height;
+        while n > 1 {
+            let next = n.div_ceil(2);
+            let (level, above) = nodes[offset..].split_at_mut(n * SPONGE_CAPACITY);
+            above[..next * SPONGE_CAPACITY]
+                .par_chunks_mut(SPONGE_CAPACITY)
+                .zip(level.par_chunks(2 * SPONGE_CAPACITY))
+                .for_each(|(parent, children)| {
+                    let (left, right) = children.split_at(SPONGE_CAPACITY);
+                    let right = if right.is_empty() {
+                        &[FGL::ZERO; SPONGE_CAPACITY][..]
+                    } else {
+                        right
+                    };
+                    parent.copy_from_slice(&hash_node(left, right));
+                });
+            offset += n * SPONGE_CAPACITY;
+            n = next;
+        }
+
+        Ok(MerkleTreeGL {
+            elements,
+            nodes,
+            width,
+            height,
+        })
+    }
+
+    /// The root, zero for a tree of no rows.
+    pub fn root(&self) -> ElementDigest {
+        let mut root = [FGL::ZERO; SPONGE_CAPACITY];
+        if let Some(last) = self.nodes.len().checked_sub(SPONGE_CAPACITY) {
+            root.copy_from_slice(&self.nodes[last..]);
+        }
+        root
+    }
+
+    /// The value of column `subIdx` at row `idx`.
+    pub fn get_element(&self, idx: usize, subIdx: usize) -> FGL {
+        assert!(subIdx < self.width, "column {} out of range", subIdx);
+        self.elements[idx * self.width + subIdx]
+    }
+
+    /// The values of row `idx` and the siblings of its path to the root, from the leaves.
+    pub fn get_group_proof(&self, idx: usize) -> Result<(Vec<FGL>, Vec<ElementDigest>)> {
+        if idx >= self.height {
+            return Err(PilError::OutOfRange {
+                kind: "row",
+                id: idx,
+                len: self.height,
+            });
+        }
+        let values = self.elements[idx * self.width..(idx + 1) * self.width].to_vec();
+
+        let mut path = vec![];
+        let mut offset = 0;
+        let mut n = self.height;
+        let mut i = idx;
+        while n > 1 {
+            let sibling = i ^ 1;
+            let mut node = [FGL::ZERO; SPONGE_CAPACITY];
+            if sibling < n {
+                let start = offset + sibling * SPONGE_CAPACITY;
+                node.copy_from_slice(&self.nodes[start..start + SPONGE_CAPACITY]);
+            }
+            path.push(node);
+            offset += n * SPONGE_CAPACITY;
+            n = n.div_ceil(2);
+            i >>= 1;
+        }
+        Ok((values, path))
+    }
+
+    /// The root a tree with `values` at row `idx` and siblings `path` has.
+    pub fn calculate_root_from_group_proof(
+        path: &[ElementDigest],
+        idx: usize,
+        values: &[FGL],
+    ) -> ElementDigest {
+        let mut node = linear_hash(values);
+        let mut i = idx;
+        for sibling in path {
+            node = if i & 1 == 0 {
+                hash_node(&node, sibling)
+            } else {
+                hash_node(sibling, &node)
+            };
+            i >>= 1;
+        }
+        node
+    }
+
+    /// Whether `values` are row `idx` of the tree of `root`, given the siblings `path`.
+    pub fn verify_group_proof(
+        root: &ElementDigest,
+        path: &[ElementDigest],
+        idx: usize,
+        values: &[FGL],
+    ) -> bool {
+        Self::calculate_root_from_group_proof(path, idx, values) == *root
+    }
+}
+
+impl MerkleTree for MerkleTreeGL {
+    type MTNode = ElementDigest;
+    type Siblings = ElementDigest;
+
+    fn merkelize(elements: Vec<FGL>, width: usize, height: usize) -> Result<Self> {
+        MerkleTreeGL::merkelize(elements, width, height)
+    }
+
+    fn root(&self) -> ElementDigest {
+        MerkleTreeGL::root(self)
+    }
+
+    fn get_element(&self, idx: usize, subIdx: usize) -> FGL {
+        MerkleTreeGL::get_element(self, idx, subIdx)
+    }
+
+    fn get_group_proof(&self, idx: usize) -> Result<(Vec<FGL>, Vec<ElementDigest>)> {
+        MerkleTreeGL::get_group_proof(self, idx)
+    }
+
+    fn verify_group_proof(
+        root: &ElementDigest,
+        path: &[ElementDigest],
+        idx: usize,
+        values: &[FGL],
+    ) -> bool {
+        MerkleTreeGL::verify_group_proof(root, path, idx, values)
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        write_elements(w, &self.elements, self.width, self.height)?;
+        write_u64s(w, self.nodes.iter().map(|v| v.as_int()))
+    }
+
+    fn read(r: &mut impl Read) -> Result<Self> {
+        let (elements, width, height) = read_elements(r)?;
+        let nodes = read_gl(r, n_nodes(height) * SPONGE_CAPACITY, "nodes")?;
+        Ok(MerkleTreeGL {
+            elements,
+            nodes,
+            width,
+            height,
+        })
+    }
+}
+
+impl PolsArray {
+    /// The Merkle tree of the rows of the polynomials. Fails unless they all have `n` rows.
+    pub fn merkelize(&self) -> Result<MerkleTreeGL> {
+        if let Some(g) = self.groups.iter().find(|g| g.polDeg != self.n) {
+            return Err(PilError::SizeMismatch {
+                expected: self.n,
+                actual: g.polDeg,
+            });
+        }
+        let mut elements = Vec::with_capacity(self.len());
+        for j in 0..self.n {
+            elements.extend_from_slice(&self.row(j));
+        }
+        MerkleTreeGL::merkelize(elements, self.nPols, self.n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polarray::PolKind;
+    use crate::utils::types::{read_json, PIL};
+
+    fn elements(width: usize, height: usize) -> Vec<FGL> {
+        (0..width * height)
+            .map(|i| FGL::from((i as u64).wrapping_mul(0x9e3779b97f4a7c15)))
+            .collect()
+    }
+
+    #[test]
+    fn test_merkelize() {
+        // 4 rows: the root is the hash of the hashes of the pairs of leaves
+        let tree = MerkleTreeGL::merkelize(elements(9, 4), 9, 4).unwrap();
+        let leaves: Vec<ElementDigest> = tree.elements.chunks(9).map(linear_hash).collect();
+        let root = hash_node(
+            &hash_node(&leaves[0], &leaves[1]),
+            &hash_node(&leaves[2], &leaves[3]),
+        );
+        assert_eq!(tree.nodes.len(), 7 * 4);
+        assert_eq!(tree.nodes[..4], leaves[0]);
+        assert_eq!(tree.root(), root);
+
+        // an odd level is completed with a zero node
+        let tree = MerkleTreeGL::merkelize(elements(2, 3), 2, 3).unwrap();
+        let leaves: Vec<ElementDigest> = tree.elements.chunks(2).map(linear_hash).collect();
+        let zero = [FGL::ZERO; 4];
+        let root = hash_node(
+            &hash_node(&leaves[0], &leaves[1]),
+            &hash_node(&leaves[2], &zero),
+        );
+        assert_eq!(tree.nodes.len(), 6 * 4);
+        assert_eq!(tree.root(), root);
+
+        // a single row is its own root
+        let tree = MerkleTreeGL::merkelize(elements(3, 1), 3, 1).unwrap();
+        assert_eq!(tree.root(), linear_hash(&tree.elements));
+
+        assert!(matches!(
+            MerkleTreeGL::merkelize(elements(3, 2), 2, 2),
+            Err(PilError::SizeMismatch {
+                expected: 4,
+                actual: 6
+            })
+        ));
+    }
+
+    #[test]
+    fn test_write_read() {
+        let tree = MerkleTreeGL::merkelize(elements(3, 5), 3, 5).unwrap();
+        let mut buff = vec![];
+        tree.write(&mut buff).unwrap();
+        assert_eq!(buff.len(), 8 * (2 + 3 * 5 + 11 * 4));
+        assert_eq!(buff[..16], [3, 0, 0, 0, 0, 0, 0, 0, 5, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(MerkleTreeGL::read(&mut &buff[..]).unwrap(), tree);
+
+        let err = MerkleTreeGL::read(&mut &buff[..buff.len() - 1]).unwrap_err();
+        assert!(matches!(err, PilError::CorruptTree(_)));
+        assert_eq!(
+            err.to_string(),
+            "corrupt tree file: truncated, reading nodes"
+        );
+        buff[16..24].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            MerkleTreeGL::read(&mut &buff[..]),
+            Err(PilError::CorruptTree(_))
+        ));
+    }
+
+    // computed with a BigInt transcription of pil-stark's poseidon, linearhash and
+    // merklehash (`merkelize` and `writeToFile`): 3 rows of 9 values, so the leaves take
+    // two permutations and the second level is completed with a zero node
+    #[test]
+    fn test_pil_stark_tree() {
+        #[rustfmt::skip]
+        let nodes: [u64; 24] = [
+            0xd2841f28a84f48cb, 0xb232ade00049d610, 0x05a016aff2078348, 0x2ada390fc949121e,
+            0x22fac7be596dbfb5, 0xa77d5f1e936408fb, 0xd23af07a56b4226d, 0xb6d5484296751ba0,
+            0x5257d8356cbcd6b4, 0x61a12e0e4a46ab17, 0xf0abd83b8307d354, 0x0335b38388757df7,
+            0x45e7b47896e14fc7, 0x67ec76024560e539, 0xe26cb63354682c6f, 0x1a52979bdef40401,
+            0xd08024d424414468, 0xa3aedbb7214cf52d, 0x2ef4bef9ae358a88, 0xb1fec0e69adff0c8,
+            0x3de0a765aa67e86d, 0x7d090c0ed186f836, 0x5eed66b57886c093, 0x34dc38260b10299f,
+        ];
+        let tree = MerkleTreeGL::merkelize(elements(9, 3), 9, 3).unwrap();
+        assert_eq!(tree.root().map(|v| v.as_int()), nodes[20..]);
+
+        let mut expected = vec![];
+        for v in [9, 3]
+            .into_iter()
+            .chain(tree.elements.iter().map(|v| v.as_int()))
+            .chain(nodes)
+        {
+            expected.extend_from_slice(&u64::to_le_bytes(v));
+        }
+        let mut buff = vec![];
+        tree.write(&mut buff).unwrap();
+        assert_eq!(buff, expected);
+    }
+
+    #[test]
+    fn test_group_proof() {
+        for height in [1, 5, 16] {
+            let tree = MerkleTreeGL::merkelize(elements(10, height), 10, height).unwrap();
+            let root = tree.root();
+            for idx in 0..height {
+                let (values, path) = tree.get_group_proof(idx).unwrap();
+                assert_eq!(values[3], tree.get_element(idx, 3));
+                assert_eq!(path.len(), (height as f64).log2().ceil() as usize);
+                assert!(MerkleTreeGL::verify_group_proof(&root, &path, idx, &values));
+
+                let mut bad = values.clone();
+                bad[9] = bad[9] + FGL::ONE;
+                assert!(!MerkleTreeGL::verify_group_proof(&root, &path, idx, &bad));
+                if height > 1 {
+                    assert!(!MerkleTreeGL::verify_group_proof(
+                        &root,
+                        &path,
+                        idx ^ 1,
+                        &values
+                    ));
+                }
+            }
+            assert!(tree.get_group_proof(height).is_err());
+        }
+    }
+
+    #[test]
+    fn test_pols_merkelize() {
+        // namespace Main(4);
+        //     pol commit a, b;
+        let pil = read_json::<PIL>(
+            r#"{
+            "nCommitments": 2, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        for j in 0..4 {
+            pols.set_value(0, j, FGL::from(j as u64));
+            pols.set_value(1, j, FGL::from(10 + j as u64));
+        }
+        let tree = pols.merkelize().unwrap();
+        assert_eq!((tree.width, tree.height), (2, 4));
+        assert_eq!(tree.get_element(2, 1), FGL::from(12u64));
+        let (values, path) = tree.get_group_proof(3).unwrap();
+        assert_eq!(values, vec![FGL::from(3u64), FGL::from(13u64)]);
+        assert!(MerkleTreeGL::verify_group_proof(
+            &tree.root(),
+            &path,
+            3,
+            &values
+        ));
+
+        // namespace Main(4);
+        //     pol commit a;
+        // namespace Small(2);
+        //     pol commit b;
+        let pil = read_json::<PIL>(
+            r#"{
+            "nCommitments": 2, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Small.b": { "type": "cmP", "id": 1, "polDeg": 2, "isArray": false }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        let pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        assert!(matches!(
+            pols.merkelize(),
+            Err(PilError::SizeMismatch {
+                expected: 4,
+                actual: 2
+            })
+        ));
+    }
+}
@@ -0,0 +1,288 @@
+#![allow(non_snake_case)]
+//! Merkle trees of BN254 Poseidon hashes, pil-stark's `MerkleHashBN128`, for the
+//! `verificationHashType` "BN128" of the STARKs that are verified in a SNARK.
+//!
+//! The leaves are the rows of a `width` x `height` buffer of Goldilocks values, each one
+//! hashed with `poseidon_bn128::linear_hash`. The tree has an arity of 16: every level
+//! above hashes groups of 16 nodes until a single node is left. The nodes are stored level
+//! by level from the leaves, every level but the root padded with zeros to a multiple of 16
+//! nodes, and written to files as 32 bytes each, the four little-endian limbs of their
+//! Montgomery form, as pil-stark does.
+use fields::bellman_ce::pairing::bn256::{Fr, FrRepr};
+use fields::ff::{Field, PrimeField};
+use fields::field_gl::Fr as FGL;
+use fields::poseidon_bn128::{hash, linear_hash, MAX_INPUTS};
+use rayon::prelude::*;
+use std::io::{Read, Write};
+
+use super::error::{PilError, Result};
+use super::merklehash::{read_elements, read_u64s, write_elements, write_u64s};
+use super::traits::MerkleTree;
+
+pub const ARITY: usize = MAX_INPUTS;
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MerkleTreeBN128 {
+    /// The committed values, row by row.
+    pub elements: Vec<FGL>,
+    /// The nodes, level by level from the hashes of the rows.
+    pub nodes: Vec<Fr>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Number of nodes of a tree of `height` leaves, padding included.
+fn n_nodes(height: usize) -> usize {
+    if height <= 1 {
+        return 1;
+    }
+    let next = height.div_ceil(ARITY);
+    next * ARITY + n_nodes(next)
+}
+
+/// The parent of `children`.
+fn hash_node(children: &[Fr]) -> Fr {
+    hash(children, Fr::zero())
+}
+
+impl MerkleTreeBN128 {
+    /// The root a tree with `values` at row `idx` and authentication path `path` has.
+    pub fn calculate_root_from_group_proof(path: &[[Fr; ARITY]], idx: usize, values: &[FGL]) -> Fr {
+        let mut node = linear_hash(values);
+        let mut i = idx;
+        for group in path {
+            let mut group = *group;
+            group[i % ARITY] = node;
+            node = hash_node(&group);
+            i /= ARITY;
+        }
+        node
+    }
+}
+
+impl MerkleTree for MerkleTreeBN128 {
+    type MTNode = Fr;
+    type Siblings = [Fr; ARITY];
+
+    fn merkelize(elements: Vec<FGL>, width: usize, height: usize) -> Result<Self> {
+        if elements.len() != width * height {
+            return Err(PilError::SizeMismatch {
+                expected: width * height,
+                actual: elements.len(),
+            });
+        }
+        let mut nodes = vec![Fr::zero(); n_nodes(height)];
+
+        // a tree of no columns has zero leaves
+        nodes[..height]
+            .par_iter_mut()
+            .zip(elements.par_chunks(width.max(1)))
+            .for_each(|(leaf, row)| *leaf = linear_hash(row));
+
+        let mut offset = 0;
+        let mut n = height;
+        while n > 1 {
+            let next = n.div_ceil(ARITY);
+            let (level, above) = nodes[offset..].split_at_mut(next * ARITY);
+            above[..next]
+                .par_iter_mut()
+                .zip(level.par_chunks(ARITY))
+                .for_each(|(parent, children)| *parent = hash_node(children));
+            offset += next * ARITY;
+            n = next;
+        }
+
+        Ok(MerkleTreeBN128 {
+            elements,
+            nodes,
+            width,
+            height,
+        })
+    }
+
+    /// The root, zero for a tree of no rows.
+    fn root(&self) -> Fr {
+        self.nodes.last().copied().unwrap_or_else(Fr::zero)
+    }
+
+    fn get_element(&self, idx: usize, subIdx: usize) -> FGL {
+        assert!(subIdx < self.width, "column {} out of range", subIdx);
+        self.elements[idx * self.width + subIdx]
+    }
+
+    /// The path holds the group of 16 nodes of the node at each level, itself included.
+    fn get_group_proof(&self, idx: usize) -> Result<(Vec<FGL>, Vec<[Fr; ARITY]>)> {
+        if idx >= self.height {
+            return Err(PilError::OutOfRange {
+                kind: "row",
+                id: idx,
+                len: self.height,
+            });
+        }
+        let values = self.elements[idx * self.width..(idx + 1) * self.width].to_vec();
+
+        let mut path = vec![];
+        let mut offset = 0;
+        let mut n = self.height;
+        let mut i = idx;
+        while n > 1 {
+            let first = offset + i - i % ARITY;
+            path.push(self.nodes[first..first + ARITY].try_into().unwrap());
+            n = n.div_ceil(ARITY);
+            offset += n * ARITY;
+            i /= ARITY;
+        }
+        Ok((values, path))
+    }
+
+    fn verify_group_proof(root: &Fr, path: &[[Fr; ARITY]], idx: usize, values: &[FGL]) -> bool {
+        MerkleTreeBN128::calculate_root_from_group_proof(path, idx, values) == *root
+    }
+
+    fn write(&self, w: &mut impl Write) -> Result<()> {
+        write_elements(w, &self.elements, self.width, self.height)?;
+        let limbs = self
+            .nodes
+            .iter()
+            .flat_map(|v| v.into_raw_repr().as_ref().to_vec());
+        write_u64s(w, limbs)
+    }
+
+    fn read(r: &mut impl Read) -> Result<Self> {
+        let (elements, width, height) = read_elements(r)?;
+        let limbs = read_u64s(r, n_nodes(height) * 4, "nodes")?;
+        let nodes = limbs
+            .chunks(4)
+            .map(|l| {
+                let mut repr = FrRepr::default();
+                repr.as_mut().copy_from_slice(l);
+                Fr::from_raw_repr(repr)
+                    .map_err(|_| PilError::CorruptTree("a node is not a field element".to_string()))
+            })
+            .collect::<Result<_>>()?;
+        Ok(MerkleTreeBN128 {
+            elements,
+            nodes,
+            width,
+            height,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::witness_file::hex;
+    use sha2::{Digest, Sha256};
+
+    fn elements(width: usize, height: usize) -> Vec<FGL> {
+        (0..width * height)
+            .map(|i| FGL::from((i as u64).wrapping_mul(0x9e3779b97f4a7c15)))
+            .collect()
+    }
+
+    #[test]
+    fn test_merkelize() {
+        // 20 rows: two groups of leaves, then the root
+        let tree = MerkleTreeBN128::merkelize(elements(5, 20), 5, 20).unwrap();
+        let mut leaves: Vec<Fr> = tree.elements.chunks(5).map(linear_hash).collect();
+        leaves.resize(32, Fr::zero());
+        assert_eq!(tree.nodes.len(), 32 + 16 + 1);
+        assert_eq!(tree.nodes[..32], leaves[..]);
+        let mut parents = vec![hash_node(&leaves[..16]), hash_node(&leaves[16..])];
+        parents.resize(16, Fr::zero());
+        assert_eq!(tree.nodes[32..48], parents[..]);
+        assert_eq!(tree.root(), hash_node(&parents));
+
+        let tree = MerkleTreeBN128::merkelize(elements(2, 1), 2, 1).unwrap();
+        assert_eq!(tree.root(), linear_hash(&tree.elements));
+        assert!(MerkleTreeBN128::merkelize(elements(2, 1), 3, 1).is_err());
+    }
+
+    #[test]
+    fn test_group_proof() {
+        let tree = MerkleTreeBN128::merkelize(elements(5, 20), 5, 20).unwrap();
+        let root = tree.root();
+        for idx in [0, 7, 16, 19] {
+            let (values, path) = tree.get_group_proof(idx).unwrap();
+            assert_eq!(values[4], tree.get_element(idx, 4));
+            assert_eq!(path.len(), 2);
+            assert!(MerkleTreeBN128::verify_group_proof(
+                &root, &path, idx, &values
+            ));
+
+            let mut bad = values.clone();
+            bad[0] = bad[0] + FGL::ONE;
+            assert!(!MerkleTreeBN128::verify_group_proof(
+                &root, &path, idx, &bad
+            ));
+            assert!(!MerkleTreeBN128::verify_group_proof(
+                &root,
+                &path,
+                idx ^ 1,
+                &values
+            ));
+        }
+        assert!(tree.get_group_proof(20).is_err());
+    }
+
+    // computed with a Python transcription of pil-stark's merklehash.bn128 and
+    // linearhash.bn128 over the Poseidon of the reference generator: the root and the
+    // SHA-256 of what `writeToFile` writes, for a level of exactly 16 leaves with two
+    // permutations per row, and for 32 leaves whose second level is padded
+    #[test]
+    fn test_pil_stark_tree() {
+        let vectors = [
+            (
+                50,
+                16,
+                17,
+                "1239767969318968113832558370078199233984191303165098279389614599433901747939",
+                "96a6d3a561c067f99149043285b4ec56aaad7cc1e2173ab9f8f28dc140685a59",
+            ),
+            (
+                5,
+                32,
+                49,
+                "4397536746025004097747445867215257062284654734902597502744374457701031281074",
+                "86df271c07929554990fd46ff291224609a3e3748a66fb6797cee6759044abe6",
+            ),
+        ];
+        for (width, height, n_nodes, root, sha) in vectors {
+            let tree = MerkleTreeBN128::merkelize(elements(width, height), width, height).unwrap();
+            assert_eq!(tree.nodes.len(), n_nodes);
+            assert_eq!(tree.root(), Fr::from_str(root).unwrap());
+
+            let mut buff = vec![];
+            tree.write(&mut buff).unwrap();
+            assert_eq!(buff.len(), 8 * (2 + width * height) + 32 * n_nodes);
+            assert_eq!(hex(&Sha256::digest(&buff)), sha);
+        }
+    }
+
+    #[test]
+    fn test_write_read() {
+        let tree = MerkleTreeBN128::merkelize(elements(5, 20), 5, 20).unwrap();
+        let mut buff = vec![];
+        tree.write(&mut buff).unwrap();
+        assert_eq!(buff.len(), 8 * (2 + 5 * 20) + 32 * 49);
+        assert_eq!(MerkleTreeBN128::read(&mut &buff[..]).unwrap(), tree);
+
+        // the Montgomery form of 1 is 2^256 mod q
+        let one = MerkleTreeBN128 {
+            nodes: vec![Fr::one()],
+            height: 1,
+            ..Default::default()
+        };
+        let mut buff = vec![];
+        one.write(&mut buff).unwrap();
+        assert_eq!(buff[16..24], 0xac96341c4ffffffbu64.to_le_bytes());
+
+        let n = buff.len();
+        buff[n - 8..].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            MerkleTreeBN128::read(&mut &buff[..]),
+            Err(PilError::CorruptTree(_))
+        ));
+    }
+}
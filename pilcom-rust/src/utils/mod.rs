@@ -1,6 +1,23 @@
+pub mod codegen;
+pub mod column_ops;
+pub mod const_tree;
+pub mod element_type;
+pub mod error;
+pub mod expr;
+pub mod f3g;
+#[cfg(test)]
+mod f3g_test;
+pub mod merklehash;
+pub mod merklehash_bn128;
+pub mod packing;
 pub mod pil_serde;
 // pub mod pol_arrary;
+pub mod pol_text;
 pub mod polarray;
 pub mod traits;
 pub mod types;
-pub mod pil_verify;
\ No newline at end of file
+pub mod pil_verify;
+pub mod report;
+pub mod stark_info;
+pub mod vcd;
+pub mod witness_file;
\ No newline at end of file
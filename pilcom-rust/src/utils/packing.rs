@@ -0,0 +1,216 @@
+#![allow(non_snake_case)]
+//! Bit-width packing of columns, the body of `Encoding::Packed` witness files.
+//!
+//! A column is stored as the offsets of its values from the smallest one, each in just
+//! enough bits for the largest offset. Columns of signed values are first shifted by
+//! `(p - 1) / 2`, which makes small negative values, stored near `p`, land right below small
+//! positive ones. On disk a column is
+//!
+//! ```text
+//! base    u64   smallest shifted value
+//! width   u8    bits per value, 0 to 64
+//! signed  u8    1 if the values were shifted
+//! values  ceil(len * width / 8) bytes, least significant bits first
+//! ```
+use fields::field_gl::{Fr as FGL, MODULUS};
+use std::io::{Read, Write};
+
+use super::element_type::ElementType;
+use super::error::{PilError, Result};
+
+const HALF: u64 = (MODULUS.0[0] - 1) / 2;
+
+fn shift(v: FGL, signed: bool) -> u64 {
+    if signed {
+        (v + FGL::from(HALF)).as_int()
+    } else {
+        v.as_int()
+    }
+}
+
+/// The value stored as `v`, which a damaged base or width can take past the modulus.
+fn unshift(v: u64, signed: bool) -> Result<FGL> {
+    if v >= MODULUS.0[0] {
+        return Err(PilError::CorruptFile(format!(
+            "bad packed column, {} is not a field element",
+            v
+        )));
+    }
+    if signed {
+        Ok(FGL::from(v) - FGL::from(HALF))
+    } else {
+        Ok(FGL::from(v))
+    }
+}
+
+/// The (base, width) packing `column` with or without the signed shift.
+fn range(column: &[FGL], signed: bool) -> (u64, u8) {
+    let (min, max) = column.iter().fold((u64::MAX, 0), |(min, max), v| {
+        let v = shift(*v, signed);
+        (min.min(v), max.max(v))
+    });
+    if column.is_empty() {
+        return (0, 0);
+    }
+    (min, (64 - (max - min).leading_zeros()) as u8)
+}
+
+/// Write `column`, packed. Signed element types are shifted, unsigned ones are not, and
+/// field columns take whichever is narrower.
+pub fn write_column<W: Write + ?Sized>(
+    w: &mut W,
+    column: &[FGL],
+    elementType: ElementType,
+) -> Result<()> {
+    let ((base, width), signed) = match elementType {
+        ElementType::S8 | ElementType::S16 | ElementType::S32 | ElementType::S64 => {
+            (range(column, true), true)
+        }
+        ElementType::U8 | ElementType::U16 | ElementType::U32 => (range(column, false), false),
+        ElementType::Field => {
+            let (unsigned, signed) = (range(column, false), range(column, true));
+            if signed.1 < unsigned.1 {
+                (signed, true)
+            } else {
+                (unsigned, false)
+            }
+        }
+    };
+    w.write_all(&base.to_le_bytes())?;
+    w.write_all(&[width, signed as u8])?;
+    if width == 0 {
+        return Ok(());
+    }
+
+    let mut out = Vec::with_capacity((column.len() * width as usize).div_ceil(8));
+    let mut acc: u128 = 0;
+    let mut bits = 0;
+    for v in column {
+        acc |= ((shift(*v, signed) - base) as u128) << bits;
+        bits += width as u32;
+        if bits >= 64 {
+            out.extend_from_slice(&(acc as u64).to_le_bytes());
+            acc >>= 64;
+            bits -= 64;
+        }
+    }
+    out.extend_from_slice(&(acc as u64).to_le_bytes()[..(bits as usize).div_ceil(8)]);
+    w.write_all(&out)?;
+    Ok(())
+}
+
+/// Read a column of `len` values written by `write_column`.
+pub fn read_column<R: Read + ?Sized>(r: &mut R, len: usize) -> Result<Vec<FGL>> {
+    let mut head = [0u8; 10];
+    r.read_exact(&mut head)?;
+    let base = u64::from_le_bytes(head[..8].try_into().unwrap());
+    let (width, signed) = (head[8] as u32, head[9] == 1);
+    if width > 64 || head[9] > 1 {
+        return Err(PilError::CorruptFile(format!(
+            "bad packed column, width {} signed {}",
+            width, head[9]
+        )));
+    }
+    if width == 0 {
+        return Ok(vec![unshift(base, signed)?; len]);
+    }
+
+    let mut bytes = vec![0u8; (len * width as usize).div_ceil(8)];
+    r.read_exact(&mut bytes)?;
+    // room to always load whole words
+    bytes.resize(bytes.len() + 8, 0);
+    let mask = if width == 64 {
+        u64::MAX
+    } else {
+        (1u64 << width) - 1
+    };
+    let mut column = Vec::with_capacity(len);
+    let mut acc: u128 = 0;
+    let mut bits = 0;
+    let mut pos = 0;
+    for _ in 0..len {
+        if bits < width {
+            let word = u64::from_le_bytes(bytes[pos..pos + 8].try_into().unwrap());
+            acc |= (word as u128) << bits;
+            bits += 64;
+            pos += 8;
+        }
+        column.push(unshift(base.wrapping_add(acc as u64 & mask), signed)?);
+        acc >>= width;
+        bits -= width;
+    }
+    Ok(column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rand;
+
+    fn round_trip(column: &[FGL], elementType: ElementType) -> usize {
+        let mut buff = vec![];
+        write_column(&mut buff, column, elementType).unwrap();
+        let len = buff.len();
+        assert_eq!(read_column(&mut &buff[..], column.len()).unwrap(), column);
+        len
+    }
+
+    #[test]
+    fn test_packing() {
+        let bits: Vec<FGL> = (0..100u64).map(|i| FGL::from(i % 2)).collect();
+        assert_eq!(round_trip(&bits, ElementType::Field), 10 + 13);
+
+        let bytes: Vec<FGL> = (0..100u64).map(|i| FGL::from(200 + i)).collect();
+        assert_eq!(round_trip(&bytes, ElementType::U8), 10 + 88);
+
+        // -3..3 takes 3 bits once shifted, 64 otherwise
+        let small: Vec<FGL> = (0..64u64)
+            .map(|i| FGL::from(i % 7) - FGL::from(3u64))
+            .collect();
+        assert_eq!(round_trip(&small, ElementType::S8), 10 + 24);
+        assert_eq!(round_trip(&small, ElementType::Field), 10 + 24);
+        assert_eq!(round_trip(&small, ElementType::U32), 10 + 64 * 8);
+
+        let constant = vec![-FGL::from(5u64); 1000];
+        assert_eq!(round_trip(&constant, ElementType::S16), 10);
+
+        let mut rng = rand::thread_rng();
+        let random: Vec<FGL> = (0..37).map(|_| FGL::rand(&mut rng)).collect();
+        round_trip(&random, ElementType::Field);
+        let extremes = vec![FGL::ZERO, -FGL::ONE, FGL::from(HALF), FGL::from(HALF + 1)];
+        round_trip(&extremes, ElementType::Field);
+        round_trip(&extremes, ElementType::S64);
+        round_trip(&[], ElementType::Field);
+    }
+
+    #[test]
+    fn test_corrupt_column() {
+        // p - 8 to p - 1: a base of p - 8 and 3 bits, followed by the next column
+        let column: Vec<FGL> = (0..8u64).map(|i| -FGL::from(8 - i)).collect();
+        let mut buff = vec![];
+        write_column(&mut buff, &column, ElementType::Field).unwrap();
+        assert_eq!((buff[8], buff.len()), (3, 10 + 3));
+        write_column(&mut buff, &column, ElementType::Field).unwrap();
+        let read = |buff: &[u8]| read_column(&mut &buff[..], column.len());
+        assert_eq!(read(&buff).unwrap(), column);
+
+        // a base one larger takes the last value to p
+        let mut bad = buff.clone();
+        bad[..8].copy_from_slice(&(MODULUS.0[0] - 7).to_le_bytes());
+        assert!(matches!(read(&bad), Err(PilError::CorruptFile(_))));
+
+        // read 4 bits at a time, the bits of 0 and 1 make an offset of 8
+        let mut bad = buff.clone();
+        bad[8] = 4;
+        assert!(matches!(read(&bad), Err(PilError::CorruptFile(_))));
+
+        // a constant column, of width 0
+        let mut bad = vec![];
+        write_column(&mut bad, &[FGL::ONE; 3], ElementType::Field).unwrap();
+        bad[..8].copy_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            read_column(&mut &bad[..], 3),
+            Err(PilError::CorruptFile(_))
+        ));
+    }
+}
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use super::error::Result;
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Pil {
     #[serde(rename = "nCommitments")]
@@ -113,7 +115,7 @@ struct PilExpressionValues {
     op: String,
     deg: u64,
     #[serde(skip_serializing_if = "Option::is_none")]
-    values: Option<Vec<Box<PilExpressionValues>>>,
+    values: Option<Vec<PilExpressionValues>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     value: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -123,12 +125,12 @@ struct PilExpressionValues {
 }
 
 impl Pil {
-    pub fn from_json_str(json: &str) -> Result<Pil, serde_json::Error> {
-        serde_json::from_str(json)
+    pub fn from_json_str(json: &str) -> Result<Pil> {
+        Ok(serde_json::from_str(json)?)
     }
 
-    pub fn from_json_file(file: &str) -> Result<Pil, serde_json::Error> {
-        let json = std::fs::read_to_string(file).unwrap();
+    pub fn from_json_file(file: &str) -> Result<Pil> {
+        let json = std::fs::read_to_string(file)?;
         Pil::from_json_str(&json)
     }
 }
@@ -146,7 +148,7 @@ mod test {
 
     #[test]
     fn test_pol_array() {
-        let pil = Pil::from_json_file("test_data/pil/main.pil.json").unwrap();
+        let _ = Pil::from_json_file("test_data/pil/main.pil.json").unwrap();
 
     }
 }
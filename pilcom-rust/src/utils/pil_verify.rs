@@ -1,16 +1,23 @@
 #![allow(non_snake_case)]
-use core::panic;
 use fields::{
     field_gl::{Fr as FGL, MODULUS},
-    Field, PrimeField,
+    Field,
+};
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::{HashMap, HashSet},
 };
-use std::{cell::RefCell, collections::HashMap, hash::Hash};
 
 use crate::utils::types::Reference;
 
 use super::{
+    column_ops,
+    error::{get_checked, PilError, Result},
+    expr::{Expr, TypedExpression},
     polarray::PolsArray,
-    types::{Expression, PIL},
+    report::{FailureReason, IdentityKind, VerificationFailure, VerificationReport},
+    types::PIL,
 };
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -30,26 +37,16 @@ struct PolsArr {
     v_n: Vec<FGL>,
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct Pols {
-    cm: Vec<PolsArr>,
     exps: Vec<PolsArr>,
-    consts: Vec<PolsArr>,
     publics: Vec<FGL>,
     p: Vec<PolsArr>,
 }
 
-impl Default for Pols {
-    fn default() -> Self {
-        Pols {
-            cm: vec![],
-            exps: vec![],
-            consts: vec![],
-            publics: vec![],
-            p: vec![],
-        }
-    }
-}
+/// The position `(j, i)` of every `ks[j] * w^i` of the connection domain, indexed by the
+/// top 12, the next 12 and the low 40 bits of its value.
+type ConnectionMap = HashMap<u64, HashMap<u64, HashMap<u64, (u64, u64)>>>;
 
 pub struct PilVerify<'a> {
     pil: &'a PIL,
@@ -57,8 +54,8 @@ pub struct PilVerify<'a> {
     const_pols: &'a PolsArray,
     pols: &'a mut Pols,
     N: usize,
-    cache_connections_map:
-        RefCell<HashMap<String, HashMap<u64, HashMap<u64, HashMap<u64, (u64, u64)>>>>>,
+    max_errors: Option<usize>,
+    cache_connections_map: RefCell<HashMap<String, ConnectionMap>>,
 }
 
 impl PilVerify<'_> {
@@ -74,17 +71,18 @@ impl PilVerify<'_> {
             const_pols,
             pols,
             N: cm_pols.n,
+            max_errors: None,
             cache_connections_map: HashMap::new().into(),
         }
     }
 
+    /// Stop verifying once `max_errors` failures have been found.
+    pub fn set_max_errors(&mut self, max_errors: Option<usize>) {
+        self.max_errors = max_errors;
+    }
+
     pub fn get_roots(&self) -> Vec<FGL> {
-        let mut roots = vec![FGL::zero(); 33];
-        roots[32] = FGL::from(7277203076849721926);
-        for i in (0..32).rev() {
-            roots[i] = roots[i + 1] * roots[i + 1];
-        }
-        roots
+        fields::fft::roots()
     }
 
     pub fn get_Ks(&self, n: usize) -> Vec<FGL> {
@@ -98,12 +96,8 @@ impl PilVerify<'_> {
         ks
     }
 
-    pub fn get_connection_map(
-        &self,
-        nk: usize,
-    ) -> HashMap<u64, HashMap<u64, HashMap<u64, (u64, u64)>>> {
-        let kc =
-            MODULUS.to_string() + "_" + self.N.to_string().as_str() + "_" + nk.to_string().as_str();
+    pub fn get_connection_map(&self, n: usize, nk: usize) -> ConnectionMap {
+        let kc = MODULUS.to_string() + "_" + n.to_string().as_str() + "_" + nk.to_string().as_str();
         log::trace!("nk: {} kc: {}", nk, kc);
 
         if self.cache_connections_map.borrow_mut().contains_key(&kc) {
@@ -112,17 +106,17 @@ impl PilVerify<'_> {
             return v.clone();
         }
 
-        let mut m: HashMap<u64, HashMap<u64, HashMap<u64, (u64, u64)>>> = HashMap::new();
-        let pow = self.N.ilog2();
+        let mut m: ConnectionMap = HashMap::new();
+        let pow = n.ilog2();
         let roots = self.get_roots();
         let wi = roots[pow as usize];
         let mut w = FGL::one();
         let mut ks = vec![FGL::one()];
         ks.extend(self.get_Ks(nk - 1));
 
-        for i in 0..self.N {
+        for i in 0..n {
             if (i % 10000) == 0 {
-                log::trace!("Building cm..  {} / {}", i, self.N);
+                log::trace!("Building cm..  {} / {}", i, n);
             }
             for j in 0..ks.len() {
                 let a = ks[j] * w;
@@ -130,9 +124,9 @@ impl PilVerify<'_> {
                 let a2 = (a.as_int() >> 40) & 0xFFF;
                 let a3 = a.as_int() & 0xFFFFFFFFFF;
                 m.entry(a1)
-                    .or_insert_with(HashMap::new)
+                    .or_default()
                     .entry(a2)
-                    .or_insert_with(HashMap::new)
+                    .or_default()
                     .insert(a3, (j as u64, i as u64));
                 log::trace!("insert to cm: a: {} a1: {} a2: {} a3: {} i: {} j: {} ks_len: {} ks[{}]: {} w:{}", a.as_int(), a1, a2, a3, i, j, ks.len(), j, ks[j].as_int(), w.as_int());
             }
@@ -145,128 +139,239 @@ impl PilVerify<'_> {
         m
     }
 
-    pub fn eval(&self, exp: &Expression) -> Vec<FGL> {
-        let mut a: Vec<FGL> = vec![];
-        let mut b: Vec<FGL> = vec![];
-        let mut c: FGL = FGL::zero();
+    /// Evaluate `exp` on every row. Numbers and publics take the degree of the polynomials
+    /// next to them, or the largest one when there are none.
+    pub fn eval(&self, exp: &TypedExpression) -> Result<Vec<FGL>> {
+        let n = self.rows(exp).unwrap_or(self.N);
+        self.eval_n(exp, n)
+    }
 
-        if exp.op == "add" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            b = self.eval(&exp.values.clone().unwrap()[1]);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = a[i] + b[i];
-            }
-            return r;
-        } else if exp.op == "sub" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            b = self.eval(&exp.values.clone().unwrap()[1]);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = a[i] - b[i];
+    fn eval_n(&self, exp: &TypedExpression, n: usize) -> Result<Vec<FGL>> {
+        match &exp.expr {
+            Expr::Add(a, b) => Ok(column_ops::add(&self.eval_n(a, n)?, &self.eval_n(b, n)?)),
+            Expr::Sub(a, b) => Ok(column_ops::sub(&self.eval_n(a, n)?, &self.eval_n(b, n)?)),
+            Expr::Mul(a, b) => Ok(column_ops::mul(&self.eval_n(a, n)?, &self.eval_n(b, n)?)),
+            Expr::AddC(a, c) => Ok(column_ops::add_scalar(&self.eval_n(a, n)?, from_i64(*c))),
+            Expr::MulC(a, c) => Ok(column_ops::mul_scalar(&self.eval_n(a, n)?, from_i64(*c))),
+            Expr::Neg(a) => Ok(column_ops::neg(&self.eval_n(a, n)?)),
+            Expr::Cm { id, next } => Ok(self.shifted(pol_column(self.cm_pols, *id, "cm")?, *next)),
+            Expr::Const { id, next } => {
+                Ok(self.shifted(pol_column(self.const_pols, *id, "const")?, *next))
             }
-            return r;
-        } else if exp.op == "mul" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            b = self.eval(&exp.values.clone().unwrap()[1]);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = a[i] * b[i];
+            Expr::Exp { id, next } => {
+                let v = &get_checked(&self.pols.exps, *id, "exp")?.v_n;
+                Ok(self.shifted(Cow::Borrowed(v), *next))
             }
-            return r;
-        } else if exp.op == "addc" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            c = FGL::from(exp.const_.unwrap() as u64);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = a[i] + c;
+            Expr::Number(v) => Ok(vec![*v; n]),
+            Expr::Public(id) => {
+                let v = *get_checked(&self.pols.publics, *id, "public")?;
+                Ok(vec![v; n])
             }
-            return r;
-        } else if exp.op == "mulc" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            c = FGL::from(exp.const_.unwrap() as u64);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = a[i] * c;
-            }
-            return r;
-        } else if exp.op == "neg" {
-            a = self.eval(&exp.values.clone().unwrap()[0]);
-            let mut r: Vec<FGL> = vec![FGL::zero(); a.len()];
-            for i in 0..a.len() {
-                r[i] = -a[i];
-            }
-            return r;
-        } else if exp.op == "cm" {
-            let mut r: Vec<FGL> = self.pols.cm[exp.id.unwrap()].v_n.clone();
-            if exp.next() {
-                r = self.get_prime(&r)
-            };
-            return r;
-        } else if exp.op == "const" {
-            let mut r: Vec<FGL> = self.pols.consts[exp.id.unwrap()].v_n.clone();
-            if exp.next() {
-                r = self.get_prime(&r)
-            };
-            return r;
-        } else if exp.op == "exp" {
-            let mut r: Vec<FGL> = self.pols.exps[exp.id.unwrap()].v_n.clone();
-            if exp.next() {
-                r = self.get_prime(&r)
-            };
-            return r;
-        } else if exp.op == "number" {
-            let v = FGL::from(exp.value.clone().unwrap().parse::<u64>().unwrap());
-            let mut r: Vec<FGL> = vec![FGL::zero(); self.N];
-            for i in 0..self.N {
-                r[i] = v;
-            }
-            return r;
-        } else if exp.op == "public" {
-            let mut r: Vec<FGL> = vec![FGL::zero(); self.N];
-            for i in 0..self.N {
-                r[i] = self.pols.publics[exp.id.unwrap()];
+        }
+    }
+
+    /// Rows of the first polynomial `exp` reads, `None` when it reads none. The expressions
+    /// it refers to must have been calculated.
+    fn rows(&self, exp: &TypedExpression) -> Option<usize> {
+        match &exp.expr {
+            Expr::Cm { id, .. } => self.cm_pols.defArray.get(*id).map(|p| p.polDeg),
+            Expr::Const { id, .. } => self.const_pols.defArray.get(*id).map(|p| p.polDeg),
+            Expr::Exp { id, .. } => self.pols.exps.get(*id).map(|e| e.v_n.len()),
+            Expr::Number(_) | Expr::Public(_) => None,
+            _ => exp.operands().into_iter().find_map(|e| self.rows(e)),
+        }
+    }
+
+    /// The degree of every expression, `None` for the ones reading no polynomial. Fails when
+    /// an expression mixes polynomials of different degrees.
+    pub fn expression_degrees(&self) -> Result<Vec<Option<usize>>> {
+        let mut degs = vec![None; self.pil.expressions.len()];
+        let mut visiting = vec![false; self.pil.expressions.len()];
+        for id in 0..self.pil.expressions.len() {
+            self.expression_degree(id, &mut degs, &mut visiting)?;
+        }
+        Ok(degs.into_iter().map(|d| d.unwrap()).collect())
+    }
+
+    fn expression_degree(
+        &self,
+        id: usize,
+        degs: &mut Vec<Option<Option<usize>>>,
+        visiting: &mut Vec<bool>,
+    ) -> Result<Option<usize>> {
+        if let Some(d) = get_checked(degs, id, "expression")? {
+            return Ok(*d);
+        }
+        if visiting[id] {
+            return Err(PilError::MalformedPil(format!(
+                "expression {} depends on itself",
+                id
+            )));
+        }
+        visiting[id] = true;
+        let d = self.node_degree(id, &self.pil.expressions[id], degs, visiting)?;
+        visiting[id] = false;
+        degs[id] = Some(d);
+        Ok(d)
+    }
+
+    fn node_degree(
+        &self,
+        id: usize,
+        exp: &TypedExpression,
+        degs: &mut Vec<Option<Option<usize>>>,
+        visiting: &mut Vec<bool>,
+    ) -> Result<Option<usize>> {
+        match &exp.expr {
+            Expr::Cm { id, .. } => Ok(Some(get_checked(&self.cm_pols.defArray, *id, "cm")?.polDeg)),
+            Expr::Const { id, .. } => Ok(Some(
+                get_checked(&self.const_pols.defArray, *id, "const")?.polDeg,
+            )),
+            Expr::Exp { id, .. } => self.expression_degree(*id, degs, visiting),
+            Expr::Number(_) | Expr::Public(_) => Ok(None),
+            _ => {
+                let mut deg = None;
+                for e in exp.operands() {
+                    let d = self.node_degree(id, e, degs, visiting)?;
+                    deg = same_degree(deg, d)
+                        .map_err(|(a, b)| mixed_degrees(format!("expression {}", id), a, b))?;
+                }
+                Ok(deg)
             }
-            return r;
+        }
+    }
+
+    fn shifted(&self, v: Cow<[FGL]>, next: bool) -> Vec<FGL> {
+        if next {
+            self.get_prime(&v)
         } else {
-            panic!("Unknown operation: {}", exp.op);
+            v.into_owned()
         }
     }
 
-    pub fn get_prime(&self, p: &Vec<FGL>) -> Vec<FGL> {
+    pub fn get_prime(&self, p: &[FGL]) -> Vec<FGL> {
         let mut r = p[1..].to_vec();
-        r[p.len() - 1] = p[0];
+        r.push(p[0]);
         r
     }
 
-    pub fn calculate_expressions(&mut self, exp_id: usize) -> Vec<FGL> {
+    pub fn calculate_expressions(&mut self, exp_id: usize) -> Result<Vec<FGL>> {
         log::trace!("Calculating expressions {}", exp_id);
-        if self.pols.exps.len() - 1 >= exp_id && self.pols.exps[exp_id].v_n.len() > 0 {
-            return self.pols.exps[exp_id].v_n.clone();
+        let pil = self.pil;
+        let exp = get_checked(&pil.expressions, exp_id, "expression")?;
+        if !self.pols.exps[exp_id].v_n.is_empty() {
+            return Ok(self.pols.exps[exp_id].v_n.clone());
         }
-        self.calculate_dependencies(&self.pil.expressions[exp_id]);
-        let p = self.eval(&self.pil.expressions[exp_id]);
+        self.calculate_dependencies(exp)?;
+        let p = self.eval(exp)?;
 
         self.pols.exps[exp_id].v_n = p.clone();
-        return self.pols.exps[exp_id].v_n.clone();
+        Ok(p)
+    }
+
+    pub fn calculate_dependencies(&mut self, exp: &TypedExpression) -> Result<()> {
+        if let Expr::Exp { id, .. } = exp.expr {
+            self.calculate_expressions(id)?;
+        }
+        for value in exp.operands() {
+            self.calculate_dependencies(value)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate `exps` and collect, for every row where the selector `sel` is not zero, the
+    /// tuple of their values along with the row index. A missing selector selects every row.
+    pub fn selected_tuples(
+        &mut self,
+        exps: &[usize],
+        sel: Option<usize>,
+    ) -> Result<Vec<(usize, Vec<u64>)>> {
+        for e in exps {
+            self.calculate_expressions(*e)?;
+        }
+        if let Some(s) = sel {
+            self.calculate_expressions(s)?;
+        }
+
+        let n = exps
+            .first()
+            .or(sel.as_ref())
+            .map_or(0, |e| self.pols.exps[*e].v_n.len());
+        let mut tuples = vec![];
+        for j in 0..n {
+            if let Some(s) = sel {
+                if self.pols.exps[s].v_n[j].is_zero() {
+                    continue;
+                }
+            }
+            let vals: Vec<u64> = exps
+                .iter()
+                .map(|e| self.pols.exps[*e].v_n[j].as_int())
+                .collect();
+            tuples.push((j, vals));
+        }
+        Ok(tuples)
     }
 
-    pub fn calculate_dependencies(&mut self, exp: &Expression) {
-        if exp.op == "exp" {
-            self.calculate_expressions(exp.id.unwrap());
+    fn clear_expressions(&mut self, exps: &[usize], sel: Option<usize>) {
+        for e in exps {
+            self.pols.exps[*e].v_n = vec![];
+        }
+        if let Some(s) = sel {
+            self.pols.exps[s].v_n = vec![];
         }
-        if exp.values.is_some() {
-            for i in 0..exp.values.clone().unwrap().len() {
-                self.calculate_dependencies(&exp.values.clone().unwrap()[i]);
+    }
+
+    /// Compute the values of the publics, which expressions may read.
+    fn calculate_publics(&mut self) -> Result<()> {
+        self.pols.publics = vec![FGL::zero(); self.pil.publics.len()];
+        for i in 0..self.pil.publics.len() {
+            log::trace!("preparing public {} / {}", i, self.pil.publics.len());
+            let public = &self.pil.publics[i];
+            if public.polType == "cmP" {
+                let pol = pol_column(self.cm_pols, public.polId, "cm")?;
+                self.pols.publics[i] = *get_checked(&pol, public.idx, "row")?;
+            } else if public.polType == "imP" {
+                let p = self.calculate_expressions(public.polId)?;
+                self.pols.publics[i] = *get_checked(&p, public.idx, "row")?;
+                self.pols.exps[public.polId].v_n = vec![];
+            } else {
+                return Err(PilError::MalformedPil(format!(
+                    "Unknown public type: {}",
+                    public.polType
+                )));
             }
         }
+        Ok(())
     }
 
-    pub fn verify_pil(&mut self) -> Vec<String> {
+    /// The values of every intermediate (`imP`) polynomial, with its reference name, in id
+    /// order.
+    pub fn intermediates(&mut self) -> Result<Vec<(String, Vec<FGL>)>> {
+        self.pols.exps = vec![PolsArr { v_n: vec![] }; self.pil.expressions.len()];
+        self.calculate_publics()?;
+
+        let mut ims: Vec<(&String, usize)> = self
+            .pil
+            .references
+            .iter()
+            .filter(|(_, r)| r.type_ == "imP")
+            .map(|(name, r)| (name, r.id))
+            .collect();
+        ims.sort_by_key(|(_, id)| *id);
+        ims.into_iter()
+            .map(|(name, id)| Ok((name.clone(), self.calculate_expressions(id)?)))
+            .collect()
+    }
+
+    pub fn verify_pil(&mut self) -> Result<VerificationReport> {
         let mut ref_cm: HashMap<usize, PilReferenceWithName<Reference>> = HashMap::new();
         let mut ref_const: HashMap<usize, PilReferenceWithName<Reference>> = HashMap::new();
         let mut ref_im: HashMap<usize, PilReferenceWithName<Reference>> = HashMap::new();
-        let mut res: Vec<String> = vec![];
+        let mut res = match self.max_errors {
+            Some(max) => VerificationReport::with_max_failures(max),
+            None => VerificationReport::default(),
+        };
 
         for (refName, ref_) in self.pil.references.iter() {
             let ref_with_name = PilReferenceWithName::new(ref_.clone(), refName.clone());
@@ -277,64 +382,196 @@ impl PilVerify<'_> {
             } else if ref_.type_ == "imP" {
                 ref_im.insert(ref_.id, ref_with_name);
             } else {
-                panic!("Unknown type of reference: {}", ref_.type_);
+                return Err(PilError::MalformedPil(format!(
+                    "Unknown type of reference: {}",
+                    ref_.type_
+                )));
             }
         }
 
-        self.pols.cm = vec![PolsArr { v_n: vec![] }; self.pil.nCommitments];
         self.pols.exps = vec![PolsArr { v_n: vec![] }; self.pil.expressions.len()];
-        self.pols.consts = vec![PolsArr { v_n: vec![] }; self.pil.nConstants];
 
-        // 1.- Prepare commited polynomials.
-        for i in 0..self.cm_pols.nPols {
-            self.pols.cm[i].v_n = self.cm_pols.array[i].clone();
+        // 1.- Check the commited and constant polynomials, they are read from the arrays when
+        // needed.
+        if self.cm_pols.nPols != self.pil.nCommitments {
+            return Err(PilError::SizeMismatch {
+                expected: self.pil.nCommitments,
+                actual: self.cm_pols.nPols,
+            });
         }
-        for i in 0..self.const_pols.nPols {
-            self.pols.consts[i].v_n = self.const_pols.array[i].clone();
+        if self.const_pols.nPols != self.pil.nConstants {
+            return Err(PilError::SizeMismatch {
+                expected: self.pil.nConstants,
+                actual: self.const_pols.nPols,
+            });
         }
-        self.pols.publics = vec![FGL::zero(); self.pil.publics.len()];
-        // self.pols.exps
 
-        for i in 0..self.pil.publics.len() {
-            log::trace!("preparing public {} / {}", i, self.pil.publics.len());
-            if self.pil.publics[i].polType == "cmP" {
-                self.pols.publics[i] =
-                    self.pols.cm[self.pil.publics[i].polId].v_n[self.pil.publics[i].idx].clone();
-            } else if self.pil.publics[i].polType == "imP" {
-                self.calculate_expressions(self.pil.publics[i].polId);
-                self.pols.publics[i] =
-                    self.pols.exps[self.pil.publics[i].polId].v_n[self.pil.publics[i].idx].clone();
-                self.pols.exps[self.pil.publics[i].polId].v_n = vec![];
-            } else {
-                panic!("Unknown public type: {}", self.pil.publics[i].polType);
+        // 2.- Check that every identity compares polynomials of a single degree.
+        self.check_degrees()?;
+
+        self.calculate_publics()?;
+
+        for i in 0..self.pil.plookupIdentities.len() {
+            log::trace!(
+                "Checking plookupIdentities {} / {}",
+                i + 1,
+                self.pil.plookupIdentities.len()
+            );
+            let pi = self.pil.plookupIdentities[i].clone();
+            let pi_f = pi.f.clone().unwrap_or_default();
+            let pi_t = pi.t.clone().unwrap_or_default();
+
+            let t: HashSet<Vec<u64>> = self
+                .selected_tuples(&pi_t, pi.selT)?
+                .into_iter()
+                .map(|(_, vals)| vals)
+                .collect();
+            for (j, vals) in self.selected_tuples(&pi_f, pi.selF)? {
+                if !t.contains(&vals) {
+                    res.push(VerificationFailure {
+                        kind: IdentityKind::Plookup,
+                        identity: i,
+                        fileName: pi.fileName.clone(),
+                        line: pi.line,
+                        row: j,
+                        expIds: pi_f.clone(),
+                        values: vals,
+                        reason: FailureReason::NotFound,
+                    });
+                }
+            }
+
+            self.clear_expressions(&pi_t, pi.selT);
+            self.clear_expressions(&pi_f, pi.selF);
+            if res.is_full() {
+                return Ok(res);
             }
         }
 
-        for i in 0..self.pil.connectionIdentities.clone().unwrap().len() {
+        let permutation_identities = self.pil.permutationIdentities.clone().unwrap_or_default();
+        for (i, pi) in permutation_identities.iter().enumerate() {
             log::trace!(
-                "Checking connectionIdentities {} / {}",
+                "Checking permutationIdentities {} / {}",
                 i + 1,
-                self.pil.connectionIdentities.clone().unwrap().len()
+                permutation_identities.len()
             );
-            let ci = self.pil.connectionIdentities.clone().unwrap()[i].clone();
+            let pi_f = pi.f.clone().unwrap_or_default();
+            let pi_t = pi.t.clone().unwrap_or_default();
+
+            let mut counts: HashMap<Vec<u64>, TupleCount> = HashMap::new();
+            for (j, vals) in self.selected_tuples(&pi_t, pi.selT)? {
+                let c = counts.entry(vals).or_default();
+                c.t += 1;
+                c.row_t.get_or_insert(j);
+            }
+            for (j, vals) in self.selected_tuples(&pi_f, pi.selF)? {
+                let c = counts.entry(vals).or_default();
+                c.f += 1;
+                c.row_f.get_or_insert(j);
+            }
 
-            let ci_pols = ci.pols.clone().unwrap();
-            let ci_cons = ci.connections.clone().unwrap();
+            let mut mismatches: Vec<(Vec<u64>, TupleCount)> =
+                counts.into_iter().filter(|(_, c)| c.f != c.t).collect();
+            mismatches.sort_by_key(|(_, c)| c.row_f.or(c.row_t));
+            for (vals, c) in mismatches {
+                let (row, expIds, reason) = if c.f > c.t {
+                    (
+                        c.row_f.unwrap(),
+                        pi_f.clone(),
+                        FailureReason::OverRepresented {
+                            fCount: c.f,
+                            tCount: c.t,
+                        },
+                    )
+                } else {
+                    (
+                        c.row_t.unwrap(),
+                        pi_t.clone(),
+                        FailureReason::UnderRepresented {
+                            fCount: c.f,
+                            tCount: c.t,
+                        },
+                    )
+                };
+                res.push(VerificationFailure {
+                    kind: IdentityKind::Permutation,
+                    identity: i,
+                    fileName: pi.fileName.clone(),
+                    line: pi.line,
+                    row,
+                    expIds,
+                    values: vals,
+                    reason,
+                });
+            }
 
-            for j in 0..ci_pols.len() {
-                self.calculate_expressions(ci_pols[j]);
+            self.clear_expressions(&pi_t, pi.selT);
+            self.clear_expressions(&pi_f, pi.selF);
+            if res.is_full() {
+                return Ok(res);
+            }
+        }
+
+        for i in 0..self.pil.polIdentities.len() {
+            log::trace!(
+                "Checking polIdentities {} / {}",
+                i + 1,
+                self.pil.polIdentities.len()
+            );
+            let pi = self.pil.polIdentities[i].clone();
+            let p = self.calculate_expressions(pi.e)?;
+            for (j, v) in p.iter().enumerate() {
+                if !v.is_zero() {
+                    res.push(VerificationFailure {
+                        kind: IdentityKind::Pol,
+                        identity: i,
+                        fileName: pi.fileName.clone(),
+                        line: pi.line,
+                        row: j,
+                        expIds: vec![pi.e],
+                        values: vec![v.as_int()],
+                        reason: FailureReason::NotZero,
+                    });
+                }
             }
-            for j in 0..ci_cons.len() {
-                self.calculate_expressions(ci_cons[j]);
+            self.pols.exps[pi.e].v_n = vec![];
+            if res.is_full() {
+                return Ok(res);
             }
+        }
+
+        let connection_identities = self.pil.connectionIdentities.clone().unwrap_or_default();
+        for (i, ci) in connection_identities.iter().enumerate() {
+            log::trace!(
+                "Checking connectionIdentities {} / {}",
+                i + 1,
+                connection_identities.len()
+            );
+
+            let ci_pols = ci.pols.clone().unwrap_or_default();
+            let ci_cons = ci.connections.clone().unwrap_or_default();
+            if ci_pols.len() != ci_cons.len() {
+                return Err(PilError::MalformedPil(format!(
+                    "{}:{}: connection identity with {} pols and {} connections",
+                    ci.fileName,
+                    ci.line,
+                    ci_pols.len(),
+                    ci_cons.len()
+                )));
+            }
+
+            for e in ci_pols.iter().chain(ci_cons.iter()) {
+                self.calculate_expressions(*e)?;
+            }
+            let n = ci_pols.first().map_or(0, |e| self.pols.exps[*e].v_n.len());
             log::trace!("start generating cm");
-            let cm = self.get_connection_map(ci_pols.len());
+            let cm = self.get_connection_map(n, ci_pols.len());
             log::trace!("cm {:?}", cm);
 
-            for mut j in 0..ci_pols.len() {
-                for mut k in 0..self.N {
+            for j in 0..ci_pols.len() {
+                for k in 0..n {
                     if k % 10000 == 0 {
-                        log::trace!("{} / {}", k+1, self.N);
+                        log::trace!("{} / {}", k + 1, n);
                     }
                     let v1 = self.pols.exps[ci_pols[j]].v_n[k].as_int();
                     let a = self.pols.exps[ci_cons[j]].v_n[k].as_int();
@@ -350,43 +587,531 @@ impl PilVerify<'_> {
                         a3,
                         a
                     );
-                    let get_res = cm.get(&a1).unwrap().get(&a2).unwrap().get(&a3);
-                    
+                    let get_res = cm
+                        .get(&a1)
+                        .and_then(|m| m.get(&a2))
+                        .and_then(|m| m.get(&a3));
+
                     match get_res {
                         Some((cp, cw)) => {
                             log::debug!("cp={} cw={} a1={} a2={} a3={}", cp, cw, a1, a2, a3);
                             let v2 =
                                 self.pols.exps[ci_pols[*cp as usize]].v_n[*cw as usize].as_int();
-                            if FGL::from(v1) != FGL::from(v2) {
-                                let log_str = format!("{:?}:{:?}: connection does not match p1={:?} w1={:?} p2={:?} w2={:?} val= {} != {}",ci.fileName, self.pil.connectionIdentities.clone().unwrap()[i].line,j,k,cp,cw,v1,v2);
-                                log::error!("{}", log_str);
-                                k = self.N;
-                                j = ci_pols.len();
+                            if v1 != v2 {
+                                res.push(VerificationFailure {
+                                    kind: IdentityKind::Connection,
+                                    identity: i,
+                                    fileName: ci.fileName.clone(),
+                                    line: ci.line,
+                                    row: k,
+                                    expIds: vec![ci_pols[j], ci_pols[*cp as usize]],
+                                    values: vec![v1, v2],
+                                    reason: FailureReason::ConnectionMismatch {
+                                        p1: j,
+                                        p2: *cp as usize,
+                                        w2: *cw as usize,
+                                    },
+                                });
                             }
                         }
                         None => {
-                            let log_str = format!(
-                                "{:?}:{:?}: invalid copy value w={:?},{:?} val={:?}",
-                                ci.fileName,
-                                self.pil.connectionIdentities.clone().unwrap()[i].line,
-                                j,
-                                k,
-                                v1
-                            );
-                            log::error!("{}", log_str);
-                            res.push(format!("{}", log_str));
+                            res.push(VerificationFailure {
+                                kind: IdentityKind::Connection,
+                                identity: i,
+                                fileName: ci.fileName.clone(),
+                                line: ci.line,
+                                row: k,
+                                expIds: vec![ci_pols[j]],
+                                values: vec![v1],
+                                reason: FailureReason::InvalidCopy { p1: j },
+                            });
                         }
                     }
                 }
             }
-            for j in 0..ci_pols.len() {
-                self.pols.exps[ci_pols[j]].v_n = vec![];
+            for &e in ci_pols.iter().chain(ci_cons.iter()) {
+                self.pols.exps[e].v_n = vec![];
             }
-            for j in 0..ci_cons.len() {
-                self.pols.exps[ci_cons[j]].v_n = vec![];
+            if res.is_full() {
+                return Ok(res);
             }
         }
 
-        res
+        Ok(res)
+    }
+
+    /// Fail when an identity mixes polynomials of different degrees: the two sides of a
+    /// plookup or a permutation may differ, but each one, along with its selector, must
+    /// have a single degree, and so must the pols and connections of a connection identity.
+    fn check_degrees(&self) -> Result<()> {
+        let degs = self.expression_degrees()?;
+
+        for (i, pi) in self.pil.plookupIdentities.iter().enumerate() {
+            let what = |side| format!("{}:{}: plookup {} {}", pi.fileName, pi.line, i, side);
+            let f = pi.f.iter().flatten().chain(pi.selF.iter());
+            check_same_degree(&degs, f, || what("f"))?;
+            let t = pi.t.iter().flatten().chain(pi.selT.iter());
+            check_same_degree(&degs, t, || what("t"))?;
+        }
+        for (i, pi) in self.pil.permutationIdentities.iter().flatten().enumerate() {
+            let what = |side| format!("{}:{}: permutation {} {}", pi.fileName, pi.line, i, side);
+            let f = pi.f.iter().flatten().chain(pi.selF.iter());
+            check_same_degree(&degs, f, || what("f"))?;
+            let t = pi.t.iter().flatten().chain(pi.selT.iter());
+            check_same_degree(&degs, t, || what("t"))?;
+        }
+        for (i, ci) in self.pil.connectionIdentities.iter().flatten().enumerate() {
+            let exps = ci
+                .pols
+                .iter()
+                .flatten()
+                .chain(ci.connections.iter().flatten());
+            check_same_degree(&degs, exps, || {
+                format!("{}:{}: connection {}", ci.fileName, ci.line, i)
+            })?;
+        }
+        Ok(())
+    }
+}
+
+fn check_same_degree<'b>(
+    degs: &[Option<usize>],
+    exps: impl Iterator<Item = &'b usize>,
+    what: impl Fn() -> String,
+) -> Result<()> {
+    let mut deg = None;
+    for e in exps {
+        let d = *get_checked(degs, *e, "expression")?;
+        deg = same_degree(deg, d).map_err(|(a, b)| mixed_degrees(what(), a, b))?;
+    }
+    Ok(())
+}
+
+/// The degree of a value combining values of degree `a` and `b`, or both when they differ.
+fn same_degree(
+    a: Option<usize>,
+    b: Option<usize>,
+) -> std::result::Result<Option<usize>, (usize, usize)> {
+    match (a, b) {
+        (Some(a), Some(b)) if a != b => Err((a, b)),
+        _ => Ok(a.or(b)),
+    }
+}
+
+fn mixed_degrees(what: String, a: usize, b: usize) -> PilError {
+    PilError::MalformedPil(format!(
+        "{} mixes polynomials of degree {} and {}",
+        what, a, b
+    ))
+}
+
+fn pol_column<'b>(pols: &'b PolsArray, id: usize, kind: &'static str) -> Result<Cow<'b, [FGL]>> {
+    if id >= pols.nPols {
+        return Err(PilError::OutOfRange {
+            kind,
+            id,
+            len: pols.nPols,
+        });
+    }
+    Ok(pols.column(id))
+}
+
+fn from_i64(c: i64) -> FGL {
+    let v = FGL::from(c.unsigned_abs());
+    if c < 0 {
+        -v
+    } else {
+        v
+    }
+}
+
+/// Occurrences of a tuple on each side of a permutation identity, and the first row where
+/// it was seen.
+#[derive(Debug, Default, Clone)]
+struct TupleCount {
+    f: usize,
+    t: usize,
+    row_f: Option<usize>,
+    row_t: Option<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polarray::PolKind;
+    use crate::utils::types::read_json;
+
+    // namespace Fibonacci(8);
+    //     pol constant LLAST;
+    //     pol commit a, b;
+    //     (a' - b) * (1 - LLAST) = 0;
+    //     (b' - (a + b)) * (1 - LLAST) = 0;
+    const FIB_PIL: &str = r#"
+    {
+        "nCommitments": 2,
+        "nQ": 0,
+        "nIm": 0,
+        "nConstants": 1,
+        "publics": [],
+        "references": {
+            "Fibonacci.LLAST": { "type": "constP", "id": 0, "polDeg": 8, "isArray": false },
+            "Fibonacci.a": { "type": "cmP", "id": 0, "polDeg": 8, "isArray": false },
+            "Fibonacci.b": { "type": "cmP", "id": 1, "polDeg": 8, "isArray": false }
+        },
+        "expressions": [
+            { "op": "mul", "deg": 2, "values": [
+                { "op": "sub", "deg": 1, "values": [
+                    { "op": "cm", "deg": 1, "id": 0, "next": true },
+                    { "op": "cm", "deg": 1, "id": 1 }
+                ] },
+                { "op": "sub", "deg": 1, "values": [
+                    { "op": "number", "deg": 0, "value": "1" },
+                    { "op": "const", "deg": 1, "id": 0 }
+                ] }
+            ] },
+            { "op": "mul", "deg": 2, "values": [
+                { "op": "sub", "deg": 1, "values": [
+                    { "op": "cm", "deg": 1, "id": 1, "next": true },
+                    { "op": "add", "deg": 1, "values": [
+                        { "op": "cm", "deg": 1, "id": 0 },
+                        { "op": "cm", "deg": 1, "id": 1 }
+                    ] }
+                ] },
+                { "op": "sub", "deg": 1, "values": [
+                    { "op": "number", "deg": 0, "value": "1" },
+                    { "op": "const", "deg": 1, "id": 0 }
+                ] }
+            ] }
+        ],
+        "polIdentities": [
+            { "e": 0, "fileName": "fib.pil", "line": 4 },
+            { "e": 1, "fileName": "fib.pil", "line": 5 }
+        ],
+        "plookupIdentities": [],
+        "permutationIdentities": [],
+        "connectionIdentities": []
+    }"#;
+
+    // namespace Lookup(8);
+    //     pol constant T1, T2;
+    //     pol commit sel, a, b;
+    //     sel {a, b} in {T1, T2};
+    const LOOKUP_PIL: &str = r#"
+    {
+        "nCommitments": 3,
+        "nQ": 0,
+        "nIm": 0,
+        "nConstants": 2,
+        "publics": [],
+        "references": {
+            "Lookup.T1": { "type": "constP", "id": 0, "polDeg": 8, "isArray": false },
+            "Lookup.T2": { "type": "constP", "id": 1, "polDeg": 8, "isArray": false },
+            "Lookup.sel": { "type": "cmP", "id": 0, "polDeg": 8, "isArray": false },
+            "Lookup.a": { "type": "cmP", "id": 1, "polDeg": 8, "isArray": false },
+            "Lookup.b": { "type": "cmP", "id": 2, "polDeg": 8, "isArray": false }
+        },
+        "expressions": [
+            { "op": "cm", "deg": 1, "id": 1 },
+            { "op": "cm", "deg": 1, "id": 2 },
+            { "op": "const", "deg": 1, "id": 0 },
+            { "op": "const", "deg": 1, "id": 1 },
+            { "op": "cm", "deg": 1, "id": 0 }
+        ],
+        "polIdentities": [],
+        "plookupIdentities": [
+            { "f": [0, 1], "t": [2, 3], "selF": 4, "selT": null, "fileName": "lookup.pil", "line": 4 }
+        ],
+        "permutationIdentities": [],
+        "connectionIdentities": []
+    }"#;
+
+    fn lookup_pols(pil: &PIL) -> (PolsArray, PolsArray) {
+        let mut const_pols = PolsArray::new(pil, PolKind::Constant).unwrap();
+        let mut cm_pols = PolsArray::new(pil, PolKind::Commit).unwrap();
+        for i in 0..cm_pols.n {
+            const_pols.array[0][i] = FGL::from(i as u64);
+            const_pols.array[1][i] = FGL::from(2 * i as u64);
+            let a = (3 * i as u64) % 8;
+            cm_pols.array[0][i] = FGL::one();
+            cm_pols.array[1][i] = FGL::from(a);
+            cm_pols.array[2][i] = FGL::from(2 * a);
+        }
+        // not in the table, but not selected either
+        cm_pols.array[0][5] = FGL::zero();
+        cm_pols.array[2][5] = FGL::from(7);
+        (const_pols, cm_pols)
+    }
+
+    // namespace Permutation(8);
+    //     pol commit a, b, c, d;
+    //     {a, b} is {c, d};
+    const PERMUTATION_PIL: &str = r#"
+    {
+        "nCommitments": 4,
+        "nQ": 0,
+        "nIm": 0,
+        "nConstants": 0,
+        "publics": [],
+        "references": {
+            "Permutation.a": { "type": "cmP", "id": 0, "polDeg": 8, "isArray": false },
+            "Permutation.b": { "type": "cmP", "id": 1, "polDeg": 8, "isArray": false },
+            "Permutation.c": { "type": "cmP", "id": 2, "polDeg": 8, "isArray": false },
+            "Permutation.d": { "type": "cmP", "id": 3, "polDeg": 8, "isArray": false }
+        },
+        "expressions": [
+            { "op": "cm", "deg": 1, "id": 0 },
+            { "op": "cm", "deg": 1, "id": 1 },
+            { "op": "cm", "deg": 1, "id": 2 },
+            { "op": "cm", "deg": 1, "id": 3 }
+        ],
+        "polIdentities": [],
+        "plookupIdentities": [],
+        "permutationIdentities": [
+            { "f": [0, 1], "t": [2, 3], "selF": null, "selT": null, "fileName": "permutation.pil", "line": 3 }
+        ],
+        "connectionIdentities": []
+    }"#;
+
+    fn permutation_pols(pil: &PIL) -> (PolsArray, PolsArray) {
+        let const_pols = PolsArray::new(pil, PolKind::Constant).unwrap();
+        let mut cm_pols = PolsArray::new(pil, PolKind::Commit).unwrap();
+        let n = cm_pols.n;
+        for i in 0..n {
+            // every tuple shows up twice, so the check has to count multiplicities
+            let v = (i / 2) as u64;
+            cm_pols.array[0][i] = FGL::from(v);
+            cm_pols.array[1][i] = FGL::from(v + 10);
+            cm_pols.array[2][n - 1 - i] = FGL::from(v);
+            cm_pols.array[3][n - 1 - i] = FGL::from(v + 10);
+        }
+        (const_pols, cm_pols)
+    }
+
+    fn messages(report: &VerificationReport) -> Vec<String> {
+        report.iter().map(|f| f.to_string()).collect()
+    }
+
+    fn fib_pols(pil: &PIL) -> (PolsArray, PolsArray) {
+        let mut const_pols = PolsArray::new(pil, PolKind::Constant).unwrap();
+        let mut cm_pols = PolsArray::new(pil, PolKind::Commit).unwrap();
+        let n = cm_pols.n;
+        let (mut a, mut b) = (FGL::one(), FGL::one());
+        for i in 0..n {
+            const_pols.array[0][i] = if i == n - 1 { FGL::one() } else { FGL::zero() };
+            cm_pols.array[0][i] = a;
+            cm_pols.array[1][i] = b;
+            (a, b) = (b, a + b);
+        }
+        (const_pols, cm_pols)
+    }
+
+    // namespace Global(4);
+    //     pol constant BYTE;
+    // namespace Main(8);
+    //     pol commit a;
+    //     a in Global.BYTE;
+    const MIXED_PIL: &str = r#"
+    {
+        "nCommitments": 1,
+        "nQ": 0,
+        "nIm": 0,
+        "nConstants": 1,
+        "publics": [],
+        "references": {
+            "Global.BYTE": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.a": { "type": "cmP", "id": 0, "polDeg": 8, "isArray": false }
+        },
+        "expressions": [
+            { "op": "cm", "deg": 1, "id": 0 },
+            { "op": "const", "deg": 1, "id": 0 },
+            { "op": "sub", "deg": 1, "values": [
+                { "op": "cm", "deg": 1, "id": 0 },
+                { "op": "number", "deg": 0, "value": "3" }
+            ] }
+        ],
+        "polIdentities": [],
+        "plookupIdentities": [
+            { "f": [0], "t": [1], "selF": null, "selT": null, "fileName": "mixed.pil", "line": 5 }
+        ],
+        "permutationIdentities": [],
+        "connectionIdentities": []
+    }"#;
+
+    #[test]
+    fn test_verify_mixed_degrees() {
+        let mut pil = read_json::<PIL>(MIXED_PIL.to_string()).unwrap();
+        let mut const_pols = PolsArray::new(&pil, PolKind::Constant).unwrap();
+        let mut cm_pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        assert_eq!((const_pols.n, cm_pols.n), (4, 8));
+        for i in 0..4 {
+            const_pols.array[0][i] = FGL::from(i as u64);
+        }
+        for i in 0..8 {
+            cm_pols.array[0][i] = FGL::from((i % 4) as u64);
+        }
+
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        assert_eq!(
+            pil_verifier.expression_degrees().unwrap(),
+            vec![Some(8), Some(4), Some(8)]
+        );
+        assert_eq!(pil_verifier.verify_pil().unwrap().len(), 0);
+
+        // numbers take the degree of the polynomials they are used with
+        pil.polIdentities =
+            read_json(r#"[{ "e": 2, "fileName": "mixed.pil", "line": 6 }]"#.to_string()).unwrap();
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let result = pil_verifier.verify_pil().unwrap();
+        assert_eq!(
+            result.iter().map(|f| f.row).collect::<Vec<_>>(),
+            vec![0, 1, 2, 4, 5, 6]
+        );
+
+        // a[i] - BYTE[i] can not be evaluated
+        pil.expressions[2] = read_json(
+            r#"{ "op": "sub", "deg": 1, "values": [
+                { "op": "cm", "deg": 1, "id": 0 }, { "op": "const", "deg": 1, "id": 0 }
+            ] }"#
+                .to_string(),
+        )
+        .unwrap();
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        match pil_verifier.verify_pil() {
+            Err(PilError::MalformedPil(msg)) => {
+                assert_eq!(msg, "expression 2 mixes polynomials of degree 8 and 4")
+            }
+            r => panic!("expected a degree mismatch, got {:?}", r.map(|r| r.len())),
+        }
+
+        // neither can a plookup side mixing them
+        pil.expressions.truncate(2);
+        pil.polIdentities = vec![];
+        pil.plookupIdentities[0].f = Some(vec![0, 1]);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        match pil_verifier.verify_pil() {
+            Err(PilError::MalformedPil(msg)) => {
+                assert_eq!(
+                    msg,
+                    "mixed.pil:5: plookup 0 f mixes polynomials of degree 8 and 4"
+                )
+            }
+            r => panic!("expected a degree mismatch, got {:?}", r.map(|r| r.len())),
+        }
+    }
+
+    #[test]
+    fn test_verify_pol_identities() {
+        let pil = read_json::<PIL>(FIB_PIL.to_string()).unwrap();
+        let (const_pols, cm_pols) = fib_pols(&pil);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        assert_eq!(pil_verifier.verify_pil().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_verify_pol_identities_failure() {
+        let pil = read_json::<PIL>(FIB_PIL.to_string()).unwrap();
+        let (const_pols, mut cm_pols) = fib_pols(&pil);
+        cm_pols.array[1][3] = FGL::from(100);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let result = pil_verifier.verify_pil().unwrap();
+        // b[3] breaks a[4] = b[3] (row 3), b[3] = a[2] + b[2] (row 2) and
+        // b[4] = a[3] + b[3] (row 3)
+        assert_eq!(result.len(), 3);
+        let result = messages(&result);
+        assert!(result[0].starts_with("fib.pil:4: identity does not match w=3"));
+        assert!(result[1].starts_with("fib.pil:5: identity does not match w=2"));
+        assert!(result[2].starts_with("fib.pil:5: identity does not match w=3"));
+    }
+
+    #[test]
+    fn test_verify_mapped_pols() {
+        let pil = read_json::<PIL>(FIB_PIL.to_string()).unwrap();
+        let (const_pols, mut cm_pols) = fib_pols(&pil);
+        cm_pols.array[1][3] = FGL::from(100);
+        let dir = std::env::temp_dir();
+        let const_file = dir.join(format!("fib_mapped_{}.const", std::process::id()));
+        let cm_file = dir.join(format!("fib_mapped_{}.commit", std::process::id()));
+        const_pols.save(const_file.to_str().unwrap()).unwrap();
+        cm_pols.save(cm_file.to_str().unwrap()).unwrap();
+
+        let mapped_const =
+            PolsArray::new_mapped(&pil, PolKind::Constant, const_file.to_str().unwrap()).unwrap();
+        let mapped_cm =
+            PolsArray::new_mapped(&pil, PolKind::Commit, cm_file.to_str().unwrap()).unwrap();
+        assert!(mapped_cm.is_mapped());
+        assert!(mapped_cm.array.is_empty());
+        assert_eq!(mapped_cm.column(1), cm_pols.column(1));
+        assert_eq!(mapped_cm.value(1, 3), FGL::from(100));
+
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let expected = pil_verifier.verify_pil().unwrap();
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &mapped_cm, &mapped_const, &mut pols);
+        let result = pil_verifier.verify_pil().unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result, expected);
+
+        std::fs::remove_file(const_file).unwrap();
+        std::fs::remove_file(cm_file).unwrap();
+    }
+
+    #[test]
+    fn test_verify_plookup_identities() {
+        let pil = read_json::<PIL>(LOOKUP_PIL.to_string()).unwrap();
+        let (const_pols, mut cm_pols) = lookup_pols(&pil);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        assert_eq!(pil_verifier.verify_pil().unwrap().len(), 0);
+
+        cm_pols.array[0][5] = FGL::one();
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let result = pil_verifier.verify_pil().unwrap();
+        assert_eq!(
+            messages(&result),
+            vec!["lookup.pil:4: plookup not found w=5 values: 7,7"]
+        );
+    }
+
+    #[test]
+    fn test_verify_permutation_identities() {
+        let pil = read_json::<PIL>(PERMUTATION_PIL.to_string()).unwrap();
+        let (const_pols, mut cm_pols) = permutation_pols(&pil);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        assert_eq!(pil_verifier.verify_pil().unwrap().len(), 0);
+
+        // (1, 11) appears at rows 2 and 3; turning row 3 into a copy of (0, 10) keeps the
+        // set of tuples but breaks the multiset.
+        cm_pols.array[0][3] = FGL::from(0);
+        cm_pols.array[1][3] = FGL::from(10);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let result = pil_verifier.verify_pil().unwrap();
+        assert_eq!(
+            messages(&result),
+            vec![
+                "permutation.pil:3: permutation over-represented in f w=0 values: 0,10 f_count=3 t_count=2",
+                "permutation.pil:3: permutation under-represented in f w=4 values: 1,11 f_count=1 t_count=2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_verify_max_errors() {
+        let pil = read_json::<PIL>(FIB_PIL.to_string()).unwrap();
+        let (const_pols, mut cm_pols) = fib_pols(&pil);
+        cm_pols.array[1][3] = FGL::from(100);
+        let mut pols: Pols = Default::default();
+        let mut pil_verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        pil_verifier.set_max_errors(Some(2));
+        assert_eq!(pil_verifier.verify_pil().unwrap().len(), 2);
     }
 }
@@ -0,0 +1,434 @@
+#![allow(non_snake_case)]
+//! CSV and JSON views of a `PolsArray`, to look at a few columns of a trace or to write
+//! small witnesses by hand.
+//!
+//! Columns are named as in the witness file headers, `Main.a` or `Main.b[0]` for arrays.
+//! The CSV has a `row` column followed by one column per polynomial:
+//!
+//! ```text
+//! row,Main.a,Main.b[0]
+//! 0,1,18446744069414584320
+//! 1,2,
+//! ```
+//!
+//! where an empty cell is a row past the degree of its polynomial. The JSON holds the
+//! first row and the values of every column:
+//!
+//! ```text
+//! {"start": 0, "pols": [{"name": "Main.a", "values": ["1", "2"]}]}
+//! ```
+//!
+//! Values are written as canonical decimals, in strings for JSON. Imports also take
+//! negative and `0x` hexadecimal values, and JSON numbers.
+use fields::field_gl::Fr as FGL;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::ops::Range;
+
+use super::error::{PilError, Result};
+use super::polarray::{pol_name, PolsArray};
+use super::types::parse_pil_number;
+
+/// The values to export.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    /// Namespaces (`Main`), polynomials (`Main.b`, every element of an array) or array
+    /// elements (`Main.b[0]`). Empty selects every polynomial.
+    pub pols: Vec<String>,
+    /// The rows, `None` for all of them.
+    pub rows: Option<Range<usize>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonPols {
+    #[serde(default)]
+    start: usize,
+    pols: Vec<JsonColumn>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct JsonColumn {
+    name: String,
+    values: Vec<serde_json::Value>,
+}
+
+fn bad_line(line: usize, msg: String) -> PilError {
+    PilError::Parse(format!("line {}: {}", line, msg))
+}
+
+impl PolsArray {
+    /// The ids of the polynomials `names` select, as in `Selection::pols`, in id order.
+    pub fn select(&self, names: &[String]) -> Result<Vec<usize>> {
+        if names.is_empty() {
+            return Ok((0..self.nPols).collect());
+        }
+        let mut ids = BTreeSet::new();
+        for name in names {
+            let selected: Vec<usize> = if name.ends_with(']') {
+                self.defArray
+                    .iter()
+                    .filter(|p| pol_name(p) == *name)
+                    .map(|p| p.id)
+                    .collect()
+            } else if name.contains('.') {
+                self.defArray
+                    .iter()
+                    .filter(|p| p.name == *name)
+                    .map(|p| p.id)
+                    .collect()
+            } else {
+                self.def
+                    .get(name)
+                    .map(|ns| ns.values().flatten().copied().collect())
+                    .unwrap_or_default()
+            };
+            if selected.is_empty() {
+                return Err(PilError::UnknownPol(name.clone()));
+            }
+            ids.extend(selected);
+        }
+        Ok(ids.into_iter().collect())
+    }
+
+    /// The rows of `selection` that some of `ids` has.
+    fn rows(&self, ids: &[usize], selection: &Selection) -> Range<usize> {
+        let n = ids.iter().map(|id| self.pol_deg(*id)).max().unwrap_or(0);
+        match &selection.rows {
+            Some(rows) => rows.start.min(n)..rows.end.min(n),
+            None => 0..n,
+        }
+    }
+
+    /// The value of `id` at row `j`, `None` past its degree.
+    fn text_value(&self, id: usize, j: usize) -> Option<String> {
+        (j < self.pol_deg(id)).then(|| self.value(id, j).as_int().to_string())
+    }
+
+    pub fn export_csv(&self, w: &mut impl Write, selection: &Selection) -> Result<()> {
+        let ids = self.select(&selection.pols)?;
+        write!(w, "row")?;
+        for id in ids.iter() {
+            write!(w, ",{}", pol_name(&self.defArray[*id]))?;
+        }
+        writeln!(w)?;
+        for j in self.rows(&ids, selection) {
+            write!(w, "{}", j)?;
+            for id in ids.iter() {
+                write!(w, ",{}", self.text_value(*id, j).unwrap_or_default())?;
+            }
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    pub fn export_json(&self, w: &mut impl Write, selection: &Selection) -> Result<()> {
+        let ids = self.select(&selection.pols)?;
+        let rows = self.rows(&ids, selection);
+        let pols = ids
+            .iter()
+            .map(|id| JsonColumn {
+                name: pol_name(&self.defArray[*id]),
+                values: rows
+                    .clone()
+                    .map_while(|j| self.text_value(*id, j))
+                    .map(serde_json::Value::String)
+                    .collect(),
+            })
+            .collect();
+        let json = JsonPols {
+            start: rows.start,
+            pols,
+        };
+        serde_json::to_writer_pretty(&mut *w, &json)?;
+        writeln!(w)?;
+        Ok(())
+    }
+
+    /// Pol ids by column name, for the imports.
+    fn column_ids(&self) -> HashMap<String, usize> {
+        self.defArray.iter().map(|p| (pol_name(p), p.id)).collect()
+    }
+
+    /// Set the value of `id` at row `j` from its text, or say what is wrong with it.
+    fn import_value(
+        &mut self,
+        id: usize,
+        j: usize,
+        value: &str,
+    ) -> std::result::Result<(), String> {
+        let polDeg = self.pol_deg(id);
+        if j >= polDeg {
+            return Err(format!(
+                "row {} past the {} rows of {}",
+                j,
+                polDeg,
+                pol_name(&self.defArray[id])
+            ));
+        }
+        let value = parse_pil_number(value).map_err(|_| format!("invalid number {}", value))?;
+        self.set_value(id, j, FGL::from(value));
+        Ok(())
+    }
+
+    /// Set the values of a CSV written by `export_csv`, or by hand. Polynomials and rows it
+    /// does not have keep their values. As with `load`, fails with the values out of the
+    /// range of their element type, which are imported anyway, when `set_validate_types` is
+    /// on. Mapped arrays are read-only and fail.
+    pub fn import_csv(&mut self, r: impl Read) -> Result<()> {
+        self.check_importable()?;
+        let columns = self.column_ids();
+        let mut lines = BufReader::new(r).lines();
+        let header = lines
+            .next()
+            .transpose()?
+            .ok_or_else(|| bad_line(1, "no header".to_string()))?;
+        let mut names = header.trim().split(',');
+        if names.next().map(str::trim) != Some("row") {
+            return Err(bad_line(1, "the first column must be row".to_string()));
+        }
+        let ids = names
+            .map(|name| {
+                columns
+                    .get(name.trim())
+                    .copied()
+                    .ok_or_else(|| PilError::UnknownPol(name.trim().to_string()))
+            })
+            .collect::<Result<Vec<usize>>>()?;
+
+        for (l, line) in lines.enumerate() {
+            let (l, line) = (l + 2, line?);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let cells: Vec<&str> = line.trim().split(',').map(str::trim).collect();
+            if cells.len() != ids.len() + 1 {
+                return Err(bad_line(
+                    l,
+                    format!("{} cells, {} expected", cells.len(), ids.len() + 1),
+                ));
+            }
+            let j = cells[0]
+                .parse::<usize>()
+                .map_err(|e| bad_line(l, format!("bad row {}: {}", cells[0], e)))?;
+            for (id, cell) in ids.iter().zip(&cells[1..]) {
+                if !cell.is_empty() {
+                    self.import_value(*id, j, cell)
+                        .map_err(|msg| bad_line(l, msg))?;
+                }
+            }
+        }
+        self.check_loaded_types()
+    }
+
+    /// Set the values of a JSON written by `export_json`, or by hand, as `import_csv` does.
+    pub fn import_json(&mut self, r: impl Read) -> Result<()> {
+        self.check_importable()?;
+        let columns = self.column_ids();
+        let json: JsonPols =
+            serde_json::from_reader(r).map_err(|e| PilError::Parse(e.to_string()))?;
+        for column in json.pols {
+            let id = *columns
+                .get(&column.name)
+                .ok_or_else(|| PilError::UnknownPol(column.name.clone()))?;
+            for (j, value) in column.values.iter().enumerate() {
+                let value = match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Number(n) => n.to_string(),
+                    _ => format!("{}", value),
+                };
+                self.import_value(id, json.start + j, &value)
+                    .map_err(|msg| PilError::Parse(format!("{}: {}", column.name, msg)))?;
+            }
+        }
+        self.check_loaded_types()
+    }
+
+    fn check_importable(&self) -> Result<()> {
+        if self.is_mapped() {
+            return Err(PilError::Layout(
+                "cannot import into a mapped PolsArray".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::polarray::PolKind;
+    use crate::utils::types::{self, PIL};
+
+    const PIL_JSON: &str = r#"{
+        "nCommitments": 4, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+        "references": {
+            "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": true, "len": 2 },
+            "Small.c": { "type": "cmP", "id": 3, "polDeg": 2, "isArray": false }
+        },
+        "expressions": [], "polIdentities": [], "plookupIdentities": []
+    }"#;
+
+    fn pil() -> PIL {
+        types::read_json::<PIL>(PIL_JSON.to_string()).unwrap()
+    }
+
+    fn pols() -> PolsArray {
+        let mut pols = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+        for id in 0..pols.nPols {
+            for j in 0..pols.pol_deg(id) {
+                pols.set_value(id, j, FGL::from((10 * id + j) as u64));
+            }
+        }
+        pols
+    }
+
+    fn export(pols: &PolsArray, names: &[&str], rows: Option<Range<usize>>, json: bool) -> String {
+        let selection = Selection {
+            pols: names.iter().map(|s| s.to_string()).collect(),
+            rows,
+        };
+        let mut out = vec![];
+        if json {
+            pols.export_json(&mut out, &selection).unwrap();
+        } else {
+            pols.export_csv(&mut out, &selection).unwrap();
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_select() {
+        let pols = pols();
+        let select =
+            |names: &[&str]| pols.select(&names.iter().map(|s| s.to_string()).collect::<Vec<_>>());
+        assert_eq!(select(&[]).unwrap(), vec![0, 1, 2, 3]);
+        assert_eq!(select(&["Main"]).unwrap(), vec![0, 1, 2]);
+        assert_eq!(select(&["Small.c", "Main.b"]).unwrap(), vec![1, 2, 3]);
+        assert_eq!(select(&["Main.b[1]", "Main.a"]).unwrap(), vec![0, 2]);
+        assert!(matches!(select(&["Main.d"]), Err(PilError::UnknownPol(name)) if name == "Main.d"));
+        assert!(select(&["Main.b[2]"]).is_err());
+    }
+
+    #[test]
+    fn test_csv() {
+        let pols = pols();
+        assert_eq!(
+            export(&pols, &["Main.b[1]", "Small"], Some(1..9), false),
+            "row,Main.b[1],Small.c\n1,21,31\n2,22,\n3,23,\n"
+        );
+
+        let csv = export(&pols, &[], None, false);
+        let mut imported = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+        imported.import_csv(csv.as_bytes()).unwrap();
+        assert_eq!(imported.array, pols.array);
+
+        // by hand, some rows only
+        let mut imported = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+        imported
+            .import_csv("row, Small.c\n1, -1\n\n0, 0x10\n".as_bytes())
+            .unwrap();
+        assert_eq!(
+            imported.column(3).to_vec(),
+            vec![FGL::from(16u64), -FGL::ONE]
+        );
+        assert_eq!(imported.value(0, 0), FGL::ZERO);
+
+        let import_err = |csv: &str| {
+            let mut imported = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+            imported.import_csv(csv.as_bytes()).unwrap_err().to_string()
+        };
+        assert_eq!(
+            import_err("row,Small.c\n2,1\n"),
+            "parse error: line 2: row 2 past the 2 rows of Small.c"
+        );
+        assert_eq!(
+            import_err("row,Main.a\n0,1,2\n"),
+            "parse error: line 2: 3 cells, 2 expected"
+        );
+        assert_eq!(
+            import_err("row,Main.a\n0,x\n"),
+            "parse error: line 2: invalid number x"
+        );
+        assert_eq!(import_err("row,Main.b\n"), "unknown polynomial: Main.b");
+    }
+
+    #[test]
+    fn test_json() {
+        let pols = pols();
+        let json = export(&pols, &["Small", "Main.a"], Some(1..3), true);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({"start": 1, "pols": [
+                {"name": "Main.a", "values": ["1", "2"]},
+                {"name": "Small.c", "values": ["31"]}
+            ]})
+        );
+
+        let mut imported = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+        imported
+            .import_json(export(&pols, &[], None, true).as_bytes())
+            .unwrap();
+        assert_eq!(imported.array, pols.array);
+
+        let mut imported = PolsArray::new(&pil(), PolKind::Commit).unwrap();
+        imported
+            .import_json(r#"{"pols": [{"name": "Main.b[0]", "values": [5, "-2"]}]}"#.as_bytes())
+            .unwrap();
+        assert_eq!(imported.value(1, 1), -FGL::from(2u64));
+        assert_eq!(imported.value(1, 0), FGL::from(5u64));
+        let err = imported
+            .import_json(
+                r#"{"start": 3, "pols": [{"name": "Main.a", "values": [1, 2]}]}"#.as_bytes(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "parse error: Main.a: row 4 past the 4 rows of Main.a"
+        );
+        assert!(matches!(
+            imported.import_json(r#"{"pols": [{"name": "Main.a"}]}"#.as_bytes()),
+            Err(PilError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_import_checks() {
+        let typed = PIL_JSON.replace(
+            "\"polDeg\": 2, \"isArray\": false }",
+            "\"polDeg\": 2, \"isArray\": false, \"elementType\": \"u8\" }",
+        );
+        let typed = types::read_json::<PIL>(typed).unwrap();
+        let mut imported = PolsArray::new(&typed, PolKind::Commit).unwrap();
+        imported.set_validate_types(true);
+        assert!(matches!(
+            imported.import_csv("row,Small.c\n0,256\n".as_bytes()),
+            Err(PilError::ElementType(v)) if v.len() == 1
+        ));
+        assert_eq!(imported.value(3, 0), FGL::from(256u64));
+        assert!(matches!(
+            imported.import_json(r#"{"pols": [{"name": "Small.c", "values": [1, -1]}]}"#.as_bytes()),
+            Err(PilError::ElementType(v)) if v.len() == 1
+        ));
+        imported.set_validate_types(false);
+        imported
+            .import_csv("row,Small.c\n0,256\n".as_bytes())
+            .unwrap();
+
+        let file = std::env::temp_dir().join(format!("import_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols().save(fileName).unwrap();
+        let mut mapped = PolsArray::new_mapped(&pil(), PolKind::Commit, fileName).unwrap();
+        assert!(matches!(
+            mapped.import_csv("row,Main.a\n0,1\n".as_bytes()),
+            Err(PilError::Layout(_))
+        ));
+        assert!(matches!(
+            mapped.import_json(r#"{"pols": []}"#.as_bytes()),
+            Err(PilError::Layout(_))
+        ));
+        std::fs::remove_file(file).unwrap();
+    }
+}
@@ -1,14 +1,20 @@
 #![allow(non_snake_case)]
-use anyhow::Result;
-use fields::field_gl::Fr as FGL;
+use fields::field_gl::{Fr as FGL, MODULUS};
+use memmap2::Mmap;
 use profiler_macro::time_profiler;
 use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::{self, File};
-use std::io::{Read, Seek, Write};
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
 
+use super::element_type::{ElementType, TypeViolation};
+use super::error::{get_checked, PilError, Result};
+use super::packing;
 use super::traits::FieldExtension;
 use super::types::PIL;
+use super::witness_file::{self, hex, Column, Encoding, WitnessHeader};
 
 #[derive(Default, Debug)]
 pub struct PolsArray {
@@ -16,8 +22,68 @@ pub struct PolsArray {
     // nameSpace, namePol, defArray's index,
     pub def: HashMap<String, HashMap<String, Vec<usize>>>,
     pub defArray: Vec<Pol>,
+    // The storage of the `PerPol` layout, empty for the others.
     pub array: Vec<Vec<FGL>>,
+    // The largest polDeg.
     pub n: usize,
+    pub groups: Vec<DegreeGroup>,
+    // Group of every pol, and its position in the group.
+    slots: Vec<(usize, usize)>,
+    // The parsed elementType of every pol.
+    types: Vec<ElementType>,
+    // Whether `load` and `set_matrix` check the values against their element type.
+    validate_types: bool,
+    kind: PolKind,
+    // Fingerprint of the PIL, written to the header of versioned files.
+    pilHash: [u8; 32],
+    storage: Storage,
+}
+
+/// The polynomials sharing a degree. Files hold one group after the other, in order of
+/// their first pol id, each one row by row, so that a single group is the plain row-major
+/// layout.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct DegreeGroup {
+    pub polDeg: usize,
+    pub pols: Vec<usize>,
+    /// Position of the group in the file, in elements.
+    pub offset: usize,
+}
+
+/// How the values of a `PolsArray` are stored.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Layout {
+    /// One vector per polynomial, in `PolsArray::array`.
+    #[default]
+    PerPol,
+    /// A single buffer, row by row, as in the commit and constant files.
+    RowMajor,
+    /// A single buffer, polynomial by polynomial.
+    ColMajor,
+}
+
+#[derive(Debug, Default)]
+enum Storage {
+    #[default]
+    PerPol,
+    RowMajor(Vec<FGL>),
+    ColMajor(Vec<FGL>),
+    // The map, and where the values start in it.
+    Mapped(Mmap, usize),
+}
+
+/// The format of the files `save_as` writes. `load` and `new_mapped` read both.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum FileFormat {
+    /// The values alone, as pil-stark reads them.
+    #[default]
+    Raw,
+    /// A `WitnessHeader` describing the polynomials and the PIL, then the values.
+    Versioned,
+    /// `Versioned`, with every polynomial packed to the bits its values need.
+    Packed,
+    /// `Packed`, then zstd compressed at `level`. Needs the `zstd` feature.
+    PackedZstd { level: i32 },
 }
 
 #[derive(Debug, Default, Clone)]
@@ -29,15 +95,150 @@ pub struct Pol {
     pub elementType: Option<String>, // "field, s8, s16, s32, s64, u16, u8"
 }
 
-#[derive(Eq, PartialEq)]
+/// A polynomial of a `PolsArray`, resolved by `PolsArray::handle`. Reading and writing
+/// through it costs no lookup. Committed and constant ids overlap, so it panics when used
+/// with an array of the other kind.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PolHandle {
+    id: usize,
+    polDeg: usize,
+    kind: PolKind,
+}
+
+impl PolHandle {
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    /// Number of rows of the polynomial.
+    pub fn pol_deg(&self) -> usize {
+        self.polDeg
+    }
+
+    pub fn kind(&self) -> PolKind {
+        self.kind
+    }
+
+    #[inline(always)]
+    fn check(&self, pols: &PolsArray, j: usize) {
+        assert!(
+            pols.kind == self.kind,
+            "{} handle used with {} polynomials",
+            kind_name(self.kind),
+            kind_name(pols.kind)
+        );
+        assert!(j < self.polDeg, "row {} out of range", j);
+    }
+
+    /// The value at row `j` in `pols`, the array the handle was resolved in.
+    #[inline(always)]
+    pub fn get(&self, pols: &PolsArray, j: usize) -> FGL {
+        self.check(pols, j);
+        pols.value(self.id, j)
+    }
+
+    /// Set the value at row `j` in `pols`, the array the handle was resolved in. Panics as
+    /// `PolsArray::set_matrix` does.
+    #[inline(always)]
+    pub fn set(&self, pols: &mut PolsArray, j: usize, value: FGL) {
+        if let Err(e) = self.try_set(pols, j, value) {
+            panic!("{}", e);
+        }
+    }
+
+    /// `set`, returning the type violation instead of panicking. The value is not set then.
+    #[inline(always)]
+    pub fn try_set(&self, pols: &mut PolsArray, j: usize, value: FGL) -> Result<()> {
+        self.check(pols, j);
+        pols.set_checked(self.id, j, value)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
 pub enum PolKind {
+    #[default]
     Commit,
     Constant,
 }
 
 impl PolsArray {
     #[time_profiler("new_pols_array")]
-    pub fn new(pil: &PIL, kind: PolKind) -> Self {
+    pub fn new(pil: &PIL, kind: PolKind) -> Result<Self> {
+        Self::new_with_layout(pil, kind, Layout::PerPol)
+    }
+
+    /// A zeroed `PolsArray` stored in `layout`. The single buffer layouts need every
+    /// polynomial to have the same degree.
+    pub fn new_with_layout(pil: &PIL, kind: PolKind, layout: Layout) -> Result<Self> {
+        let mut pols = Self::describe(pil, kind)?;
+        if layout != Layout::PerPol {
+            if let Some(p) = pols.defArray.iter().find(|p| p.polDeg != pols.n) {
+                return Err(PilError::MalformedPil(format!(
+                    "{} has {} rows, a single buffer needs {}",
+                    p.name, p.polDeg, pols.n
+                )));
+            }
+        }
+        let size = pols.nPols * pols.n;
+        pols.storage = match layout {
+            Layout::PerPol => {
+                pols.array = pols
+                    .defArray
+                    .iter()
+                    .map(|p| vec![FGL::default(); p.polDeg])
+                    .collect();
+                Storage::PerPol
+            }
+            Layout::RowMajor => Storage::RowMajor(vec![FGL::default(); size]),
+            Layout::ColMajor => Storage::ColMajor(vec![FGL::default(); size]),
+        };
+        Ok(pols)
+    }
+
+    /// A read-only `PolsArray` backed by a memory map of `fileName`, in any `FileFormat`.
+    /// `column` and `value` decode from the map when called, so files larger than the
+    /// available memory can be used. As with `load`, the values are checked to be field
+    /// elements up front, and versioned files against their header and content hash; raw
+    /// files have no hash to check.
+    pub fn new_mapped(pil: &PIL, kind: PolKind, fileName: &str) -> Result<Self> {
+        let mut pols = Self::describe(pil, kind)?;
+        let mut f = File::open(fileName)?;
+        let header = WitnessHeader::read(&mut f)?;
+        let offset = match &header {
+            Some(header) => {
+                pols.check_header(header)?;
+                if header.encoding != Encoding::Plain {
+                    return Err(PilError::Layout(
+                        "packed witness files cannot be mapped".to_string(),
+                    ));
+                }
+                header.size()
+            }
+            None => 0,
+        };
+        let totalSize = offset + pols.len() * std::mem::size_of::<u64>();
+        let actual = f.metadata()?.len() as usize;
+        if actual != totalSize {
+            return Err(PilError::SizeMismatch {
+                expected: totalSize,
+                actual,
+            });
+        }
+        // An empty file cannot be mapped, and there is nothing to read anyway.
+        if totalSize > 0 {
+            // Safety: the map is read-only, the file must not be truncated while in use.
+            let m = unsafe { Mmap::map(&f)? };
+            check_elements(Self::mapped_u64(&m, offset), 0)?;
+            if let Some(header) = &header {
+                check_content_hash(Sha256::digest(&m[offset..]).into(), header)?;
+            }
+            pols.storage = Storage::Mapped(m, offset);
+        }
+        Ok(pols)
+    }
+
+    /// The definitions of the polynomials of `kind` in `pil`, without any storage.
+    pub(crate) fn describe(pil: &PIL, kind: PolKind) -> Result<Self> {
         log::trace!("Creating PolsArray for");
         let nPols = match kind {
             PolKind::Commit => pil.nCommitments,
@@ -46,19 +247,34 @@ impl PolsArray {
 
         let mut def: HashMap<String, HashMap<String, Vec<usize>>> = HashMap::new();
         let mut defArray: Vec<Pol> = vec![Pol::default(); nPols];
-        let mut array: Vec<Vec<FGL>> = (0..nPols).map(|_| vec![FGL::default(); nPols]).collect();
 
         for (refName, ref_) in pil.references.iter() {
             if (ref_.type_ == "cmP" && kind == PolKind::Commit)
                 || (ref_.type_ == "constP" && kind == PolKind::Constant)
             {
-                let name_vec: Vec<&str> = refName.split('.').collect();
-                let nameSpace = name_vec[0].to_string();
-                let namePols = name_vec[1].to_string();
+                let (nameSpace, namePols) = refName.split_once('.').ok_or_else(|| {
+                    PilError::MalformedPil(format!("Invalid reference name: {}", refName))
+                })?;
+                let nameSpace = nameSpace.to_string();
+                let namePols = namePols.to_string();
+                let len = if ref_.isArray {
+                    ref_.len.ok_or_else(|| {
+                        PilError::MalformedPil(format!("Array reference without len: {}", refName))
+                    })?
+                } else {
+                    1
+                };
+                if ref_.id + len > nPols {
+                    return Err(PilError::OutOfRange {
+                        kind: "pol",
+                        id: ref_.id + len - 1,
+                        len: nPols,
+                    });
+                }
 
                 if ref_.isArray {
                     let mut ns: HashMap<String, Vec<usize>> = HashMap::new();
-                    let mut arrayPols: Vec<usize> = vec![0usize; ref_.len.unwrap()];
+                    let mut arrayPols: Vec<usize> = vec![0usize; len];
                     if def.contains_key(&nameSpace) {
                         ns.clone_from(def.get(&nameSpace).unwrap());
                         if ns.contains_key(&namePols) {
@@ -66,7 +282,7 @@ impl PolsArray {
                         }
                     }
 
-                    for i in 0..ref_.len.unwrap() {
+                    for i in 0..len {
                         defArray[ref_.id + i] = Pol {
                             name: refName.clone(),
                             id: ref_.id + i,
@@ -75,7 +291,6 @@ impl PolsArray {
                             polDeg: ref_.polDeg,
                         };
                         arrayPols[i] = ref_.id + i;
-                        array[ref_.id + i] = vec![FGL::default(); ref_.polDeg];
                     }
                     ns.insert(namePols, arrayPols);
                     def.insert(nameSpace, ns);
@@ -88,33 +303,380 @@ impl PolsArray {
                         polDeg: ref_.polDeg,
                     };
                     let arrayPols: Vec<usize> = vec![ref_.id];
-                    let mut ns: HashMap<String, Vec<usize>> = HashMap::new();
-                    ns.insert(namePols, arrayPols);
-                    def.insert(nameSpace, ns);
-                    array[ref_.id] = vec![FGL::default(); ref_.polDeg];
+                    def.entry(nameSpace)
+                        .or_default()
+                        .insert(namePols, arrayPols);
                 }
             }
         }
 
-        for i in 0..nPols {
-            if defArray[i].name.is_empty() {
-                panic!("Invalid pils sequence");
+        for (i, pol) in defArray.iter().enumerate() {
+            if pol.name.is_empty() {
+                return Err(PilError::MalformedPil(format!(
+                    "Invalid pils sequence, pol {} is not defined",
+                    i
+                )));
             }
         }
 
-        PolsArray {
+        let mut groups: Vec<DegreeGroup> = vec![];
+        let mut slots = Vec::with_capacity(nPols);
+        for pol in defArray.iter() {
+            let g = match groups.iter().position(|g| g.polDeg == pol.polDeg) {
+                Some(g) => g,
+                None => {
+                    groups.push(DegreeGroup {
+                        polDeg: pol.polDeg,
+                        ..Default::default()
+                    });
+                    groups.len() - 1
+                }
+            };
+            slots.push((g, groups[g].pols.len()));
+            groups[g].pols.push(pol.id);
+        }
+        let mut offset = 0;
+        for g in groups.iter_mut() {
+            g.offset = offset;
+            offset += g.polDeg * g.pols.len();
+        }
+        let types = defArray
+            .iter()
+            .map(|p| {
+                p.elementType
+                    .as_deref()
+                    .map_or(Ok(ElementType::Field), str::parse)
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(PolsArray {
             nPols: defArray.len(),
-            n: defArray[0].polDeg,
+            n: defArray.iter().map(|p| p.polDeg).max().unwrap_or(0),
             defArray,
-            array,
+            array: vec![],
             def,
+            groups,
+            slots,
+            types,
+            validate_types: false,
+            kind,
+            pilHash: witness_file::pil_hash(pil),
+            storage: Storage::PerPol,
+        })
+    }
+
+    /// The header of a versioned file holding these polynomials.
+    pub fn header(&self, encoding: Encoding, contentHash: [u8; 32]) -> WitnessHeader {
+        WitnessHeader {
+            version: witness_file::VERSION,
+            kind: self.kind,
+            encoding,
+            n: self.n,
+            nPols: self.nPols,
+            pilHash: self.pilHash,
+            contentHash,
+            columns: self.columns(),
         }
     }
 
+    pub fn kind(&self) -> PolKind {
+        self.kind
+    }
+
+    /// The name and degree of every polynomial, as in the headers of versioned files.
+    pub fn columns(&self) -> Vec<Column> {
+        self.defArray
+            .iter()
+            .map(|p| Column {
+                name: pol_name(p),
+                polDeg: p.polDeg,
+            })
+            .collect()
+    }
+
+    /// Fail, telling how, unless these are the `kind` polynomials `columns` lists by name
+    /// and degree. Code generated from a PIL checks with this that it is used with the same
+    /// one.
+    pub fn check_columns(&self, kind: PolKind, columns: &[(&str, usize)]) -> Result<()> {
+        if kind != self.kind {
+            return Err(PilError::WrongPil(format!(
+                "{} polynomials expected, these are {}",
+                kind_name(kind),
+                kind_name(self.kind)
+            )));
+        }
+        if columns.len() != self.nPols {
+            return Err(PilError::WrongPil(format!(
+                "{} polynomials expected, the PIL defines {}",
+                columns.len(),
+                self.nPols
+            )));
+        }
+        let pairs = columns.iter().zip(self.columns());
+        if let Some((id, ((name, polDeg), c))) = pairs
+            .enumerate()
+            .find(|(_, ((name, polDeg), c))| *name != c.name || *polDeg != c.polDeg)
+        {
+            return Err(PilError::WrongPil(format!(
+                "pol {} is {} of {} rows, {} of {} rows in the PIL",
+                id, name, polDeg, c.name, c.polDeg
+            )));
+        }
+        Ok(())
+    }
+
+    /// Fail, telling how, when `header` does not describe these polynomials.
+    fn check_header(&self, header: &WitnessHeader) -> Result<()> {
+        if header.kind != self.kind {
+            return Err(PilError::WrongPil(format!(
+                "the file holds {} polynomials, {} expected",
+                kind_name(header.kind),
+                kind_name(self.kind)
+            )));
+        }
+        let expected = self.header(header.encoding, header.contentHash);
+        if header.nPols != expected.nPols {
+            return Err(PilError::WrongPil(format!(
+                "the file holds {} polynomials, the PIL defines {}",
+                header.nPols, expected.nPols
+            )));
+        }
+        let columns = header.columns.iter().zip(expected.columns.iter());
+        if let Some((id, (c, e))) = columns.enumerate().find(|(_, (c, e))| c != e) {
+            return Err(PilError::WrongPil(format!(
+                "pol {} is {} of {} rows in the file, {} of {} rows in the PIL",
+                id, c.name, c.polDeg, e.name, e.polDeg
+            )));
+        }
+        if header.pilHash != self.pilHash {
+            return Err(PilError::WrongPil(format!(
+                "same polynomials, but the PIL fingerprint is {} in the file and {} here",
+                hex(&header.pilHash),
+                hex(&self.pilHash)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Have `load`, the CSV and JSON imports and `set_matrix` check every value against the
+    /// `elementType` of its polynomial. Off by default.
+    pub fn set_validate_types(&mut self, validate: bool) {
+        self.validate_types = validate;
+    }
+
+    /// `check_types` after loading values, when `set_validate_types` is on.
+    pub(crate) fn check_loaded_types(&self) -> Result<()> {
+        if self.validate_types {
+            self.check_types()?;
+        }
+        Ok(())
+    }
+
+    pub fn element_type(&self, id: usize) -> ElementType {
+        self.types[id]
+    }
+
+    fn violation(&self, id: usize, j: usize, value: FGL) -> Option<TypeViolation> {
+        let elementType = self.types[id];
+        if elementType.contains(value) {
+            return None;
+        }
+        Some(TypeViolation {
+            pol: id,
+            name: pol_name(&self.defArray[id]),
+            row: j,
+            value: value.as_int(),
+            elementType,
+        })
+    }
+
+    /// Every value out of the range of the `elementType` of its polynomial, by pol and row.
+    pub fn type_violations(&self) -> Vec<TypeViolation> {
+        (0..self.nPols)
+            .into_par_iter()
+            .filter(|id| self.types[*id] != ElementType::Field)
+            .flat_map_iter(|id| {
+                let column = self.column(id);
+                column
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(j, v)| self.violation(id, j, *v))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Fail with the values out of the range of their `elementType`, if any.
+    pub fn check_types(&self) -> Result<()> {
+        let violations = self.type_violations();
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(PilError::ElementType(violations))
+        }
+    }
+
+    /// Number of values, over all polynomials.
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|g| g.polDeg * g.pols.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of rows of polynomial `id`.
+    pub fn pol_deg(&self, id: usize) -> usize {
+        self.defArray[id].polDeg
+    }
+
+    /// Position of the value of polynomial `id` at row `j` in the file layout.
+    #[inline(always)]
+    fn file_index(&self, id: usize, j: usize) -> usize {
+        let (g, k) = self.slots[id];
+        let group = &self.groups[g];
+        assert!(j < group.polDeg, "row {} out of range", j);
+        group.offset + j * group.pols.len() + k
+    }
+
+    /// The (pol id, row) of every value, in the order of the file layout.
+    fn file_order(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.groups
+            .iter()
+            .flat_map(|g| (0..g.polDeg).flat_map(move |j| g.pols.iter().map(move |id| (*id, j))))
+    }
+
+    pub fn is_mapped(&self) -> bool {
+        matches!(self.storage, Storage::Mapped(..))
+    }
+
+    /// The layout of an in-memory array, `None` when it is mapped.
+    pub fn layout(&self) -> Option<Layout> {
+        match self.storage {
+            Storage::PerPol => Some(Layout::PerPol),
+            Storage::RowMajor(_) => Some(Layout::RowMajor),
+            Storage::ColMajor(_) => Some(Layout::ColMajor),
+            Storage::Mapped(..) => None,
+        }
+    }
+
+    /// The values of polynomial `id`, borrowed when the layout stores them contiguously.
+    pub fn column(&self, id: usize) -> Cow<'_, [FGL]> {
+        assert!(id < self.nPols, "pol id {} out of range", id);
+        match &self.storage {
+            Storage::PerPol => Cow::Borrowed(&self.array[id]),
+            Storage::ColMajor(buff) => Cow::Borrowed(&buff[id * self.n..(id + 1) * self.n]),
+            Storage::RowMajor(buff) => {
+                Cow::Owned(buff[id..].iter().step_by(self.nPols).copied().collect())
+            }
+            Storage::Mapped(m, offset) => {
+                let (g, k) = self.slots[id];
+                let group = &self.groups[g];
+                let buff =
+                    &Self::mapped_u64(m, *offset)[group.offset..group.offset + self.len_of(g)];
+                Cow::Owned(
+                    buff[k..]
+                        .iter()
+                        .step_by(group.pols.len())
+                        .map(|v| FGL::from(*v))
+                        .collect(),
+                )
+            }
+        }
+    }
+
+    /// The values of every polynomial at row `j`, borrowed when the layout stores them
+    /// contiguously. Every polynomial must have row `j`.
+    pub fn row(&self, j: usize) -> Cow<'_, [FGL]> {
+        assert!(j < self.n, "row {} out of range", j);
+        match &self.storage {
+            Storage::RowMajor(buff) => Cow::Borrowed(&buff[j * self.nPols..(j + 1) * self.nPols]),
+            _ => Cow::Owned((0..self.nPols).map(|i| self.value(i, j)).collect()),
+        }
+    }
+
+    /// Mutable view of polynomial `id`, if the layout stores it contiguously.
+    pub fn column_mut(&mut self, id: usize) -> Option<&mut [FGL]> {
+        let n = self.n;
+        match &mut self.storage {
+            Storage::PerPol => Some(&mut self.array[id]),
+            Storage::ColMajor(buff) => Some(&mut buff[id * n..(id + 1) * n]),
+            _ => None,
+        }
+    }
+
+    /// Mutable views of every polynomial at once, if the layout stores them contiguously.
+    pub fn columns_mut(&mut self) -> Option<Vec<&mut [FGL]>> {
+        let n = self.n;
+        match &mut self.storage {
+            Storage::PerPol => Some(self.array.iter_mut().map(|c| c.as_mut_slice()).collect()),
+            Storage::ColMajor(buff) => Some(buff.chunks_mut(n.max(1)).collect()),
+            _ => None,
+        }
+    }
+
+    /// Mutable view of row `j`, if the layout stores it contiguously.
+    pub fn row_mut(&mut self, j: usize) -> Option<&mut [FGL]> {
+        let nPols = self.nPols;
+        match &mut self.storage {
+            Storage::RowMajor(buff) => Some(&mut buff[j * nPols..(j + 1) * nPols]),
+            _ => None,
+        }
+    }
+
+    /// The value of polynomial `id` at row `j`.
+    #[inline(always)]
+    pub fn value(&self, id: usize, j: usize) -> FGL {
+        match &self.storage {
+            Storage::PerPol => self.array[id][j],
+            Storage::RowMajor(buff) => {
+                assert!(id < self.nPols, "pol id {} out of range", id);
+                buff[j * self.nPols + id]
+            }
+            Storage::ColMajor(buff) => {
+                assert!(j < self.n, "row {} out of range", j);
+                buff[id * self.n + j]
+            }
+            Storage::Mapped(m, offset) => {
+                FGL::from(Self::mapped_u64(m, *offset)[self.file_index(id, j)])
+            }
+        }
+    }
+
+    /// Set the value of polynomial `id` at row `j`. Mapped arrays are read-only and panic.
+    #[inline(always)]
+    pub fn set_value(&mut self, id: usize, j: usize, value: FGL) {
+        match &mut self.storage {
+            Storage::PerPol => self.array[id][j] = value,
+            Storage::RowMajor(buff) => {
+                assert!(id < self.nPols, "pol id {} out of range", id);
+                buff[j * self.nPols + id] = value;
+            }
+            Storage::ColMajor(buff) => {
+                assert!(j < self.n, "row {} out of range", j);
+                buff[id * self.n + j] = value;
+            }
+            Storage::Mapped(..) => panic!("cannot write to a mapped PolsArray"),
+        }
+    }
+
+    fn len_of(&self, g: usize) -> usize {
+        self.groups[g].polDeg * self.groups[g].pols.len()
+    }
+
+    fn mapped_u64(m: &Mmap, offset: usize) -> &[u64] {
+        // Maps are page aligned, headers are padded to a multiple of 8, and the size was
+        // checked.
+        let values = &m[offset..];
+        unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u64, values.len() / 8) }
+    }
+
+    /// The value of ns.np[i] at row j. The polynomial is looked up on every call, loops
+    /// should resolve it once with `handle`.
     #[inline(always)]
     pub fn get(&self, pil: &PIL, ns: &String, np: &String, i: usize, j: usize) -> FGL {
         let ref_id = self.get_pol_id(pil, ns, np, i);
-        self.array[ref_id][j]
+        self.value(ref_id, j)
     }
 
     /// Set the ns.np[i][j] = value, where ns is the namespace, np is the state variable, i is
@@ -122,7 +684,10 @@ impl PolsArray {
     ///
     /// e.g. For JS statement, constPols.Compressor.C[7][pr.row] = c[5], i is 7 and j is pr.row.
     ///
-    /// Before calling this function, you must ensure that this polsarray has been initialized
+    /// Before calling this function, you must ensure that this polsarray has been initialized.
+    /// Mapped arrays are read-only and panic here, and so do values out of the range of
+    /// their element type when `set_validate_types` is on. Loops should resolve the
+    /// polynomial once with `handle` instead.
     #[inline(always)]
     pub fn set_matrix(
         &mut self,
@@ -133,28 +698,102 @@ impl PolsArray {
         j: usize,
         value: FGL,
     ) {
+        if let Err(e) = self.try_set_matrix(pil, ns, np, i, j, value) {
+            panic!("{}", e);
+        }
+    }
+
+    /// `set_matrix`, returning the type violation instead of panicking. The value is not set
+    /// then.
+    #[inline(always)]
+    pub fn try_set_matrix(
+        &mut self,
+        pil: &PIL,
+        ns: &String,
+        np: &String,
+        i: usize,
+        j: usize,
+        value: FGL,
+    ) -> Result<()> {
         let ref_id = self.get_pol_id(pil, ns, np, i);
-        self.array[ref_id][j] = value;
+        self.set_checked(ref_id, j, value)
     }
+
+    /// `set_value`, checking the type of `value` when `set_validate_types` is on.
+    #[inline(always)]
+    fn set_checked(&mut self, id: usize, j: usize, value: FGL) -> Result<()> {
+        if self.validate_types {
+            if let Some(v) = self.violation(id, j, value) {
+                return Err(PilError::ElementType(vec![v]));
+            }
+        }
+        self.set_value(id, j, value);
+        Ok(())
+    }
+
     #[inline(always)]
     pub fn get_pol_id(&self, pil: &PIL, ns: &String, np: &String, k: usize) -> usize {
         let pol = &pil.references[&format!("{}.{}", ns, np)];
         pol.id + k
     }
 
+    /// Resolve element `idx` of the polynomial `name`, `Main.A`, once for `PolHandle::get`
+    /// and `PolHandle::set`. `idx` is 0 for polynomials that are not arrays.
+    pub fn handle(&self, name: &str, idx: usize) -> Result<PolHandle> {
+        let ids = name
+            .split_once('.')
+            .and_then(|(ns, np)| self.def.get(ns)?.get(np))
+            .ok_or_else(|| PilError::UnknownPol(name.to_string()))?;
+        let id = *get_checked(ids, idx, "array index")?;
+        Ok(PolHandle {
+            id,
+            polDeg: self.defArray[id].polDeg,
+            kind: self.kind,
+        })
+    }
+
+    /// Read the values `save_as` wrote to `fileName`, in either format. Versioned files must
+    /// have been written for the same PIL, and their content must match its hash. With
+    /// `set_validate_types` on, fails with the values out of the range of their element
+    /// type, which are loaded anyway. Mapped arrays are read-only and fail.
     #[time_profiler("load_cm_pols_array")]
     pub fn load(&mut self, fileName: &str) -> Result<()> {
+        if self.is_mapped() {
+            return Err(PilError::Layout(
+                "cannot load into a mapped PolsArray".to_string(),
+            ));
+        }
         let mut f = File::open(fileName)?;
+        let header = WitnessHeader::read(&mut f)?;
+        let offset = match &header {
+            Some(header) => {
+                self.check_header(header)?;
+                if header.encoding != Encoding::Plain {
+                    return self.load_packed(f, header);
+                }
+                header.size()
+            }
+            None => {
+                f.rewind()?;
+                0
+            }
+        };
         let maxBufferSize = 1024 * 1024 * 256; // 256Mb
-        let totalSize = self.nPols * self.n * std::mem::size_of::<FGL>();
+        let totalSize = self.len() * std::mem::size_of::<FGL>();
         let metadata = fs::metadata(fileName)?;
 
-        assert_eq!(metadata.len(), totalSize as u64, "file size not equal to expected size");
+        if metadata.len() != (offset + totalSize) as u64 {
+            return Err(PilError::SizeMismatch {
+                expected: offset + totalSize,
+                actual: metadata.len() as usize,
+            });
+        }
 
         let mut buff8: Vec<u8> = vec![0u8; std::cmp::min(totalSize, maxBufferSize)];
 
-        let mut i = 0;
-        let mut j = 0;
+        let order: Vec<(usize, usize)> = self.file_order().collect();
+        let mut hasher = Sha256::new();
+        let mut p = 0;
         let mut position = 0;
         while position < totalSize {
             log::trace!(
@@ -184,77 +823,234 @@ impl PolsArray {
                 )
             };
             n = rs / 8;
+            check_elements(&buff[..n], p)?;
+            if header.is_some() {
+                hasher.update(&buff8[..n * 8]);
+            }
 
-            for l in 0..n {
-                self.array[i][j] = FGL::from(buff[l]);
-                i += 1;
-                if i == self.nPols {
-                    i = 0;
-                    j += 1;
-                }
+            for v in &buff[..n] {
+                let (i, j) = order[p];
+                self.set_value(i, j, FGL::from(*v));
+                p += 1;
             }
             position += n * 8;
         }
 
-        Ok(())
+        if let Some(header) = header {
+            check_content_hash(hasher.finalize().into(), &header)?;
+        }
+        self.check_loaded_types()
     }
 
+    /// The values of a packed file, after its header.
+    fn load_packed(&mut self, f: File, header: &WitnessHeader) -> Result<()> {
+        let mut reader: Box<dyn Read> = match header.encoding {
+            Encoding::PackedZstd => zstd_reader(BufReader::new(f))?,
+            _ => Box::new(BufReader::new(f)),
+        };
+        for id in 0..self.nPols {
+            let column = packing::read_column(&mut reader, self.defArray[id].polDeg)?;
+            for (j, v) in column.into_iter().enumerate() {
+                self.set_value(id, j, v);
+            }
+        }
+        if reader.read(&mut [0u8])? != 0 {
+            return Err(PilError::CorruptFile(
+                "data after the last polynomial".to_string(),
+            ));
+        }
+
+        check_content_hash(self.content_hash()?, header)?;
+        self.check_loaded_types()
+    }
+
+    /// Save in the raw format.
     pub fn save(&self, fileName: &str) -> Result<()> {
-        let mut writer = File::create(fileName)?;
+        self.save_as(fileName, FileFormat::Raw)
+    }
+
+    pub fn save_as(&self, fileName: &str, format: FileFormat) -> Result<()> {
+        let mut writer = BufWriter::new(File::create(fileName)?);
+        let encoding = match format {
+            FileFormat::Raw => None,
+            FileFormat::Versioned => Some(Encoding::Plain),
+            FileFormat::Packed => Some(Encoding::Packed),
+            FileFormat::PackedZstd { .. } => Some(Encoding::PackedZstd),
+        };
+        if let Some(encoding) = encoding {
+            self.header(encoding, self.content_hash()?)
+                .write(&mut writer)?;
+        }
+        match format {
+            FileFormat::Raw | FileFormat::Versioned => {
+                self.for_each_chunk(|chunk| Ok(writer.write_all(chunk)?))?
+            }
+            FileFormat::Packed => self.write_packed(&mut writer)?,
+            FileFormat::PackedZstd { level } => {
+                zstd_write(&mut writer, level, |w| self.write_packed(w))?
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn write_packed(&self, w: &mut dyn Write) -> Result<()> {
+        for id in 0..self.nPols {
+            packing::write_column(w, &self.column(id), self.types[id])?;
+        }
+        Ok(())
+    }
+
+    /// SHA-256 of the values in the raw format.
+    fn content_hash(&self) -> Result<[u8; 32]> {
+        let mut hasher = Sha256::new();
+        self.for_each_chunk(|chunk| {
+            hasher.update(chunk);
+            Ok(())
+        })?;
+        Ok(hasher.finalize().into())
+    }
+
+    /// Pass the bytes of the values, in the order of the files, to `f` in chunks.
+    fn for_each_chunk(&self, mut f: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
         let maxBufferSize = 1024 * 1024 * 32;
-        let totalSize = self.nPols * self.n;
+        let totalSize = self.len();
         let mut buff: Vec<u64> = vec![0u64; std::cmp::min(totalSize, maxBufferSize)];
 
         let mut p = 0usize;
-        for i in 0..self.n {
-            for j in 0..self.nPols {
-                buff[p] = self.array[j][i].as_int() % 0xFFFFFFFF00000001; //u128
-                p += 1;
-                if p == buff.capacity() {
-                    // copy to [u8]
-                    let buff8: &[u8] = unsafe {
-                        std::slice::from_raw_parts(
-                            buff.as_ptr() as *const u8,
-                            buff.len() * std::mem::size_of::<u64>(),
-                        )
-                    };
-                    writer.write_all(buff8)?;
-                    p = 0;
-                }
+        for (j, i) in self.file_order() {
+            buff[p] = self.value(j, i).as_int() % 0xFFFFFFFF00000001; //u128
+            p += 1;
+            if p == buff.capacity() {
+                // copy to [u8]
+                let buff8: &[u8] = unsafe {
+                    std::slice::from_raw_parts(
+                        buff.as_ptr() as *const u8,
+                        buff.len() * std::mem::size_of::<u64>(),
+                    )
+                };
+                f(buff8)?;
+                p = 0;
             }
         }
         if p > 0 {
             let buff8: &[u8] = unsafe {
                 std::slice::from_raw_parts(
                     buff.as_ptr() as *const u8,
-                    buff.len() * std::mem::size_of::<u64>(),
+                    p * std::mem::size_of::<u64>(),
                 )
             };
-            writer.write_all(buff8)?;
+            f(buff8)?;
         }
         Ok(())
     }
 
-    pub fn write_buff<F: FieldExtension>(&self) -> Vec<F> {
+    /// The values row by row, as pil-stark expects them. Fails unless every polynomial has
+    /// `n` rows.
+    pub fn write_buff<F: FieldExtension>(&self) -> Result<Vec<F>> {
+        if let Some(g) = self.groups.iter().find(|g| g.polDeg != self.n) {
+            return Err(PilError::SizeMismatch {
+                expected: self.n,
+                actual: g.polDeg,
+            });
+        }
         let mut buff: Vec<F> = vec![F::ZERO; self.n * self.nPols];
         buff.par_chunks_mut(self.nPols)
             .enumerate()
             .for_each(|(i, chunk)| {
-                for j in 0..self.nPols {
-                    chunk[j] = F::from(self.array[j][i]);
+                for (j, v) in chunk.iter_mut().enumerate() {
+                    *v = F::from(self.value(j, i));
                 }
             });
-        buff
+        Ok(buff)
+    }
+}
+
+/// Fails on the first of `values`, starting at value `position` of the file, that is not
+/// a field element, which only a damaged file can hold.
+fn check_elements(values: &[u64], position: usize) -> Result<()> {
+    match values.iter().position(|v| *v >= MODULUS.0[0]) {
+        Some(i) => Err(PilError::CorruptFile(format!(
+            "{} is not a field element, at value {}",
+            values[i],
+            position + i
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn check_content_hash(contentHash: [u8; 32], header: &WitnessHeader) -> Result<()> {
+    if contentHash != header.contentHash {
+        return Err(PilError::CorruptFile(format!(
+            "content hash is {}, the header says {}",
+            hex(&contentHash),
+            hex(&header.contentHash)
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_reader<'a>(r: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    Ok(Box::new(zstd::Decoder::new(r)?))
+}
+
+#[cfg(feature = "zstd")]
+fn zstd_write(
+    w: &mut dyn Write,
+    level: i32,
+    body: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    let mut encoder = zstd::Encoder::new(w, level)?;
+    body(&mut encoder)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn no_zstd() -> PilError {
+    PilError::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "zstd compressed witness files need the zstd feature",
+    ))
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_reader<'a>(_r: impl Read + 'a) -> Result<Box<dyn Read + 'a>> {
+    Err(no_zstd())
+}
+
+#[cfg(not(feature = "zstd"))]
+fn zstd_write(
+    _w: &mut dyn Write,
+    _level: i32,
+    _body: impl FnOnce(&mut dyn Write) -> Result<()>,
+) -> Result<()> {
+    Err(no_zstd())
+}
+
+fn kind_name(kind: PolKind) -> &'static str {
+    match kind {
+        PolKind::Commit => "committed",
+        PolKind::Constant => "constant",
+    }
+}
+
+/// Reference name of `pol`, with its index for arrays.
+pub(crate) fn pol_name(pol: &Pol) -> String {
+    match pol.idx {
+        Some(idx) => format!("{}[{}]", pol.name, idx),
+        None => pol.name.clone(),
     }
 }
 
 #[cfg(test)]
 pub mod tests {
     use super::*;
+    use crate::utils::f3g::F3G;
     use crate::utils::types;
     use crate::utils::pil_verify::{PilVerify, Pols};
     use env_logger::{Builder, Env};
-    use log::LevelFilter;
 
     #[test]
     fn test_load_polsarray() {
@@ -286,9 +1082,9 @@ pub mod tests {
         .init();
         
         let pil = types::load_json::<PIL>("test_data/tmp/connectCheck.pil.json").unwrap();
-        let mut const_pol = PolsArray::new(&pil, PolKind::Constant);
+        let mut const_pol = PolsArray::new(&pil, PolKind::Constant).unwrap();
         const_pol.load("test_data/tmp/connectCheck.const").unwrap();
-        let mut commit_pol = PolsArray::new(&pil, PolKind::Commit);
+        let mut commit_pol = PolsArray::new(&pil, PolKind::Commit).unwrap();
         commit_pol.load("test_data/tmp/connectCheck.commit").unwrap();
 
         // let pil = types::load_json::<PIL>("test_data/zkevm/main.pil.json").unwrap();
@@ -300,12 +1096,439 @@ pub mod tests {
         let mut pols: Pols = Default::default();
 
         let mut pil_verifier = PilVerify::new(&pil, &commit_pol, &const_pol, &mut pols);
-        let result = pil_verifier.verify_pil();
-        if result.len() > 0 {
+        let result = pil_verifier.verify_pil().unwrap();
+        if !result.is_empty() {
             for r in &result {
                 println!("{}", r);
             }
         }
         assert_eq!(result.len(), 0)
     }
+
+    const TWO_POLS_PIL: &str = r#"{
+        "nCommitments": 2, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+        "references": {
+            "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": false }
+        },
+        "expressions": [], "polIdentities": [], "plookupIdentities": []
+    }"#;
+
+    #[test]
+    fn test_layouts() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.to_string()).unwrap();
+        let (ns, a, b) = ("Main".to_string(), "a".to_string(), "b".to_string());
+        let dir = std::env::temp_dir();
+        let mut files = vec![];
+        for layout in [Layout::PerPol, Layout::RowMajor, Layout::ColMajor] {
+            let mut pols = PolsArray::new_with_layout(&pil, PolKind::Commit, layout).unwrap();
+            assert_eq!(pols.layout(), Some(layout));
+            for j in 0..pols.n {
+                pols.set_matrix(&pil, &ns, &a, 0, j, FGL::from(j as u64));
+                pols.set_matrix(&pil, &ns, &b, 0, j, FGL::from(10 + j as u64));
+            }
+            assert_eq!(pols.get(&pil, &ns, &b, 0, 2), FGL::from(12u64));
+            assert_eq!(pols.column(1)[3], FGL::from(13u64));
+            assert_eq!(&*pols.row(3), &[FGL::from(3u64), FGL::from(13u64)]);
+            match layout {
+                Layout::RowMajor => {
+                    assert!(matches!(pols.row(0), Cow::Borrowed(_)));
+                    assert!(pols.column_mut(0).is_none());
+                    pols.row_mut(1).unwrap()[1] = FGL::from(11u64);
+                }
+                _ => {
+                    assert!(matches!(pols.column(0), Cow::Borrowed(_)));
+                    assert!(pols.row_mut(0).is_none());
+                    pols.column_mut(1).unwrap()[1] = FGL::from(11u64);
+                }
+            }
+
+            let file = dir.join(format!("layout_{:?}_{}.commit", layout, std::process::id()));
+            pols.save(file.to_str().unwrap()).unwrap();
+            let mut loaded = PolsArray::new_with_layout(&pil, PolKind::Commit, layout).unwrap();
+            loaded.load(file.to_str().unwrap()).unwrap();
+            assert_eq!(loaded.column(1), pols.column(1));
+            files.push(file);
+        }
+
+        // every layout saves the same file
+        let contents: Vec<Vec<u8>> = files.iter().map(|f| fs::read(f).unwrap()).collect();
+        assert_eq!(contents[0], contents[1]);
+        assert_eq!(contents[0], contents[2]);
+        for f in files {
+            fs::remove_file(f).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_mixed_degrees() {
+        let pil = types::read_json::<PIL>(
+            r#"{
+            "nCommitments": 3, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Small.b": { "type": "cmP", "id": 1, "polDeg": 2, "isArray": false },
+                "Main.c": { "type": "cmP", "id": 2, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        assert!(PolsArray::new_with_layout(&pil, PolKind::Commit, Layout::RowMajor).is_err());
+
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        assert_eq!(pols.n, 4);
+        assert_eq!(pols.len(), 10);
+        assert_eq!(pols.groups.len(), 2);
+        assert_eq!(
+            (pols.groups[1].pols.clone(), pols.groups[1].offset),
+            (vec![1], 8)
+        );
+        for id in 0..3 {
+            for j in 0..pols.pol_deg(id) {
+                pols.set_value(id, j, FGL::from((10 * id + j) as u64));
+            }
+        }
+
+        // the rows of a and c, then the rows of b
+        let file = std::env::temp_dir().join(format!("mixed_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols.save(fileName).unwrap();
+        let raw: Vec<u64> = fs::read(fileName)
+            .unwrap()
+            .chunks(8)
+            .map(|c| u64::from_le_bytes(c.try_into().unwrap()))
+            .collect();
+        assert_eq!(raw, vec![0, 20, 1, 21, 2, 22, 3, 23, 10, 11]);
+
+        let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        loaded.load(fileName).unwrap();
+        let mapped = PolsArray::new_mapped(&pil, PolKind::Commit, fileName).unwrap();
+        for id in 0..3 {
+            assert_eq!(loaded.column(id), pols.column(id));
+            assert_eq!(mapped.column(id), pols.column(id));
+        }
+        assert_eq!(mapped.value(1, 1), FGL::from(11u64));
+        fs::remove_file(file).unwrap();
+        assert!(matches!(
+            pols.write_buff::<F3G>(),
+            Err(PilError::SizeMismatch {
+                expected: 4,
+                actual: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_element_types() {
+        let json = r#"{
+            "nCommitments": 3, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false,
+                    "elementType": "u8" },
+                "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": true, "len": 2,
+                    "elementType": "s8" }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#;
+        let pil = types::read_json::<PIL>(json.to_string()).unwrap();
+        let (ns, a, b) = ("Main".to_string(), "a".to_string(), "b".to_string());
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        assert_eq!(pols.element_type(2), ElementType::S8);
+
+        // unchecked unless asked for
+        pols.set_matrix(&pil, &ns, &a, 0, 1, FGL::from(256u64));
+        pols.set_matrix(&pil, &ns, &b, 1, 3, -FGL::from(129u64));
+        pols.set_matrix(&pil, &ns, &b, 0, 2, -FGL::from(128u64));
+        let violations = pols.type_violations();
+        assert_eq!(
+            violations.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec![
+                "Main.a w=1: 256 is not a u8",
+                "Main.b[1] w=3: 18446744069414584192 is not a s8"
+            ]
+        );
+
+        let file = std::env::temp_dir().join(format!("types_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols.save(fileName).unwrap();
+        let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        loaded.set_validate_types(true);
+        match loaded.load(fileName) {
+            Err(PilError::ElementType(v)) => assert_eq!(v, violations),
+            r => panic!("expected type violations, got {:?}", r),
+        }
+        fs::remove_file(file).unwrap();
+
+        assert!(loaded
+            .try_set_matrix(&pil, &ns, &a, 0, 1, FGL::from(300u64))
+            .is_err());
+        assert_eq!(loaded.value(0, 1), FGL::from(256u64));
+        loaded
+            .try_set_matrix(&pil, &ns, &a, 0, 1, FGL::from(255u64))
+            .unwrap();
+        loaded.set_matrix(&pil, &ns, &b, 1, 3, FGL::ZERO);
+        assert!(loaded.check_types().is_ok());
+
+        let bad = types::read_json::<PIL>(json.replace("\"s8\"", "\"s128\"")).unwrap();
+        assert!(matches!(
+            PolsArray::new(&bad, PolKind::Commit),
+            Err(PilError::MalformedPil(_))
+        ));
+    }
+
+    #[test]
+    fn test_versioned_file() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.to_string()).unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        for i in 0..pols.n {
+            pols.array[0][i] = FGL::from(i as u64);
+            pols.array[1][i] = FGL::from(10 + i as u64);
+        }
+        let file = std::env::temp_dir().join(format!("versioned_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols.save_as(fileName, FileFormat::Versioned).unwrap();
+
+        let header = WitnessHeader::load(fileName).unwrap().unwrap();
+        assert_eq!((header.n, header.nPols), (4, 2));
+        assert_eq!(header.columns[1].name, "Main.b");
+        assert_eq!(
+            fs::metadata(fileName).unwrap().len() as usize,
+            header.size() + 64
+        );
+
+        let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        loaded.load(fileName).unwrap();
+        assert_eq!(loaded.array, pols.array);
+        let mut mapped = PolsArray::new_mapped(&pil, PolKind::Commit, fileName).unwrap();
+        assert_eq!(mapped.column(1), pols.column(1));
+        assert!(matches!(mapped.load(fileName), Err(PilError::Layout(_))));
+
+        let load_err = |pil: &PIL, kind| {
+            let mut loaded = PolsArray::new(pil, kind).unwrap();
+            loaded.load(fileName).unwrap_err().to_string()
+        };
+        let other = types::read_json::<PIL>(TWO_POLS_PIL.replace("Main.b", "Main.c")).unwrap();
+        assert_eq!(
+            load_err(&other, PolKind::Commit),
+            "witness of a different PIL: pol 1 is Main.b of 4 rows in the file, \
+             Main.c of 4 rows in the PIL"
+        );
+        let constants = TWO_POLS_PIL.replace("\"nConstants\": 0", "\"nConstants\": 2");
+        let constants = types::read_json::<PIL>(constants.replace("cmP", "constP")).unwrap();
+        assert_eq!(
+            load_err(&constants, PolKind::Constant),
+            "witness of a different PIL: the file holds committed polynomials, constant \
+             expected"
+        );
+        let mut other = pil.clone();
+        other.nQ = 1;
+        assert!(load_err(&other, PolKind::Commit).starts_with(
+            "witness of a different PIL: same polynomials, but the PIL fingerprint is"
+        ));
+
+        // flip a value
+        let mut contents = fs::read(fileName).unwrap();
+        let last = contents.len() - 1;
+        contents[last] ^= 1;
+        fs::write(fileName, contents).unwrap();
+        assert!(load_err(&pil, PolKind::Commit).starts_with("corrupt witness file: content hash"));
+        assert!(matches!(
+            PolsArray::new_mapped(&pil, PolKind::Commit, fileName),
+            Err(PilError::CorruptFile(msg)) if msg.starts_with("content hash")
+        ));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_raw_file_with_magic_prefix() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.to_string()).unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        // "PILW", the start of the magic, in the low bytes of the first value
+        pols.array[0][0] = FGL::from(1464617296u64);
+        pols.array[1][3] = FGL::from(7u64);
+        let file = std::env::temp_dir().join(format!("magic_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols.save(fileName).unwrap();
+        assert_eq!(fs::read(fileName).unwrap()[..4], *b"PILW");
+
+        let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        loaded.load(fileName).unwrap();
+        assert_eq!(loaded.array, pols.array);
+        let mapped = PolsArray::new_mapped(&pil, PolKind::Commit, fileName).unwrap();
+        assert_eq!(mapped.value(0, 0), FGL::from(1464617296u64));
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_values_out_of_field() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.to_string()).unwrap();
+        let pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        let file = std::env::temp_dir().join(format!("out_of_field_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        for format in [FileFormat::Raw, FileFormat::Versioned] {
+            pols.save_as(fileName, format).unwrap();
+            // the modulus, as the second value of row 2
+            let mut contents = fs::read(fileName).unwrap();
+            let at = contents.len() - 3 * 8;
+            contents[at..at + 8].copy_from_slice(&MODULUS.0[0].to_le_bytes());
+            fs::write(fileName, contents).unwrap();
+
+            let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+            assert!(matches!(
+                loaded.load(fileName),
+                Err(PilError::CorruptFile(msg))
+                    if msg == "18446744069414584321 is not a field element, at value 5"
+            ));
+            assert!(matches!(
+                PolsArray::new_mapped(&pil, PolKind::Commit, fileName),
+                Err(PilError::CorruptFile(_))
+            ));
+        }
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn test_packed_file() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.replace(
+            "\"isArray\": false }",
+            "\"isArray\": false, \"elementType\": \"s8\" }",
+        ))
+        .unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        for i in 0..pols.n {
+            pols.array[0][i] = FGL::from(i as u64 % 2);
+            pols.array[1][i] = FGL::from(3u64) - FGL::from(i as u64);
+        }
+        let dir = std::env::temp_dir();
+        let raw = dir.join(format!("packed_{}.raw", std::process::id()));
+        pols.save(raw.to_str().unwrap()).unwrap();
+
+        let mut formats = vec![FileFormat::Packed];
+        if cfg!(feature = "zstd") {
+            formats.push(FileFormat::PackedZstd { level: 3 });
+        }
+        for format in formats {
+            let file = dir.join(format!("packed_{:?}_{}.commit", format, std::process::id()));
+            let fileName = file.to_str().unwrap();
+            pols.save_as(fileName, format).unwrap();
+            let header = WitnessHeader::load(fileName).unwrap().unwrap();
+            assert_ne!(header.encoding, Encoding::Plain);
+
+            let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+            loaded.load(fileName).unwrap();
+            assert_eq!(loaded.array, pols.array);
+            assert!(matches!(
+                PolsArray::new_mapped(&pil, PolKind::Commit, fileName),
+                Err(PilError::Layout(_))
+            ));
+            fs::remove_file(file).unwrap();
+        }
+
+        // two columns of 4 values, of 1 and 2 bits each
+        let file = dir.join(format!("packed_{}.commit", std::process::id()));
+        let fileName = file.to_str().unwrap();
+        pols.save_as(fileName, FileFormat::Packed).unwrap();
+        let size = WitnessHeader::load(fileName).unwrap().unwrap().size();
+        assert_eq!(
+            fs::metadata(fileName).unwrap().len() as usize,
+            size + 2 * 10 + 1 + 1
+        );
+
+        // a value changed after the header was written
+        let mut contents = fs::read(fileName).unwrap();
+        contents[size + 10] ^= 1;
+        fs::write(fileName, contents).unwrap();
+        let mut loaded = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        assert!(matches!(
+            loaded.load(fileName),
+            Err(PilError::CorruptFile(_))
+        ));
+        fs::remove_file(file).unwrap();
+        fs::remove_file(raw).unwrap();
+    }
+
+    #[test]
+    fn test_handles() {
+        let pil = types::read_json::<PIL>(
+            r#"{
+            "nCommitments": 4, "nQ": 0, "nIm": 0, "nConstants": 0, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": true, "len": 2 },
+                "Main.c": { "type": "cmP", "id": 3, "polDeg": 4, "isArray": false,
+                    "elementType": "u8" }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        let b = pols.handle("Main.b", 1).unwrap();
+        assert_eq!((b.id(), b.pol_deg(), b.kind()), (2, 4, PolKind::Commit));
+        for j in 0..b.pol_deg() {
+            b.set(&mut pols, j, FGL::from(j as u64));
+        }
+        assert_eq!(b.get(&pols, 3), FGL::from(3u64));
+        assert_eq!(
+            pols.get(&pil, &"Main".to_string(), &"b".to_string(), 1, 2),
+            FGL::from(2u64)
+        );
+
+        assert!(matches!(
+            pols.handle("Main.B", 0),
+            Err(PilError::UnknownPol(name)) if name == "Main.B"
+        ));
+        assert!(matches!(pols.handle("b", 0), Err(PilError::UnknownPol(_))));
+        assert!(matches!(
+            pols.handle("Main.b", 2),
+            Err(PilError::OutOfRange { id: 2, len: 2, .. })
+        ));
+        assert!(pols.handle("Main.a", 1).is_err());
+
+        pols.set_validate_types(true);
+        let c = pols.handle("Main.c", 0).unwrap();
+        assert!(c.try_set(&mut pols, 0, FGL::from(256u64)).is_err());
+        assert_eq!(c.get(&pols, 0), FGL::ZERO);
+        c.set(&mut pols, 0, FGL::from(255u64));
+        assert_eq!(c.get(&pols, 0), FGL::from(255u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "committed handle used with constant polynomials")]
+    fn test_handle_kind() {
+        let pil = types::read_json::<PIL>(
+            r#"{
+            "nCommitments": 1, "nQ": 0, "nIm": 0, "nConstants": 1, "publics": [],
+            "references": {
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.L1": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [], "polIdentities": [], "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap();
+        let cm = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        let consts = PolsArray::new(&pil, PolKind::Constant).unwrap();
+        let a = cm.handle("Main.a", 0).unwrap();
+        a.get(&consts, 0);
+    }
+
+    #[test]
+    fn test_write_buff() {
+        let pil = types::read_json::<PIL>(TWO_POLS_PIL.to_string()).unwrap();
+        let mut pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        for i in 0..pols.n {
+            pols.array[0][i] = FGL::from(i as u64);
+            pols.array[1][i] = FGL::from(10 + i as u64);
+        }
+        let buff = pols.write_buff::<F3G>().unwrap();
+        assert_eq!(buff.len(), 8);
+        assert_eq!(buff[2], F3G::from(1u64));
+        assert_eq!(buff[3], F3G::from(11u64));
+        assert_eq!(buff[3].dim(), 1);
+    }
 }
@@ -0,0 +1,203 @@
+#![allow(non_snake_case)]
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The kind of PIL identity a failure was found in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum IdentityKind {
+    Pol,
+    Plookup,
+    Permutation,
+    Connection,
+}
+
+/// Why a row failed its identity.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum FailureReason {
+    /// The identity expression does not evaluate to zero.
+    NotZero,
+    /// The `f` tuple is not in the `t` set of a plookup.
+    NotFound,
+    /// The `f` tuple shows up more times than in `t`.
+    OverRepresented { fCount: usize, tCount: usize },
+    /// The `f` tuple shows up fewer times than in `t`.
+    UnderRepresented { fCount: usize, tCount: usize },
+    /// The value at (`expIds[0]`, `row`) differs from the one it is connected to, at
+    /// (`expIds[1]`, `w2`). `p1` and `p2` are the positions in the connection identity.
+    ConnectionMismatch { p1: usize, p2: usize, w2: usize },
+    /// The connection value at (`expIds[0]`, `row`) is not in the permutation domain. `p1` is
+    /// the position in the connection identity.
+    InvalidCopy { p1: usize },
+}
+
+/// A single failing row of a PIL identity.
+///
+/// `expIds` are the ids of the expressions involved and `values` the observed values, in
+/// canonical form.
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct VerificationFailure {
+    pub kind: IdentityKind,
+    pub identity: usize,
+    pub fileName: String,
+    pub line: usize,
+    pub row: usize,
+    pub expIds: Vec<usize>,
+    pub values: Vec<u64>,
+    pub reason: FailureReason,
+}
+
+fn join_values(vals: &[u64]) -> String {
+    vals.iter()
+        .map(|v| v.to_string())
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+impl fmt::Display for VerificationFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.reason {
+            FailureReason::NotZero => write!(
+                f,
+                "{}:{}: identity does not match w={} val={}",
+                self.fileName, self.line, self.row, self.values[0]
+            ),
+            FailureReason::NotFound => write!(
+                f,
+                "{}:{}: plookup not found w={} values: {}",
+                self.fileName,
+                self.line,
+                self.row,
+                join_values(&self.values)
+            ),
+            FailureReason::OverRepresented { fCount, tCount } => write!(
+                f,
+                "{}:{}: permutation over-represented in f w={} values: {} f_count={} t_count={}",
+                self.fileName,
+                self.line,
+                self.row,
+                join_values(&self.values),
+                fCount,
+                tCount
+            ),
+            FailureReason::UnderRepresented { fCount, tCount } => write!(
+                f,
+                "{}:{}: permutation under-represented in f w={} values: {} f_count={} t_count={}",
+                self.fileName,
+                self.line,
+                self.row,
+                join_values(&self.values),
+                fCount,
+                tCount
+            ),
+            FailureReason::ConnectionMismatch { p1, p2, w2 } => write!(
+                f,
+                "{:?}:{:?}: connection does not match p1={:?} w1={:?} p2={:?} w2={:?} val= {} != {}",
+                self.fileName, self.line, p1, self.row, p2, w2, self.values[0], self.values[1]
+            ),
+            FailureReason::InvalidCopy { p1 } => write!(
+                f,
+                "{:?}:{:?}: invalid copy value w={:?},{:?} val={:?}",
+                self.fileName, self.line, p1, self.row, self.values[0]
+            ),
+        }
+    }
+}
+
+/// The outcome of `PilVerify::verify_pil`, one record per failing row.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct VerificationReport {
+    pub failures: Vec<VerificationFailure>,
+    #[serde(skip)]
+    max_failures: Option<usize>,
+}
+
+impl VerificationReport {
+    /// A report that stops recording once it holds `max_failures` failures.
+    pub fn with_max_failures(max_failures: usize) -> Self {
+        VerificationReport {
+            failures: vec![],
+            max_failures: Some(max_failures),
+        }
+    }
+
+    pub fn push(&mut self, failure: VerificationFailure) {
+        if self.is_full() {
+            return;
+        }
+        self.failures.push(failure);
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.max_failures
+            .is_some_and(|max| self.failures.len() >= max)
+    }
+
+    pub fn len(&self) -> usize {
+        self.failures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, VerificationFailure> {
+        self.failures.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a VerificationReport {
+    type Item = &'a VerificationFailure;
+    type IntoIter = std::slice::Iter<'a, VerificationFailure>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.failures.iter()
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for failure in &self.failures {
+            writeln!(f, "{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_serde() {
+        let mut report = VerificationReport::default();
+        report.push(VerificationFailure {
+            kind: IdentityKind::Permutation,
+            identity: 0,
+            fileName: "main.pil".to_string(),
+            line: 12,
+            row: 3,
+            expIds: vec![4, 5],
+            values: vec![1, 2],
+            reason: FailureReason::OverRepresented {
+                fCount: 2,
+                tCount: 1,
+            },
+        });
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{"failures":[{"kind":"permutation","identity":0,"fileName":"main.pil","line":12,"row":3,"expIds":[4,5],"values":[1,2],"reason":{"type":"overRepresented","fCount":2,"tCount":1}}]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<VerificationReport>(&json).unwrap(),
+            report
+        );
+        assert_eq!(
+            report.to_string(),
+            "main.pil:12: permutation over-represented in f w=3 values: 1,2 f_count=2 t_count=1\n"
+        );
+    }
+}
@@ -0,0 +1,1908 @@
+#![allow(non_snake_case)]
+//! The STARK setup of a PIL, pil-stark's `starkInfoGen`, serialized in the shape of its
+//! `starkinfo.json`.
+//!
+//! Working on a copy of the PIL, the plookup, permutation and connection identities get
+//! their committed polynomials: `h1`/`h2` in stage 2, the grand products `z` in stage 3,
+//! each with its two constraints, `L1 * (z - 1)` and `z' * den - z * num`. All the
+//! constraints are then folded with the challenge `vc` into the constraint expression
+//! `cExp`. Subexpressions whose degree exceeds what the blowup allows, `2^(nBitsExt -
+//! nBits) + 1`, are committed as intermediate polynomials in stage 3, each one constrained
+//! to its expression, and the quotient of `cExp` by the vanishing polynomial is split into
+//! `qDeg` pieces of dimension `qDim`, committed in stage 4.
+//!
+//! Each step gets the code pil-stark's `pilCodeGen` generates for the expressions it
+//! calculates, one operation per node, the prover steps on the `n` rows (`step2prev`,
+//! `step3prev`, `step3`) or on the extended ones (`step42ns` for `cExp`, `step52ns` for the
+//! FRI polynomial `friExpId`). The evaluations the verifier needs at `xi` (`evMap`) come in
+//! the order its code of `cExp` (`verifierCode`) reads them, followed by the quotient pieces,
+//! and the code of the FRI polynomial at the queries (`verifierQueryCode`) reads the trees.
+//! Every polynomial gets its place in the map sections of the prover buffers, and the
+//! references of the code point there.
+use fields::field_gl::Fr as FGL;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::error::{get_checked, PilError, Result};
+use super::expr::{Expr, TypedExpression};
+use super::types::{StarkStruct, PIL};
+
+// the ids pil-stark gives its challenges
+const U: usize = 0;
+const DEF_VAL: usize = 1;
+const GAMMA: usize = 2;
+const BETA: usize = 3;
+const VC: usize = 4;
+const VF1: usize = 5;
+const VF2: usize = 6;
+
+/// Generator of the cosets of the connection identities, as in `PilVerify::get_Ks`.
+const K: u64 = 12275445934081160404;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PlookupCtx {
+    pub tExpId: usize,
+    pub fExpId: usize,
+    pub h1Id: usize,
+    pub h2Id: usize,
+    pub zId: usize,
+    pub c1Id: usize,
+    pub numId: usize,
+    pub denId: usize,
+    pub c2Id: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PermutationCtx {
+    pub tExpId: usize,
+    pub fExpId: usize,
+    pub zId: usize,
+    pub c1Id: usize,
+    pub numId: usize,
+    pub denId: usize,
+    pub c2Id: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ConnectionCtx {
+    pub zId: usize,
+    pub numId: usize,
+    pub denId: usize,
+    pub c1Id: usize,
+    pub c2Id: usize,
+}
+
+/// A section of the prover buffers: the polynomials of every stage on the `n` rows and on
+/// the extended `2ns` ones, the expressions kept on `n` rows, the constraint polynomial and
+/// the FRI polynomial.
+#[allow(non_camel_case_types)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Section {
+    #[default]
+    cm1_n,
+    cm2_n,
+    cm3_n,
+    cm4_n,
+    tmpExp_n,
+    cm1_2ns,
+    cm2_2ns,
+    cm3_2ns,
+    cm4_2ns,
+    q_2ns,
+    f_2ns,
+}
+
+impl Section {
+    /// The sections, in the order of the buffers: the `n` ones first.
+    pub const ALL: [Section; 11] = [
+        Section::cm1_n,
+        Section::cm2_n,
+        Section::cm3_n,
+        Section::cm4_n,
+        Section::tmpExp_n,
+        Section::cm1_2ns,
+        Section::cm2_2ns,
+        Section::cm3_2ns,
+        Section::cm4_2ns,
+        Section::q_2ns,
+        Section::f_2ns,
+    ];
+
+    /// The sections of the committed polynomials of every stage, on `n` rows and on `2ns`.
+    const CM_N: [Section; 4] = [
+        Section::cm1_n,
+        Section::cm2_n,
+        Section::cm3_n,
+        Section::cm4_n,
+    ];
+    const CM_2NS: [Section; 4] = [
+        Section::cm1_2ns,
+        Section::cm2_2ns,
+        Section::cm3_2ns,
+        Section::cm4_2ns,
+    ];
+
+    /// Whether the section is on the extended rows.
+    pub fn is_2ns(self) -> bool {
+        Section::ALL[5..].contains(&self)
+    }
+}
+
+/// A polynomial of the prover buffers: its section and its position in the rows of it.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct VarPol {
+    pub section: Section,
+    pub dim: usize,
+    pub sectionPos: usize,
+}
+
+/// A polynomial the verifier evaluates at `xi`, or at `w * xi` when `prime`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EvPol {
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub id: usize,
+    pub prime: bool,
+}
+
+/// An operand or the destination of an operation of the generated code. Besides its
+/// `type` and `dim`, a reference has the fields of its type: `id` but for `number` (its
+/// `value`) and `x`, `prime` for the polynomials and expressions, `p`, the `varPolMap` entry
+/// of a committed polynomial in the prover, `expId` for the expressions held in temporaries
+/// or sections (whose `id` is then the `varPolMap` entry), and `treePos`, the column in the
+/// tree of a committed polynomial.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Ref {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub id: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub prime: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub p: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub expId: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub treePos: Option<usize>,
+    pub dim: usize,
+}
+
+impl Ref {
+    fn new(type_: &str, id: Option<usize>) -> Self {
+        Ref {
+            type_: type_.to_string(),
+            id,
+            ..Default::default()
+        }
+    }
+
+    fn id(&self) -> Result<usize> {
+        self.id.ok_or_else(|| {
+            PilError::MalformedPil(format!("a {} reference of the code without id", self.type_))
+        })
+    }
+
+    fn prime(&self) -> usize {
+        self.prime.unwrap_or(false) as usize
+    }
+}
+
+/// `dest = op(src)`, `op` being `add`, `sub`, `mul` or `copy`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct CodeOp {
+    pub op: String,
+    pub dest: Ref,
+    pub src: Vec<Ref>,
+}
+
+/// The code of a step, and the temporaries it needs.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Code {
+    pub tmpUsed: usize,
+    pub code: Vec<CodeOp>,
+}
+
+impl Code {
+    /// Call `f` on every reference, the operands of each operation before its destination,
+    /// with the temporaries used.
+    fn iterate(&mut self, mut f: impl FnMut(&mut Ref, &mut usize) -> Result<()>) -> Result<()> {
+        for op in self.code.iter_mut() {
+            for r in op.src.iter_mut() {
+                f(r, &mut self.tmpUsed)?;
+            }
+            f(&mut op.dest, &mut self.tmpUsed)?;
+        }
+        Ok(())
+    }
+}
+
+/// The `evMap` entry of the committed and constant polynomials the verifier evaluates,
+/// at `xi` first and at `w * xi` second.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct EvIdx {
+    pub cm: [BTreeMap<usize, usize>; 2],
+    #[serde(rename = "const")]
+    pub const_: [BTreeMap<usize, usize>; 2],
+}
+
+/// One value per section of the prover buffers: the polynomials of every stage on the
+/// `n` rows and on the extended `2ns` ones, the expressions kept on `n` rows, the
+/// constraint polynomial and the FRI polynomial.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Sections<T> {
+    pub cm1_n: T,
+    pub cm1_2ns: T,
+    pub cm2_n: T,
+    pub cm2_2ns: T,
+    pub cm3_n: T,
+    pub cm3_2ns: T,
+    pub cm4_n: T,
+    pub cm4_2ns: T,
+    pub tmpExp_n: T,
+    pub q_2ns: T,
+    pub f_2ns: T,
+}
+
+impl<T> Sections<T> {
+    pub fn get(&self, section: Section) -> &T {
+        match section {
+            Section::cm1_n => &self.cm1_n,
+            Section::cm1_2ns => &self.cm1_2ns,
+            Section::cm2_n => &self.cm2_n,
+            Section::cm2_2ns => &self.cm2_2ns,
+            Section::cm3_n => &self.cm3_n,
+            Section::cm3_2ns => &self.cm3_2ns,
+            Section::cm4_n => &self.cm4_n,
+            Section::cm4_2ns => &self.cm4_2ns,
+            Section::tmpExp_n => &self.tmpExp_n,
+            Section::q_2ns => &self.q_2ns,
+            Section::f_2ns => &self.f_2ns,
+        }
+    }
+
+    pub fn get_mut(&mut self, section: Section) -> &mut T {
+        match section {
+            Section::cm1_n => &mut self.cm1_n,
+            Section::cm1_2ns => &mut self.cm1_2ns,
+            Section::cm2_n => &mut self.cm2_n,
+            Section::cm2_2ns => &mut self.cm2_2ns,
+            Section::cm3_n => &mut self.cm3_n,
+            Section::cm3_2ns => &mut self.cm3_2ns,
+            Section::cm4_n => &mut self.cm4_n,
+            Section::cm4_2ns => &mut self.cm4_2ns,
+            Section::tmpExp_n => &mut self.tmpExp_n,
+            Section::q_2ns => &mut self.q_2ns,
+            Section::f_2ns => &mut self.f_2ns,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct StarkInfo {
+    pub starkStruct: StarkStruct,
+    pub nConstants: usize,
+    pub nPublics: usize,
+    pub nCm1: usize,
+    pub nCm2: usize,
+    pub nCm3: usize,
+    pub nCm4: usize,
+    pub puCtx: Vec<PlookupCtx>,
+    pub peCtx: Vec<PermutationCtx>,
+    pub ciCtx: Vec<ConnectionCtx>,
+    /// The linear combinations of the plookups.
+    pub step2prev: Code,
+    /// Those of the permutations, and the `num` and `den` of every grand product.
+    pub step3prev: Code,
+    /// The constraint expression, an id in the expressions of the extended PIL.
+    pub cExp: usize,
+    /// The expressions committed as intermediate polynomials, and their committed ids.
+    pub imExpsList: Vec<usize>,
+    pub imExp2cm: BTreeMap<usize, usize>,
+    /// The intermediate polynomials.
+    pub step3: Code,
+    pub qDeg: usize,
+    pub qDim: usize,
+    /// The committed ids of the quotient pieces.
+    pub qs: Vec<usize>,
+    /// The constraint polynomial, into `q_2ns`.
+    pub step42ns: Code,
+    /// `cExp` at `xi`, from the evaluations.
+    pub verifierCode: Code,
+    pub evIdx: EvIdx,
+    pub evMap: Vec<EvPol>,
+    /// The FRI polynomial, an id in the expressions of the extended PIL.
+    pub friExpId: usize,
+    /// The FRI polynomial, into `f_2ns`.
+    pub step52ns: Code,
+    /// The FRI polynomial at a query, from the values of the trees.
+    pub verifierQueryCode: Code,
+    pub varPolMap: Vec<VarPol>,
+    /// The `varPolMap` entry of every committed polynomial on `n` rows, the quotient pieces
+    /// having none.
+    pub cm_n: Vec<usize>,
+    pub cm_2ns: Vec<usize>,
+    /// The `varPolMap` entry of the expressions kept on `n` rows.
+    pub exps_n: Vec<Option<usize>>,
+    /// The `varPolMap` entry of the constraint polynomial.
+    pub exps_2ns: Vec<Option<usize>>,
+    pub mapSections: Sections<Vec<usize>>,
+    pub mapSectionsN: Sections<usize>,
+    pub mapSectionsN1: Sections<usize>,
+    pub mapSectionsN3: Sections<usize>,
+    pub mapOffsets: Sections<usize>,
+    pub mapTotalN: usize,
+    pub mapDeg: Sections<usize>,
+}
+
+/// A node of the expressions of the extended PIL: those of the PIL, and the ones pil-stark
+/// adds to the constraints it derives and to the FRI polynomial, which a PIL cannot hold.
+#[derive(Debug, Clone, PartialEq)]
+enum StarkExpr {
+    Add(Box<StarkExpression>, Box<StarkExpression>),
+    Sub(Box<StarkExpression>, Box<StarkExpression>),
+    Mul(Box<StarkExpression>, Box<StarkExpression>),
+    AddC(Box<StarkExpression>, i64),
+    MulC(Box<StarkExpression>, i64),
+    Neg(Box<StarkExpression>),
+    Cm {
+        id: usize,
+        next: bool,
+    },
+    Const {
+        id: usize,
+        next: bool,
+    },
+    Exp {
+        id: usize,
+        next: bool,
+    },
+    Number(FGL),
+    Public(usize),
+    /// A verifier challenge.
+    Challenge(usize),
+    /// The evaluation point.
+    X,
+    /// The value at `xi` of entry `id` of `evMap`.
+    Eval(usize),
+    /// `x / (x - xi)`.
+    XDivXSubXi,
+    /// `x / (x - w * xi)`.
+    XDivXSubWXi,
+}
+
+/// An expression of the extended PIL, with the annotations of its PIL expression that the
+/// setup reads.
+#[derive(Debug, Clone, PartialEq)]
+struct StarkExpression {
+    expr: StarkExpr,
+    deg: usize,
+    keep: Option<bool>,
+    keep2ns: Option<bool>,
+    /// A number as the PIL writes it, which the code keeps.
+    raw_value: Option<String>,
+}
+
+impl StarkExpression {
+    fn new(expr: StarkExpr, deg: usize) -> Self {
+        StarkExpression {
+            expr,
+            deg,
+            keep: None,
+            keep2ns: None,
+            raw_value: None,
+        }
+    }
+
+    /// The `op` of this node, which names the references of the code.
+    fn op(&self) -> &'static str {
+        match &self.expr {
+            StarkExpr::Add(..) => "add",
+            StarkExpr::Sub(..) => "sub",
+            StarkExpr::Mul(..) => "mul",
+            StarkExpr::AddC(..) => "addc",
+            StarkExpr::MulC(..) => "mulc",
+            StarkExpr::Neg(..) => "neg",
+            StarkExpr::Cm { .. } => "cm",
+            StarkExpr::Const { .. } => "const",
+            StarkExpr::Exp { .. } => "exp",
+            StarkExpr::Number(..) => "number",
+            StarkExpr::Public(..) => "public",
+            StarkExpr::Challenge(..) => "challenge",
+            StarkExpr::X => "x",
+            StarkExpr::Eval(..) => "eval",
+            StarkExpr::XDivXSubXi => "xDivXSubXi",
+            StarkExpr::XDivXSubWXi => "xDivXSubWXi",
+        }
+    }
+
+    /// The direct operands of this node, empty for leaves.
+    fn operands(&self) -> Vec<&StarkExpression> {
+        match &self.expr {
+            StarkExpr::Add(a, b) | StarkExpr::Sub(a, b) | StarkExpr::Mul(a, b) => vec![a, b],
+            StarkExpr::AddC(a, _) | StarkExpr::MulC(a, _) | StarkExpr::Neg(a) => vec![a],
+            _ => vec![],
+        }
+    }
+}
+
+impl From<&TypedExpression> for StarkExpression {
+    fn from(e: &TypedExpression) -> Self {
+        let node = |a: &TypedExpression| Box::new(StarkExpression::from(a));
+        let expr = match &e.expr {
+            Expr::Add(a, b) => StarkExpr::Add(node(a), node(b)),
+            Expr::Sub(a, b) => StarkExpr::Sub(node(a), node(b)),
+            Expr::Mul(a, b) => StarkExpr::Mul(node(a), node(b)),
+            Expr::AddC(a, c) => StarkExpr::AddC(node(a), *c),
+            Expr::MulC(a, c) => StarkExpr::MulC(node(a), *c),
+            Expr::Neg(a) => StarkExpr::Neg(node(a)),
+            Expr::Cm { id, next } => StarkExpr::Cm {
+                id: *id,
+                next: *next,
+            },
+            Expr::Const { id, next } => StarkExpr::Const {
+                id: *id,
+                next: *next,
+            },
+            Expr::Exp { id, next } => StarkExpr::Exp {
+                id: *id,
+                next: *next,
+            },
+            Expr::Number(v) => StarkExpr::Number(*v),
+            Expr::Public(id) => StarkExpr::Public(*id),
+        };
+        StarkExpression {
+            expr,
+            deg: e.deg,
+            keep: e.keep,
+            keep2ns: e.keep2ns,
+            raw_value: e.raw_value.clone(),
+        }
+    }
+}
+
+/// The PIL the setup works on: the expressions of `source`, to which the derived ones are
+/// added, its constraints, and its committed polynomials with their dimensions, followed by
+/// the new ones.
+struct ExtendedPil<'a> {
+    source: &'a PIL,
+    expressions: Vec<StarkExpression>,
+    polIdentities: Vec<usize>,
+    cm_dims: Vec<usize>,
+}
+
+impl<'a> ExtendedPil<'a> {
+    fn new(pil: &'a PIL) -> Self {
+        ExtendedPil {
+            source: pil,
+            expressions: pil.expressions.iter().map(StarkExpression::from).collect(),
+            polIdentities: pil.polIdentities.iter().map(|pi| pi.e).collect(),
+            cm_dims: vec![1; pil.nCommitments],
+        }
+    }
+}
+
+fn leaf(expr: StarkExpr) -> StarkExpression {
+    let deg = match expr {
+        StarkExpr::Cm { .. } | StarkExpr::Const { .. } | StarkExpr::X => 1,
+        _ => 0,
+    };
+    StarkExpression::new(expr, deg)
+}
+
+fn cm(id: usize, next: bool) -> StarkExpression {
+    leaf(StarkExpr::Cm { id, next })
+}
+
+fn number(v: FGL) -> StarkExpression {
+    leaf(StarkExpr::Number(v))
+}
+
+fn challenge(id: usize) -> StarkExpression {
+    leaf(StarkExpr::Challenge(id))
+}
+
+fn add(a: StarkExpression, b: StarkExpression) -> StarkExpression {
+    let deg = a.deg.max(b.deg);
+    StarkExpression::new(StarkExpr::Add(Box::new(a), Box::new(b)), deg)
+}
+
+fn sub(a: StarkExpression, b: StarkExpression) -> StarkExpression {
+    let deg = a.deg.max(b.deg);
+    StarkExpression::new(StarkExpr::Sub(Box::new(a), Box::new(b)), deg)
+}
+
+fn mul(a: StarkExpression, b: StarkExpression) -> StarkExpression {
+    let deg = a.deg + b.deg;
+    StarkExpression::new(StarkExpr::Mul(Box::new(a), Box::new(b)), deg)
+}
+
+/// A reference to expression `id` of `pil`.
+fn exp(pil: &ExtendedPil, id: usize, next: bool) -> Result<StarkExpression> {
+    let deg = get_checked(&pil.expressions, id, "expression")?.deg;
+    Ok(StarkExpression::new(StarkExpr::Exp { id, next }, deg))
+}
+
+/// Push `e` to the expressions of `pil`, returning its id.
+fn push(pil: &mut ExtendedPil, e: StarkExpression) -> usize {
+    pil.expressions.push(e);
+    pil.expressions.len() - 1
+}
+
+fn push_keep(pil: &mut ExtendedPil, mut e: StarkExpression) -> usize {
+    e.keep = Some(true);
+    push(pil, e)
+}
+
+/// Constrain expression `e` of `pil` to be zero.
+fn push_identity(pil: &mut ExtendedPil, e: usize) {
+    pil.polIdentities.push(e);
+}
+
+/// A new committed polynomial of dimension `dim`.
+fn commit(pil: &mut ExtendedPil, dim: usize) -> usize {
+    pil.cm_dims.push(dim);
+    pil.cm_dims.len() - 1
+}
+
+fn expressions<'a>(ids: &'a Option<Vec<usize>>, what: &str) -> Result<&'a [usize]> {
+    match ids.as_deref() {
+        Some(ids) if !ids.is_empty() => Ok(ids),
+        _ => Err(PilError::MalformedPil(format!(
+            "{} without expressions",
+            what
+        ))),
+    }
+}
+
+/// `L1 * (z - 1)`, which needs the constant `Global.L1`.
+fn first_row_is_one(pil: &ExtendedPil, z: usize) -> Result<StarkExpression> {
+    let l1 = pil
+        .source
+        .references
+        .get("Global.L1")
+        .ok_or_else(|| PilError::MalformedPil("Global.L1 must be defined".to_string()))?;
+    let l1 = leaf(StarkExpr::Const {
+        id: l1.id,
+        next: false,
+    });
+    Ok(mul(l1, sub(cm(z, false), number(FGL::ONE))))
+}
+
+/// `z' * den - z * num`.
+fn grand_product(pil: &ExtendedPil, z: usize, num: usize, den: usize) -> Result<StarkExpression> {
+    Ok(sub(
+        mul(cm(z, true), exp(pil, den, false)?),
+        mul(cm(z, false), exp(pil, num, false)?),
+    ))
+}
+
+/// Check that the expressions of `pil` only refer to polynomials, publics and expressions
+/// it has, and that none of them depends on itself, so that the walks below can index and
+/// recurse through them.
+fn check_expressions(pil: &ExtendedPil) -> Result<()> {
+    let mut checked = vec![false; pil.expressions.len()];
+    let mut visiting = vec![false; pil.expressions.len()];
+    for id in 0..pil.expressions.len() {
+        check_expression(pil, id, &mut checked, &mut visiting)?;
+    }
+    Ok(())
+}
+
+fn check_expression(
+    pil: &ExtendedPil,
+    id: usize,
+    checked: &mut Vec<bool>,
+    visiting: &mut Vec<bool>,
+) -> Result<()> {
+    if *get_checked(checked, id, "expression")? {
+        return Ok(());
+    }
+    if visiting[id] {
+        return Err(PilError::MalformedPil(format!(
+            "expression {} depends on itself",
+            id
+        )));
+    }
+    visiting[id] = true;
+    check_node(pil, &pil.expressions[id], checked, visiting)?;
+    visiting[id] = false;
+    checked[id] = true;
+    Ok(())
+}
+
+fn check_node(
+    pil: &ExtendedPil,
+    e: &StarkExpression,
+    checked: &mut Vec<bool>,
+    visiting: &mut Vec<bool>,
+) -> Result<()> {
+    match &e.expr {
+        StarkExpr::Cm { id, .. } => get_checked(&pil.cm_dims, *id, "cm").map(|_| ()),
+        StarkExpr::Const { id, .. } if *id >= pil.source.nConstants => Err(PilError::OutOfRange {
+            kind: "const",
+            id: *id,
+            len: pil.source.nConstants,
+        }),
+        StarkExpr::Public(id) => get_checked(&pil.source.publics, *id, "public").map(|_| ()),
+        StarkExpr::Exp { id, .. } => check_expression(pil, *id, checked, visiting),
+        _ => {
+            for o in e.operands() {
+                check_node(pil, o, checked, visiting)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Dimension of expression `e`: 3 when it involves a challenge or a polynomial of the
+/// extension field, 1 otherwise.
+fn dim(pil: &ExtendedPil, e: &StarkExpression, memo: &mut HashMap<usize, usize>) -> usize {
+    match &e.expr {
+        StarkExpr::Cm { id, .. } => pil.cm_dims[*id],
+        StarkExpr::Challenge(_) | StarkExpr::Eval(_) => 3,
+        StarkExpr::Exp { id, .. } => {
+            if let Some(d) = memo.get(id) {
+                return *d;
+            }
+            let d = dim(pil, &pil.expressions[*id], memo);
+            memo.insert(*id, d);
+            d
+        }
+        _ => e
+            .operands()
+            .into_iter()
+            .map(|o| dim(pil, o, memo))
+            .fold(1, usize::max),
+    }
+}
+
+fn exp_dim(pil: &ExtendedPil, id: usize) -> usize {
+    dim(pil, &pil.expressions[id], &mut HashMap::new())
+}
+
+/// Intermediate polynomials and the degree they leave, `None` when there are none.
+type ImChoice = Option<(BTreeSet<usize>, usize)>;
+
+/// The choice of intermediate polynomials, pil-stark's `calculateImPols`: the fewest
+/// expressions to commit so that every constraint, once they are replaced by their
+/// polynomials, is of degree `absoluteMax` at most.
+struct ImPols<'a> {
+    expressions: &'a [StarkExpression],
+    degs: Vec<Option<usize>>,
+    absoluteMax: usize,
+    /// The largest degree of the expressions chosen, which their own constraints have.
+    absMaxD: usize,
+    memo: HashMap<(usize, BTreeSet<usize>), ImChoice>,
+}
+
+impl<'a> ImPols<'a> {
+    fn new(expressions: &'a [StarkExpression], absoluteMax: usize) -> Self {
+        ImPols {
+            expressions,
+            degs: vec![None; expressions.len()],
+            absoluteMax,
+            absMaxD: 0,
+            memo: HashMap::new(),
+        }
+    }
+
+    fn degree(&mut self, e: &StarkExpression) -> usize {
+        match &e.expr {
+            StarkExpr::Mul(a, b) => self.degree(a) + self.degree(b),
+            StarkExpr::Cm { .. } | StarkExpr::Const { .. } | StarkExpr::X => 1,
+            StarkExpr::Exp { id, .. } => {
+                if let Some(d) = self.degs[*id] {
+                    return d;
+                }
+                let expressions = self.expressions;
+                let d = self.degree(&expressions[*id]);
+                self.degs[*id] = Some(d);
+                d
+            }
+            _ => e
+                .operands()
+                .into_iter()
+                .map(|o| self.degree(o))
+                .fold(0, usize::max),
+        }
+    }
+
+    /// The intermediate polynomials that, added to `imPols`, bring `e` to `maxDeg` at most,
+    /// and the degree it then has.
+    fn calculate(
+        &mut self,
+        e: &StarkExpression,
+        imPols: &BTreeSet<usize>,
+        maxDeg: usize,
+    ) -> ImChoice {
+        match &e.expr {
+            StarkExpr::Add(..)
+            | StarkExpr::Sub(..)
+            | StarkExpr::AddC(..)
+            | StarkExpr::MulC(..)
+            | StarkExpr::Neg(..) => {
+                let mut imPols = imPols.clone();
+                let mut md = 0;
+                for o in e.operands() {
+                    let (im, d) = self.calculate(o, &imPols, maxDeg)?;
+                    imPols = im;
+                    md = md.max(d);
+                }
+                Some((imPols, md))
+            }
+            StarkExpr::Number(_)
+            | StarkExpr::Public(_)
+            | StarkExpr::Challenge(_)
+            | StarkExpr::Eval(_) => Some((imPols.clone(), 0)),
+            StarkExpr::Cm { .. }
+            | StarkExpr::Const { .. }
+            | StarkExpr::X
+            | StarkExpr::XDivXSubXi
+            | StarkExpr::XDivXSubWXi => (maxDeg >= 1).then(|| (imPols.clone(), 1)),
+            StarkExpr::Mul(a, b) => {
+                let isScalar = |e: &StarkExpression| {
+                    matches!(
+                        e.expr,
+                        StarkExpr::Number(_) | StarkExpr::Public(_) | StarkExpr::Challenge(_)
+                    )
+                };
+                if isScalar(a) {
+                    return self.calculate(b, imPols, maxDeg);
+                }
+                if isScalar(b) {
+                    return self.calculate(a, imPols, maxDeg);
+                }
+                let maxDegHere = self.degree(e);
+                if maxDegHere <= maxDeg {
+                    return Some((imPols.clone(), maxDegHere));
+                }
+                let mut best: ImChoice = None;
+                for l in 0..=maxDeg {
+                    let r = maxDeg - l;
+                    let split = self.calculate(a, imPols, l).and_then(|(e1, d1)| {
+                        self.calculate(b, &e1, r).map(|(e2, d2)| (e2, d1 + d2))
+                    });
+                    if let Some((e2, d)) = split {
+                        if best.as_ref().is_none_or(|(eb, _)| e2.len() < eb.len()) {
+                            best = Some((e2, d));
+                        }
+                    }
+                    // nothing added, it cannot get better
+                    if best
+                        .as_ref()
+                        .is_some_and(|(eb, _)| eb.len() == imPols.len())
+                    {
+                        break;
+                    }
+                }
+                best
+            }
+            StarkExpr::Exp { id, .. } => {
+                if maxDeg < 1 {
+                    return None;
+                }
+                if imPols.contains(id) {
+                    return Some((imPols.clone(), 1));
+                }
+                let key = (*id, imPols.clone());
+                let res = match self.memo.get(&key) {
+                    Some(res) => res.clone(),
+                    None => {
+                        let expressions = self.expressions;
+                        let res = self.calculate(&expressions[*id], imPols, self.absoluteMax);
+                        self.memo.insert(key, res.clone());
+                        res
+                    }
+                };
+                let (im, d) = res?;
+                if d > maxDeg {
+                    let mut im = im;
+                    im.insert(*id);
+                    self.absMaxD = self.absMaxD.max(d);
+                    Some((im, 1))
+                } else {
+                    Some((im, d))
+                }
+            }
+        }
+    }
+}
+
+/// pil-stark's `pilCodeGen`: the code calculating expressions, each one after the ones it
+/// refers to, its value going to the destination `exp` of its last operation. The
+/// expressions calculated are not calculated again until the code is built.
+#[derive(Default)]
+struct CodeGen {
+    calculated: [BTreeSet<usize>; 2],
+    tmpUsed: usize,
+    code: Vec<CodeOp>,
+}
+
+impl CodeGen {
+    /// Take expressions `ids` as calculated, so that the code reads them instead.
+    fn calculated(ids: &[usize]) -> Self {
+        let ids: BTreeSet<usize> = ids.iter().copied().collect();
+        CodeGen {
+            calculated: [ids.clone(), ids],
+            ..Default::default()
+        }
+    }
+
+    fn shifted(id: usize, prime: bool, next: bool) -> Result<bool> {
+        if prime && next {
+            return Err(PilError::MalformedPil(format!(
+                "a shifted reference in expression {}, itself shifted",
+                id
+            )));
+        }
+        Ok(prime || next)
+    }
+
+    /// Add the code of expression `id`, shifted when `prime`.
+    fn exp(&mut self, pil: &ExtendedPil, id: usize, prime: bool) -> Result<()> {
+        if self.calculated[prime as usize].contains(&id) {
+            return Ok(());
+        }
+        let e = &pil.expressions[id];
+        self.deps(pil, e, prime)?;
+
+        let mut tmpUsed = self.tmpUsed;
+        let r = self.eval(e, prime, &mut tmpUsed)?;
+        let mut dest = Ref::new("exp", Some(id));
+        dest.prime = Some(prime);
+        if r.type_ == "tmp" {
+            // the last operation calculated it
+            self.code.last_mut().unwrap().dest = dest;
+            tmpUsed -= 1;
+        } else {
+            self.code.push(CodeOp {
+                op: "copy".to_string(),
+                dest,
+                src: vec![r],
+            });
+        }
+        self.tmpUsed = self.tmpUsed.max(tmpUsed);
+        self.calculated[prime as usize].insert(id);
+        Ok(())
+    }
+
+    fn deps(&mut self, pil: &ExtendedPil, e: &StarkExpression, prime: bool) -> Result<()> {
+        match &e.expr {
+            StarkExpr::Exp { id, next } => self.exp(pil, *id, Self::shifted(*id, prime, *next)?),
+            _ => {
+                for o in e.operands() {
+                    self.deps(pil, o, prime)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// The reference to the value of `e`, after the operations calculating it.
+    fn eval(&mut self, e: &StarkExpression, prime: bool, tmpUsed: &mut usize) -> Result<Ref> {
+        let number = |value: String| Ref {
+            value: Some(value),
+            ..Ref::new("number", None)
+        };
+        let (op, src) = match &e.expr {
+            StarkExpr::Add(a, b) | StarkExpr::Sub(a, b) | StarkExpr::Mul(a, b) => {
+                let a = self.eval(a, prime, tmpUsed)?;
+                let b = self.eval(b, prime, tmpUsed)?;
+                (e.op(), vec![a, b])
+            }
+            StarkExpr::AddC(a, c) | StarkExpr::MulC(a, c) => {
+                let a = self.eval(a, prime, tmpUsed)?;
+                let op = if matches!(e.expr, StarkExpr::AddC(..)) {
+                    "add"
+                } else {
+                    "mul"
+                };
+                (op, vec![a, number(c.to_string())])
+            }
+            StarkExpr::Neg(a) => {
+                let a = self.eval(a, prime, tmpUsed)?;
+                ("sub", vec![number("0".to_string()), a])
+            }
+            StarkExpr::Cm { id, next }
+            | StarkExpr::Const { id, next }
+            | StarkExpr::Exp { id, next } => {
+                let mut r = Ref::new(e.op(), Some(*id));
+                r.prime = Some(Self::shifted(*id, prime, *next)?);
+                return Ok(r);
+            }
+            StarkExpr::Number(v) => {
+                return Ok(number(
+                    e.raw_value
+                        .clone()
+                        .unwrap_or_else(|| v.as_int().to_string()),
+                ))
+            }
+            StarkExpr::Public(id) | StarkExpr::Challenge(id) | StarkExpr::Eval(id) => {
+                return Ok(Ref::new(e.op(), Some(*id)))
+            }
+            StarkExpr::X | StarkExpr::XDivXSubXi | StarkExpr::XDivXSubWXi => {
+                return Ok(Ref::new(e.op(), None))
+            }
+        };
+        let dest = Ref::new("tmp", Some(*tmpUsed));
+        *tmpUsed += 1;
+        self.code.push(CodeOp {
+            op: op.to_string(),
+            dest: dest.clone(),
+            src,
+        });
+        Ok(dest)
+    }
+
+    /// The code added since the last build, which starts afresh.
+    fn build(&mut self) -> Code {
+        let code = Code {
+            tmpUsed: self.tmpUsed,
+            code: std::mem::take(&mut self.code),
+        };
+        *self = CodeGen::default();
+        code
+    }
+}
+
+/// Read expression `r` from a temporary of the code, the one `expMap` gives it or a new one.
+fn exp_to_tmp(
+    r: &mut Ref,
+    expMap: &mut [BTreeMap<usize, usize>; 2],
+    tmpUsed: &mut usize,
+) -> Result<()> {
+    let id = r.id()?;
+    let tmp = *expMap[r.prime()].entry(id).or_insert_with(|| {
+        *tmpUsed += 1;
+        *tmpUsed - 1
+    });
+    r.type_ = "tmp".to_string();
+    r.expId = Some(id);
+    r.id = Some(tmp);
+    Ok(())
+}
+
+/// One of the prover codes of a `StarkInfo`.
+type ProverCode = fn(&mut StarkInfo) -> &mut Code;
+
+impl StarkInfo {
+    /// The STARK setup of `pil` for `starkStruct`. Every polynomial must have
+    /// `2^nBits` rows, and the plookup, permutation and connection identities need the
+    /// constant `Global.L1`. Fails on ids out of range and on expressions that depend on
+    /// themselves.
+    pub fn new(pil: &PIL, starkStruct: &StarkStruct) -> Result<Self> {
+        let (nBits, nBitsExt) = (starkStruct.nBits, starkStruct.nBitsExt);
+        if nBitsExt < nBits {
+            return Err(PilError::StarkStruct(format!(
+                "nBitsExt {} is below nBits {}",
+                nBitsExt, nBits
+            )));
+        }
+        let mut references: Vec<_> = pil.references.iter().collect();
+        references.sort_by_key(|(name, _)| *name);
+        for (name, r) in references {
+            if ["cmP", "constP"].contains(&r.type_.as_str()) && r.polDeg != 1 << nBits {
+                return Err(PilError::StarkStruct(format!(
+                    "{} has {} rows, not 2^{}",
+                    name, r.polDeg, nBits
+                )));
+            }
+        }
+
+        let mut pil = ExtendedPil::new(pil);
+        check_expressions(&pil)?;
+        let mut res = StarkInfo {
+            starkStruct: starkStruct.clone(),
+            nConstants: pil.source.nConstants,
+            nPublics: pil.source.publics.len(),
+            nCm1: pil.cm_dims.len(),
+            ..Default::default()
+        };
+
+        let mut ctx = CodeGen::default();
+        res.generate_plookup_lc(&mut pil, &mut ctx)?;
+        res.step2prev = ctx.build();
+        res.nCm2 = pil.cm_dims.len() - res.nCm1;
+
+        res.generate_permutation_lc(&mut pil, &mut ctx)?;
+        res.generate_plookup_z(&mut pil, &mut ctx)?;
+        res.generate_permutation_z(&mut pil, &mut ctx)?;
+        res.generate_connection_z(&mut pil, &mut ctx)?;
+        res.step3prev = ctx.build();
+        res.generate_constraint_polynomial(&mut pil, &mut ctx)?;
+        res.nCm4 = res.qDeg;
+        res.nCm3 = pil.cm_dims.len() - res.nCm1 - res.nCm2 - res.nCm4;
+
+        res.generate_constraint_polynomial_verifier(&pil)?;
+        res.generate_fri_polynomial(&mut pil)?;
+        res.generate_verifier_query(&pil)?;
+        res.map(&pil)?;
+        Ok(res)
+    }
+
+    /// Stage 2: the linear combinations of the columns of every plookup, and its `h1` and
+    /// `h2`.
+    fn generate_plookup_lc(&mut self, pil: &mut ExtendedPil, ctx: &mut CodeGen) -> Result<()> {
+        for pi in pil.source.plookupIdentities.clone() {
+            let mut tExp: Option<StarkExpression> = None;
+            for &t in expressions(&pi.t, "plookup identity")? {
+                let e = exp(pil, t, false)?;
+                tExp = Some(match tExp {
+                    Some(tExp) => add(mul(challenge(U), tExp), e),
+                    None => e,
+                });
+            }
+            let mut tExp = tExp.unwrap();
+            if let Some(selT) = pi.selT {
+                tExp = sub(tExp, challenge(DEF_VAL));
+                tExp = mul(tExp, exp(pil, selT, false)?);
+                tExp = add(tExp, challenge(DEF_VAL));
+            }
+            let tExpId = push_keep(pil, tExp);
+
+            let mut fExp: Option<StarkExpression> = None;
+            for &f in expressions(&pi.f, "plookup identity")? {
+                let e = exp(pil, f, false)?;
+                fExp = Some(match fExp {
+                    Some(fExp) => add(mul(fExp, challenge(U)), e),
+                    None => e,
+                });
+            }
+            let mut fExp = fExp.unwrap();
+            if let Some(selF) = pi.selF {
+                fExp = sub(fExp, exp(pil, tExpId, false)?);
+                fExp = mul(fExp, exp(pil, selF, false)?);
+                fExp = add(fExp, exp(pil, tExpId, false)?);
+            }
+            let fExpId = push_keep(pil, fExp);
+            ctx.exp(pil, fExpId, false)?;
+            ctx.exp(pil, tExpId, false)?;
+
+            let d = exp_dim(pil, fExpId).max(exp_dim(pil, tExpId));
+            self.puCtx.push(PlookupCtx {
+                tExpId,
+                fExpId,
+                h1Id: commit(pil, d),
+                h2Id: commit(pil, d),
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+
+    /// The linear combinations of the columns of every permutation.
+    fn generate_permutation_lc(&mut self, pil: &mut ExtendedPil, ctx: &mut CodeGen) -> Result<()> {
+        for pi in pil.source.permutationIdentities.clone().unwrap_or_default() {
+            let mut ids = [0; 2];
+            for (i, (columns, sel)) in [(&pi.t, pi.selT), (&pi.f, pi.selF)].into_iter().enumerate()
+            {
+                let mut lc: Option<StarkExpression> = None;
+                for &c in expressions(columns, "permutation identity")? {
+                    let e = exp(pil, c, false)?;
+                    lc = Some(match lc {
+                        Some(lc) => add(mul(lc, challenge(U)), e),
+                        None => e,
+                    });
+                }
+                let mut lc = lc.unwrap();
+                if let Some(sel) = sel {
+                    lc = sub(lc, challenge(DEF_VAL));
+                    lc = mul(lc, exp(pil, sel, false)?);
+                    lc = add(lc, challenge(DEF_VAL));
+                }
+                ids[i] = push_keep(pil, lc);
+            }
+            ctx.exp(pil, ids[1], false)?;
+            ctx.exp(pil, ids[0], false)?;
+            self.peCtx.push(PermutationCtx {
+                tExpId: ids[0],
+                fExpId: ids[1],
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+
+    /// Stage 3: the grand product of every plookup, with `num` and `den` as in pil-stark.
+    fn generate_plookup_z(&mut self, pil: &mut ExtendedPil, ctx: &mut CodeGen) -> Result<()> {
+        for i in 0..self.puCtx.len() {
+            let zId = commit(pil, 3);
+            let PlookupCtx {
+                h1Id,
+                h2Id,
+                fExpId,
+                tExpId,
+                ..
+            } = self.puCtx[i];
+
+            let c1 = first_row_is_one(pil, zId)?;
+            let c1Id = push(pil, c1);
+            push_identity(pil, c1Id);
+
+            let one_beta = || add(number(FGL::ONE), challenge(BETA));
+            let gamma_one_beta = || mul(challenge(GAMMA), one_beta());
+            let numExp = mul(
+                mul(
+                    add(exp(pil, fExpId, false)?, challenge(GAMMA)),
+                    add(
+                        add(
+                            exp(pil, tExpId, false)?,
+                            mul(exp(pil, tExpId, true)?, challenge(BETA)),
+                        ),
+                        gamma_one_beta(),
+                    ),
+                ),
+                one_beta(),
+            );
+            let numId = push_keep(pil, numExp);
+            let denExp = mul(
+                add(
+                    add(cm(h1Id, false), mul(cm(h2Id, false), challenge(BETA))),
+                    gamma_one_beta(),
+                ),
+                add(
+                    add(cm(h2Id, false), mul(cm(h1Id, true), challenge(BETA))),
+                    gamma_one_beta(),
+                ),
+            );
+            let denId = push_keep(pil, denExp);
+
+            let c2 = grand_product(pil, zId, numId, denId)?;
+            let c2Id = push(pil, c2);
+            push_identity(pil, c2Id);
+            ctx.exp(pil, numId, false)?;
+            ctx.exp(pil, denId, false)?;
+
+            let ctx = &mut self.puCtx[i];
+            (ctx.zId, ctx.c1Id, ctx.numId, ctx.denId, ctx.c2Id) = (zId, c1Id, numId, denId, c2Id);
+        }
+        Ok(())
+    }
+
+    /// The grand product of every permutation: `num = f + beta`, `den = t + beta`.
+    fn generate_permutation_z(&mut self, pil: &mut ExtendedPil, ctx: &mut CodeGen) -> Result<()> {
+        for i in 0..self.peCtx.len() {
+            let zId = commit(pil, 3);
+            let c1 = first_row_is_one(pil, zId)?;
+            let c1Id = push(pil, c1);
+            push_identity(pil, c1Id);
+
+            let numExp = add(exp(pil, self.peCtx[i].fExpId, false)?, challenge(BETA));
+            let numId = push_keep(pil, numExp);
+            let denExp = add(exp(pil, self.peCtx[i].tExpId, false)?, challenge(BETA));
+            let denId = push_keep(pil, denExp);
+
+            let c2 = grand_product(pil, zId, numId, denId)?;
+            let c2Id = push(pil, c2);
+            push_identity(pil, c2Id);
+            ctx.exp(pil, numId, false)?;
+            ctx.exp(pil, denId, false)?;
+
+            let ctx = &mut self.peCtx[i];
+            (ctx.zId, ctx.c1Id, ctx.numId, ctx.denId, ctx.c2Id) = (zId, c1Id, numId, denId, c2Id);
+        }
+        Ok(())
+    }
+
+    /// The grand product of every connection, over the cosets `k^i * H` of its columns.
+    fn generate_connection_z(&mut self, pil: &mut ExtendedPil, ctx: &mut CodeGen) -> Result<()> {
+        for ci in pil.source.connectionIdentities.clone().unwrap_or_default() {
+            let pols = expressions(&ci.pols, "connection identity")?;
+            let connections = expressions(&ci.connections, "connection identity")?;
+            if pols.len() != connections.len() {
+                return Err(PilError::MalformedPil(format!(
+                    "connection identity of {} polynomials and {} connections",
+                    pols.len(),
+                    connections.len()
+                )));
+            }
+            let zId = commit(pil, 3);
+
+            let term = |pil: &ExtendedPil, p: usize, shift: StarkExpression| {
+                Ok::<_, PilError>(add(
+                    add(exp(pil, p, false)?, mul(challenge(BETA), shift)),
+                    challenge(GAMMA),
+                ))
+            };
+            let numExp = term(pil, pols[0], leaf(StarkExpr::X))?;
+            let mut numId = push_keep(pil, numExp);
+            let denExp = term(pil, pols[0], exp(pil, connections[0], false)?)?;
+            let mut denId = push_keep(pil, denExp);
+
+            let mut k = FGL::ONE;
+            for i in 1..pols.len() {
+                k = k * FGL::from(K);
+                let x = mul(mul(challenge(BETA), number(k)), leaf(StarkExpr::X));
+                let numExp = mul(
+                    exp(pil, numId, false)?,
+                    add(add(exp(pil, pols[i], false)?, x), challenge(GAMMA)),
+                );
+                let denExp = mul(
+                    exp(pil, denId, false)?,
+                    term(pil, pols[i], exp(pil, connections[i], false)?)?,
+                );
+                numId = push_keep(pil, numExp);
+                denId = push_keep(pil, denExp);
+            }
+
+            let c1 = first_row_is_one(pil, zId)?;
+            let c1Id = push(pil, c1);
+            push_identity(pil, c1Id);
+            let c2 = grand_product(pil, zId, numId, denId)?;
+            let c2Id = push(pil, c2);
+            push_identity(pil, c2Id);
+            ctx.exp(pil, numId, false)?;
+            ctx.exp(pil, denId, false)?;
+
+            self.ciCtx.push(ConnectionCtx {
+                zId,
+                numId,
+                denId,
+                c1Id,
+                c2Id,
+            });
+        }
+        Ok(())
+    }
+
+    /// Stage 4: the constraint expression, the intermediate polynomials that keep it within
+    /// the blowup, and the quotient pieces.
+    fn generate_constraint_polynomial(
+        &mut self,
+        pil: &mut ExtendedPil,
+        ctx: &mut CodeGen,
+    ) -> Result<()> {
+        let mut cExp: Option<StarkExpression> = None;
+        for id in pil.polIdentities.clone() {
+            let e = exp(pil, id, false)?;
+            cExp = Some(match cExp {
+                Some(cExp) => add(mul(challenge(VC), cExp), e),
+                None => e,
+            });
+        }
+        let mut cExp = cExp.unwrap_or_else(|| number(FGL::ZERO));
+
+        let nBitsBlowup = self.starkStruct.nBitsExt - self.starkStruct.nBits;
+        let maxDeg = (1 << nBitsBlowup) + 1;
+        let mut imPols = ImPols::new(&pil.expressions, maxDeg);
+        let (imExps, rd) = imPols
+            .calculate(&cExp, &BTreeSet::new(), maxDeg)
+            .ok_or_else(|| {
+                PilError::StarkStruct(format!(
+                    "the constraints cannot be brought to degree {}, a blowup of 2^{}",
+                    maxDeg, nBitsBlowup
+                ))
+            })?;
+        // the quotient by the vanishing polynomial
+        self.qDeg = rd.max(imPols.absMaxD).saturating_sub(1);
+
+        for id in imExps {
+            let cmId = commit(pil, exp_dim(pil, id));
+            self.imExpsList.push(id);
+            self.imExp2cm.insert(id, cmId);
+            let e = sub(pil.expressions[id].clone(), cm(cmId, false));
+            cExp = add(mul(challenge(VC), cExp), e);
+        }
+        self.cExp = push(pil, cExp);
+
+        self.qDim = exp_dim(pil, self.cExp);
+        for _ in 0..self.qDeg {
+            self.qs.push(commit(pil, self.qDim));
+        }
+
+        for &id in self.imExpsList.iter() {
+            ctx.exp(pil, id, false)?;
+        }
+        self.step3 = ctx.build();
+        // on the extended rows, the intermediate polynomials are committed
+        let mut ctx2ns = CodeGen::calculated(&self.imExpsList);
+        ctx2ns.exp(pil, self.cExp, false)?;
+        self.step42ns = ctx2ns.build();
+        Ok(())
+    }
+
+    /// The code of `cExp` for the verifier. It reads the committed and constant polynomials
+    /// from their evaluations, which make `evMap` in the order it reads them, and the
+    /// intermediate polynomials as committed ones. The quotient pieces close `evMap`.
+    fn generate_constraint_polynomial_verifier(&mut self, pil: &ExtendedPil) -> Result<()> {
+        let mut ctx = CodeGen::calculated(&self.imExpsList);
+        ctx.exp(pil, self.cExp, false)?;
+        let mut code = ctx.build();
+
+        let mut expMap = Default::default();
+        let (evIdx, evMap) = (&mut self.evIdx, &mut self.evMap);
+        code.iterate(|r, tmpUsed| {
+            if r.type_ == "exp" {
+                match self.imExp2cm.get(&r.id()?) {
+                    Some(&cm) => (r.type_, r.id) = ("cm".to_string(), Some(cm)),
+                    None => return exp_to_tmp(r, &mut expMap, tmpUsed),
+                }
+            }
+            let idx = match r.type_.as_str() {
+                "cm" => &mut evIdx.cm,
+                "const" => &mut evIdx.const_,
+                _ => return Ok(()),
+            };
+            let id = r.id()?;
+            let ev = *idx[r.prime()].entry(id).or_insert_with(|| {
+                evMap.push(EvPol {
+                    type_: r.type_.clone(),
+                    id,
+                    prime: r.prime() == 1,
+                });
+                evMap.len() - 1
+            });
+            *r = Ref::new("eval", Some(ev));
+            Ok(())
+        })?;
+        for &q in self.qs.iter() {
+            self.evIdx.cm[0].insert(q, self.evMap.len());
+            self.evMap.push(EvPol {
+                type_: "cm".to_string(),
+                id: q,
+                prime: false,
+            });
+        }
+        self.verifierCode = code;
+        Ok(())
+    }
+
+    /// Stage 5: the FRI polynomial. The differences `p - p(xi)` of the evaluations at `xi`
+    /// and those of the ones at `w * xi` are combined with `vf2`, divided by `x - xi` and
+    /// `x - w * xi`, and the two quotients combined with `vf1`.
+    fn generate_fri_polynomial(&mut self, pil: &mut ExtendedPil) -> Result<()> {
+        let mut fri: [Option<StarkExpression>; 2] = [None, None];
+        for (i, ev) in self.evMap.iter().enumerate() {
+            let p = if ev.type_ == "cm" {
+                cm(ev.id, false)
+            } else {
+                leaf(StarkExpr::Const {
+                    id: ev.id,
+                    next: false,
+                })
+            };
+            let e = sub(p, leaf(StarkExpr::Eval(i)));
+            let f = &mut fri[ev.prime as usize];
+            *f = Some(match f.take() {
+                Some(f) => add(mul(f, challenge(VF2)), e),
+                None => e,
+            });
+        }
+        let mut friExp: Option<StarkExpression> = None;
+        for (f, x) in fri
+            .into_iter()
+            .zip([StarkExpr::XDivXSubXi, StarkExpr::XDivXSubWXi])
+        {
+            if let Some(f) = f {
+                let f = mul(f, leaf(x));
+                friExp = Some(match friExp {
+                    Some(friExp) => add(mul(challenge(VF1), friExp), f),
+                    None => f,
+                });
+            }
+        }
+        let mut friExp = friExp.unwrap_or_else(|| number(FGL::ZERO));
+        friExp.keep2ns = Some(true);
+        self.friExpId = push(pil, friExp);
+
+        let mut ctx = CodeGen::default();
+        ctx.exp(pil, self.friExpId, false)?;
+        self.step52ns = ctx.build();
+        Ok(())
+    }
+
+    /// The code of the FRI polynomial for the verifier, at the queries.
+    fn generate_verifier_query(&mut self, pil: &ExtendedPil) -> Result<()> {
+        let mut ctx = CodeGen::default();
+        ctx.exp(pil, self.friExpId, false)?;
+        let mut code = ctx.build();
+        let mut expMap = Default::default();
+        code.iterate(|r, tmpUsed| {
+            if r.type_ == "exp" {
+                match self.imExp2cm.get(&r.id()?) {
+                    Some(&cm) => (r.type_, r.id) = ("cm".to_string(), Some(cm)),
+                    None => exp_to_tmp(r, &mut expMap, tmpUsed)?,
+                }
+            }
+            Ok(())
+        })?;
+        self.verifierQueryCode = code;
+        Ok(())
+    }
+
+    /// The stage of committed polynomial `id`, from 1 to 4.
+    fn cm_stage(&self, id: usize) -> usize {
+        if id < self.nCm1 {
+            1
+        } else if id < self.nCm1 + self.nCm2 {
+            2
+        } else if id < self.nCm1 + self.nCm2 + self.nCm3 {
+            3
+        } else {
+            4
+        }
+    }
+
+    fn add_pol(&mut self, section: Section, dim: usize) -> usize {
+        self.varPolMap.push(VarPol {
+            section,
+            dim,
+            sectionPos: 0,
+        });
+        self.mapSections
+            .get_mut(section)
+            .push(self.varPolMap.len() - 1);
+        self.varPolMap.len() - 1
+    }
+
+    /// The place of every polynomial in the prover buffers.
+    fn map(&mut self, pil: &ExtendedPil) -> Result<()> {
+        let nQs = self.qs.len();
+        for id in 0..pil.cm_dims.len() - nQs {
+            let stage = self.cm_stage(id);
+            let d = pil.cm_dims[id];
+            let n = self.add_pol(Section::CM_N[stage - 1], d);
+            let ext = self.add_pol(Section::CM_2NS[stage - 1], d);
+            self.cm_n.push(n);
+            self.cm_2ns.push(ext);
+        }
+        for _ in 0..nQs {
+            let ext = self.add_pol(Section::cm4_2ns, self.qDim);
+            self.cm_2ns.push(ext);
+        }
+
+        self.exps_n = vec![None; pil.expressions.len()];
+        for (id, e) in pil.expressions.iter().enumerate() {
+            if e.keep == Some(true) && !self.imExp2cm.contains_key(&id) {
+                self.exps_n[id] = Some(self.add_pol(Section::tmpExp_n, exp_dim(pil, id)));
+            }
+        }
+        self.exps_2ns = vec![None; pil.expressions.len()];
+        self.exps_2ns[self.cExp] = Some(self.add_pol(Section::q_2ns, self.qDim));
+        self.exps_2ns[self.friExpId] = Some(self.add_pol(Section::f_2ns, 3));
+
+        // within a section, the polynomials of the base field come first
+        let (N, Next) = (1 << self.starkStruct.nBits, 1 << self.starkStruct.nBitsExt);
+        let mut offset = 0;
+        for section in Section::ALL {
+            let mut p = 0;
+            for d in [1, 3] {
+                for pol in self.varPolMap.iter_mut() {
+                    if pol.section == section && pol.dim == d {
+                        pol.sectionPos = p;
+                        p += d;
+                    }
+                }
+                if d == 1 {
+                    *self.mapSectionsN1.get_mut(section) = p;
+                } else {
+                    *self.mapSectionsN3.get_mut(section) =
+                        (p - self.mapSectionsN1.get(section)) / 3;
+                }
+            }
+            *self.mapSectionsN.get_mut(section) = p;
+
+            let deg = if section.is_2ns() { Next } else { N };
+            *self.mapDeg.get_mut(section) = deg;
+            *self.mapOffsets.get_mut(section) = offset;
+            offset += deg * p;
+        }
+        self.mapTotalN = offset;
+
+        let steps: [(ProverCode, bool); 5] = [
+            (|s| &mut s.step2prev, false),
+            (|s| &mut s.step3prev, false),
+            (|s| &mut s.step3, false),
+            (|s| &mut s.step42ns, true),
+            (|s| &mut s.step52ns, true),
+        ];
+        for (step, ext) in steps {
+            let code = std::mem::take(step(self));
+            *step(self) = self.fix_prover_code(code, ext)?;
+        }
+        // the verifier reads the committed polynomials from the trees of their stage
+        let mut code = std::mem::take(&mut self.verifierQueryCode);
+        code.iterate(|r, _| {
+            if r.type_ == "cm" {
+                let id = r.id()?;
+                let pol = &self.varPolMap[self.cm_2ns[id]];
+                r.type_ = format!("tree{}", self.cm_stage(id));
+                r.treePos = Some(pol.sectionPos);
+                r.dim = pol.dim;
+            }
+            Ok(())
+        })?;
+        self.verifierQueryCode = code;
+
+        for (code, dimX) in [
+            (&mut self.step2prev, 1),
+            (&mut self.step3prev, 1),
+            (&mut self.step3, 1),
+            (&mut self.step42ns, 1),
+            (&mut self.step52ns, 1),
+            (&mut self.verifierCode, 3),
+            (&mut self.verifierQueryCode, 1),
+        ] {
+            set_code_dimensions(code, &self.varPolMap, dimX)?;
+        }
+        Ok(())
+    }
+
+    /// Point the references of prover code to the buffers, on the `n` rows or on the
+    /// extended ones when `ext`: the committed polynomials, the intermediate ones included,
+    /// to their `varPolMap` entry, and the expressions to the section that keeps them or to
+    /// temporaries.
+    fn fix_prover_code(&self, mut code: Code, ext: bool) -> Result<Code> {
+        let mut expMap = Default::default();
+        code.iterate(|r, tmpUsed| {
+            if r.type_ == "exp" {
+                let id = r.id()?;
+                if let Some(&cm) = self.imExp2cm.get(&id) {
+                    (r.type_, r.id) = ("cm".to_string(), Some(cm));
+                } else {
+                    let kept = if ext {
+                        self.exps_2ns[id]
+                    } else {
+                        self.exps_n[id]
+                    };
+                    let Some(p) = kept else {
+                        return exp_to_tmp(r, &mut expMap, tmpUsed);
+                    };
+                    r.type_ = match self.varPolMap[p].section {
+                        Section::tmpExp_n => "tmpExp",
+                        Section::q_2ns => "q",
+                        _ => "f",
+                    }
+                    .to_string();
+                    r.expId = Some(id);
+                    r.id = Some(p);
+                }
+            }
+            if r.type_ == "cm" {
+                r.p = Some(if ext {
+                    self.cm_2ns[r.id()?]
+                } else {
+                    self.cm_n[r.id()?]
+                });
+            }
+            Ok(())
+        })?;
+        Ok(code)
+    }
+}
+
+/// Set the dimension of every reference of `code`, the one of `x` being `dimX`. The
+/// destination of an operation has the largest dimension of its operands.
+fn set_code_dimensions(code: &mut Code, varPolMap: &[VarPol], dimX: usize) -> Result<()> {
+    let mut tmpDim = vec![0; code.tmpUsed];
+    for op in code.code.iter_mut() {
+        let mut dim = 1;
+        for r in op.src.iter_mut() {
+            r.dim = match r.type_.as_str() {
+                "tmp" => tmpDim[r.id()?],
+                "cm" => {
+                    let p = r.p.ok_or_else(|| {
+                        PilError::MalformedPil("a cm reference of the code without p".to_string())
+                    })?;
+                    varPolMap[p].dim
+                }
+                "tmpExp" => varPolMap[r.id()?].dim,
+                t if t.starts_with("tree") => r.dim,
+                "eval" | "challenge" => 3,
+                "x" | "xDivXSubXi" | "xDivXSubWXi" => dimX,
+                _ => 1,
+            };
+            dim = dim.max(r.dim);
+        }
+        op.dest.dim = dim;
+        if op.dest.type_ == "tmp" {
+            tmpDim[op.dest.id()?] = dim;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::types::read_json;
+
+    fn stark_struct(nBits: usize, nBitsExt: usize) -> StarkStruct {
+        StarkStruct {
+            nBits,
+            nBitsExt,
+            nQueries: 8,
+            verificationHashType: "GL".to_string(),
+            steps: vec![],
+        }
+    }
+
+    // namespace Global(4);
+    //     pol constant L1;
+    // namespace Main(4);
+    //     pol commit a, b, c;
+    //     a * b * c = 0;
+    fn cubic_pil() -> PIL {
+        read_json::<PIL>(
+            r#"{
+            "nCommitments": 3, "nQ": 0, "nIm": 0, "nConstants": 1, "publics": [],
+            "references": {
+                "Global.L1": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": false },
+                "Main.c": { "type": "cmP", "id": 2, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [
+                { "op": "mul", "deg": 2, "values": [
+                    { "op": "cm", "deg": 1, "id": 0, "next": false },
+                    { "op": "cm", "deg": 1, "id": 1, "next": false } ] },
+                { "op": "mul", "deg": 3, "values": [
+                    { "op": "exp", "deg": 2, "id": 0, "next": false },
+                    { "op": "cm", "deg": 1, "id": 2, "next": false } ] }
+            ],
+            "polIdentities": [ { "e": 1, "fileName": "main.pil", "line": 6 } ],
+            "plookupIdentities": []
+        }"#
+            .to_string(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_intermediate_polynomials() {
+        // a blowup of 4 allows degree 5, no intermediate polynomial is needed
+        let info = StarkInfo::new(&cubic_pil(), &stark_struct(2, 4)).unwrap();
+        assert!(info.imExpsList.is_empty());
+        assert_eq!((info.qDeg, info.qDim), (2, 1));
+        assert_eq!((info.nCm1, info.nCm2, info.nCm3, info.nCm4), (3, 0, 0, 2));
+        assert_eq!(info.qs, vec![3, 4]);
+        let ev: Vec<(&str, usize, bool)> = info
+            .evMap
+            .iter()
+            .map(|e| (e.type_.as_str(), e.id, e.prime))
+            .collect();
+        assert_eq!(
+            ev,
+            vec![
+                ("cm", 0, false),
+                ("cm", 1, false),
+                ("cm", 2, false),
+                ("cm", 3, false),
+                ("cm", 4, false)
+            ]
+        );
+
+        // a blowup of 2 allows degree 3, that of a * b * c
+        let info = StarkInfo::new(&cubic_pil(), &stark_struct(2, 3)).unwrap();
+        assert!(info.imExpsList.is_empty());
+        assert_eq!(info.qDeg, 2);
+
+        // no blowup allows degree 2: a * b is committed, and constrained to be a * b
+        let info = StarkInfo::new(&cubic_pil(), &stark_struct(2, 2)).unwrap();
+        assert_eq!(info.imExpsList, vec![0]);
+        assert_eq!(info.imExp2cm[&0], 3);
+        assert_eq!((info.qDeg, info.qDim), (1, 3));
+        assert_eq!((info.nCm1, info.nCm3, info.nCm4), (3, 1, 1));
+        assert_eq!(info.evMap.len(), 5);
+        assert_eq!(info.evMap[0].id, 3);
+    }
+
+    #[test]
+    fn test_map_sections() {
+        let info = StarkInfo::new(&cubic_pil(), &stark_struct(2, 2)).unwrap();
+        // a, b and c, then a * b, then the quotient piece of the extension field
+        assert_eq!(info.mapSections.cm1_n, vec![0, 2, 4]);
+        assert_eq!(info.mapSections.cm3_n, vec![6]);
+        assert_eq!(info.mapSections.cm4_2ns, vec![8]);
+        assert_eq!(info.mapSectionsN.cm1_n, 3);
+        assert_eq!(info.mapSectionsN.cm4_2ns, 3);
+        assert_eq!(
+            (info.mapSectionsN1.cm4_2ns, info.mapSectionsN3.cm4_2ns),
+            (0, 1)
+        );
+        assert_eq!(info.varPolMap[4].sectionPos, 2);
+        assert_eq!(info.cm_n, vec![0, 2, 4, 6]);
+        assert_eq!(info.cm_2ns, vec![1, 3, 5, 7, 8]);
+
+        // the n sections, then the 2ns ones: 3 + 1 columns, then 3 + 1 + 3 + 3 + 3
+        assert_eq!(info.mapOffsets.cm3_n, 12);
+        assert_eq!(info.mapOffsets.cm1_2ns, 16);
+        assert_eq!(info.mapOffsets.cm4_2ns, 32);
+        assert_eq!(info.mapOffsets.f_2ns, 56);
+        assert_eq!(info.mapTotalN, 68);
+
+        let json = serde_json::to_value(&info).unwrap();
+        assert_eq!(json["mapSectionsN"]["cm1_n"], 3);
+        assert_eq!(json["imExp2cm"]["0"], 3);
+        assert_eq!(json["evMap"][0]["type"], "cm");
+
+        let info = StarkInfo::new(&cubic_pil(), &stark_struct(2, 4)).unwrap();
+        assert_eq!((info.mapDeg.tmpExp_n, info.mapDeg.q_2ns), (4, 16));
+        assert_eq!(info.mapOffsets.cm2_2ns, 12 + 3 * 16);
+    }
+
+    // namespace Global(4);
+    //     pol constant L1, T;
+    // namespace Main(4);
+    //     pol commit a, b, c, d;
+    //     a in Global.T;
+    //     { a } is { b };
+    //     { c, d } connect { Global.T, Global.T };
+    fn arguments_pil(withL1: bool) -> PIL {
+        let json = r#"{
+            "nCommitments": 4, "nQ": 0, "nIm": 0, "nConstants": 2, "publics": [],
+            "references": {
+                "Global.L1": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+                "Global.T": { "type": "constP", "id": 1, "polDeg": 4, "isArray": false },
+                "Main.a": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false },
+                "Main.b": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": false },
+                "Main.c": { "type": "cmP", "id": 2, "polDeg": 4, "isArray": false },
+                "Main.d": { "type": "cmP", "id": 3, "polDeg": 4, "isArray": false }
+            },
+            "expressions": [
+                { "op": "cm", "deg": 1, "id": 0, "next": false },
+                { "op": "const", "deg": 1, "id": 1, "next": false },
+                { "op": "cm", "deg": 1, "id": 1, "next": false },
+                { "op": "cm", "deg": 1, "id": 2, "next": false },
+                { "op": "cm", "deg": 1, "id": 3, "next": false }
+            ],
+            "polIdentities": [],
+            "plookupIdentities": [
+                { "f": [0], "t": [1], "selF": null, "selT": null, "fileName": "main.pil", "line": 7 }
+            ],
+            "permutationIdentities": [
+                { "f": [0], "t": [2], "selF": null, "selT": null, "fileName": "main.pil", "line": 8 }
+            ],
+            "connectionIdentities": [
+                { "pols": [3, 4], "connections": [1, 1], "fileName": "main.pil", "line": 9 }
+            ]
+        }"#;
+        let json = if withL1 {
+            json.to_string()
+        } else {
+            json.replace("Global.L1", "Global.L2")
+        };
+        read_json::<PIL>(json).unwrap()
+    }
+
+    #[test]
+    fn test_grand_products() {
+        let info = StarkInfo::new(&arguments_pil(true), &stark_struct(2, 3)).unwrap();
+        let pu = &info.puCtx[0];
+        assert_eq!((pu.tExpId, pu.fExpId, pu.h1Id, pu.h2Id), (5, 6, 4, 5));
+        assert_eq!(
+            (pu.zId, pu.c1Id, pu.numId, pu.denId, pu.c2Id),
+            (6, 9, 10, 11, 12)
+        );
+        let pe = &info.peCtx[0];
+        assert_eq!((pe.tExpId, pe.fExpId), (7, 8));
+        assert_eq!(
+            (pe.zId, pe.c1Id, pe.numId, pe.denId, pe.c2Id),
+            (7, 13, 14, 15, 16)
+        );
+        // the products of both columns
+        let ci = &info.ciCtx[0];
+        assert_eq!(
+            (ci.zId, ci.numId, ci.denId, ci.c1Id, ci.c2Id),
+            (8, 19, 20, 21, 22)
+        );
+        assert_eq!((info.nCm1, info.nCm2), (4, 2));
+
+        // h1 and h2 take the dimension of single columns, z is of the extension field
+        assert_eq!(info.varPolMap[info.cm_n[4]].dim, 1);
+        assert_eq!(info.varPolMap[info.cm_n[6]].dim, 3);
+        // f and t of both, num and den of all three
+        assert_eq!(info.mapSections.tmpExp_n.len(), 4 + 2 + 2 + 4);
+        assert_eq!(info.exps_n[20], info.mapSections.tmpExp_n.last().copied());
+        assert!(info.evMap.contains(&EvPol {
+            type_: "cm".to_string(),
+            id: 6,
+            prime: true
+        }));
+        assert!(info.evMap.contains(&EvPol {
+            type_: "const".to_string(),
+            id: 0,
+            prime: false
+        }));
+
+        assert!(matches!(
+            StarkInfo::new(&arguments_pil(false), &stark_struct(2, 3)),
+            Err(PilError::MalformedPil(msg)) if msg == "Global.L1 must be defined"
+        ));
+    }
+
+    #[test]
+    fn test_malformed_pil() {
+        let new = |pil: &PIL| StarkInfo::new(pil, &stark_struct(2, 3));
+        let node = TypedExpression::new;
+        let c = Box::new(node(Expr::Cm { id: 2, next: false }, 1));
+
+        let mut pil = cubic_pil();
+        pil.polIdentities[0].e = 2;
+        assert!(matches!(
+            new(&pil),
+            Err(PilError::OutOfRange {
+                kind: "expression",
+                id: 2,
+                len: 2
+            })
+        ));
+
+        let mut pil = cubic_pil();
+        pil.expressions[1] = node(Expr::Cm { id: 3, next: false }, 1);
+        assert!(matches!(
+            new(&pil),
+            Err(PilError::OutOfRange {
+                kind: "cm",
+                id: 3,
+                len: 3
+            })
+        ));
+
+        let mut pil = cubic_pil();
+        let missing = Box::new(node(Expr::Exp { id: 5, next: false }, 1));
+        pil.expressions[1] = node(Expr::Mul(missing, c.clone()), 2);
+        assert!(matches!(
+            new(&pil),
+            Err(PilError::OutOfRange {
+                kind: "expression",
+                id: 5,
+                len: 2
+            })
+        ));
+
+        // expression 0 refers to 1, which refers to 0
+        let mut pil = cubic_pil();
+        let e1 = Box::new(node(Expr::Exp { id: 1, next: false }, 3));
+        pil.expressions[0] = node(Expr::Mul(e1, c.clone()), 4);
+        assert!(matches!(
+            new(&pil),
+            Err(PilError::MalformedPil(msg)) if msg == "expression 0 depends on itself"
+        ));
+        let mut pil = cubic_pil();
+        let e1 = Box::new(node(Expr::Exp { id: 1, next: true }, 3));
+        pil.expressions[1] = node(Expr::Add(e1, c), 3);
+        assert!(matches!(
+            new(&pil),
+            Err(PilError::MalformedPil(msg)) if msg == "expression 1 depends on itself"
+        ));
+        // nor can the code lose the ids of its references
+        assert!(matches!(
+            Ref::new("tmp", None).id(),
+            Err(PilError::MalformedPil(_))
+        ));
+    }
+
+    // the expected file was computed with a Node transcription of pil-stark's starkInfoGen
+    // (steps 2 to 5, the verifier codes and map) from stark_info_pil.json, where expression 8
+    // (a * c) becomes an intermediate polynomial
+    #[test]
+    fn test_pil_stark_stark_info() {
+        let pil = read_json::<PIL>(include_str!("stark_info_pil.json").to_string()).unwrap();
+        let starkStruct = read_json::<StarkStruct>(
+            r#"{ "nBits": 2, "nBitsExt": 3, "nQueries": 8, "verificationHashType": "GL",
+            "steps": [{ "nBits": 3 }, { "nBits": 2 }] }"#
+                .to_string(),
+        )
+        .unwrap();
+        let info = StarkInfo::new(&pil, &starkStruct).unwrap();
+        let expected: serde_json::Value =
+            serde_json::from_str(include_str!("stark_info_expected.json")).unwrap();
+        assert_eq!(serde_json::to_value(&info).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stark_struct() {
+        assert!(matches!(
+            StarkInfo::new(&cubic_pil(), &stark_struct(3, 4)),
+            Err(PilError::StarkStruct(_))
+        ));
+        assert!(matches!(
+            StarkInfo::new(&cubic_pil(), &stark_struct(2, 1)),
+            Err(PilError::StarkStruct(_))
+        ));
+    }
+}
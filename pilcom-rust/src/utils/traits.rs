@@ -1,11 +1,14 @@
-use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+#![allow(non_snake_case)]
 use ::rand::Rand;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 use fields::field_gl::Fr as FGL;
 use fields::Field;
 use serde::{de::DeserializeOwned, ser::Serialize};
 use std::fmt::{Debug, Display};
 use std::hash::Hash;
+use std::io::{Read, Write};
 
+use super::error::Result;
 
 pub trait FieldExtension:
     From<FGL>
@@ -62,4 +65,33 @@ pub trait FieldExtension:
     // TODO: Add generate rand fields vector for test/dev.
     // fn rand_
     // (&self) -> &[u8];
-}
\ No newline at end of file
+}
+
+/// A Merkle tree over the rows of a buffer of Goldilocks values, one per hash pil-stark can
+/// commit with (`StarkStruct::verificationHashType`).
+pub trait MerkleTree: Sized {
+    /// A node, and so the root.
+    type MTNode: Copy + PartialEq + Debug;
+    /// What an authentication path holds for each level.
+    type Siblings: Clone + Debug;
+
+    /// Build the tree of `elements`, `height` rows of `width` values.
+    fn merkelize(elements: Vec<FGL>, width: usize, height: usize) -> Result<Self>;
+    fn root(&self) -> Self::MTNode;
+    /// The value of column `subIdx` at row `idx`.
+    fn get_element(&self, idx: usize, subIdx: usize) -> FGL;
+    /// The values of row `idx` and its authentication path, from the leaves.
+    fn get_group_proof(&self, idx: usize) -> Result<(Vec<FGL>, Vec<Self::Siblings>)>;
+    /// Whether `values` are row `idx` of the tree of `root`, given its authentication path.
+    fn verify_group_proof(
+        root: &Self::MTNode,
+        path: &[Self::Siblings],
+        idx: usize,
+        values: &[FGL],
+    ) -> bool;
+    /// Write the tree as pil-stark's `writeToFile`: width and height as u64, the elements
+    /// and then the nodes, all little-endian.
+    fn write(&self, w: &mut impl Write) -> Result<()>;
+    /// Read a tree `write` wrote.
+    fn read(r: &mut impl Read) -> Result<Self>;
+}
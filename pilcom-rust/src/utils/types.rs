@@ -9,6 +9,9 @@ use std::io::Read;
 
 use anyhow::{anyhow, Result};
 
+use super::error::PilError;
+use super::expr::TypedExpression;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Public {
     pub polType: String,
@@ -148,7 +151,7 @@ pub struct PIL {
     pub publics: Vec<Public>,
     #[serde(serialize_with = "ordered_map")]
     pub references: HashMap<String, Reference>,
-    pub expressions: Vec<Expression>,
+    pub expressions: Vec<TypedExpression>,
     pub polIdentities: Vec<PolIdentity>,
     pub plookupIdentities: Vec<PlookupIdentity>,
     pub permutationIdentities: Option<Vec<PermutationIdentity>>,
@@ -219,18 +222,20 @@ where
 }
 
 #[inline(always)]
-pub fn parse_pil_number(raw_val: &str) -> u64 {
+pub fn parse_pil_number(raw_val: &str) -> std::result::Result<u64, PilError> {
     //let raw_val = r.value.as_ref().unwrap();
-    let mut n_val: i128 = match raw_val.starts_with("0x") {
-        true => i128::from_str_radix(&raw_val[2..], 16).unwrap(),
-        _ => raw_val.parse::<i128>().unwrap(),
+    let n_val: std::result::Result<i128, _> = match raw_val.starts_with("0x") {
+        true => i128::from_str_radix(&raw_val[2..], 16),
+        _ => raw_val.parse::<i128>(),
     };
+    let mut n_val =
+        n_val.map_err(|e| PilError::MalformedPil(format!("invalid number {}: {}", raw_val, e)))?;
     // FIXME: Goldilocks modular, try to fetch it from FieldExtension
+    n_val %= 18446744069414584321;
     if n_val < 0 {
         n_val += 18446744069414584321;
     }
-    n_val %= 18446744069414584321;
-    n_val as u64
+    Ok(n_val as u64)
 }
 
 #[cfg(test)]
@@ -274,4 +279,15 @@ mod test {
     }"#;
         read_json::<StarkStruct>(json_str.to_string()).unwrap();
     }
+
+    #[test]
+    pub fn test_parse_pil_number() {
+        assert_eq!(parse_pil_number("42").unwrap(), 42);
+        assert_eq!(parse_pil_number("0x10").unwrap(), 16);
+        assert_eq!(parse_pil_number("-1").unwrap(), 18446744069414584320);
+        assert!(matches!(
+            parse_pil_number("1a"),
+            Err(PilError::MalformedPil(_))
+        ));
+    }
 }
@@ -0,0 +1,269 @@
+#![allow(non_snake_case)]
+//! Value Change Dump export of witness traces, for waveform viewers such as GTKWave.
+//!
+//! Every polynomial is a signal in the scope of its PIL namespace, and row `j` is time `j`.
+//! Signals are as wide as their `elementType`, 64 bits for field elements, and signed types
+//! are dumped in two's complement so that viewers can show them as signed decimals. A
+//! polynomial of fewer rows than the trace turns to `x` after its last one.
+use fields::field_gl::{Fr as FGL, MODULUS};
+use std::borrow::Cow;
+use std::io::Write;
+use std::ops::Range;
+
+use super::element_type::ElementType;
+use super::error::Result;
+use super::pol_text::Selection;
+use super::polarray::{pol_name, PolsArray};
+
+/// A signal of the dump.
+#[derive(Debug, Clone)]
+pub struct Signal<'a> {
+    /// Reference name, `Main.b[0]`. The part before the first dot is the scope.
+    pub name: String,
+    pub elementType: ElementType,
+    /// The value at every row.
+    pub values: Cow<'a, [FGL]>,
+}
+
+impl Signal<'_> {
+    fn width(&self) -> u32 {
+        match self.elementType {
+            ElementType::U8 | ElementType::S8 => 8,
+            ElementType::U16 | ElementType::S16 => 16,
+            ElementType::U32 | ElementType::S32 => 32,
+            ElementType::Field | ElementType::S64 => 64,
+        }
+    }
+
+    /// The bits of `v`, negative values of signed types in two's complement.
+    fn bits(&self, v: FGL) -> u64 {
+        let v = v.as_int();
+        let signed = matches!(
+            self.elementType,
+            ElementType::S8 | ElementType::S16 | ElementType::S32 | ElementType::S64
+        );
+        if !signed || v <= (MODULUS.0[0] - 1) / 2 {
+            return v;
+        }
+        let neg = (MODULUS.0[0] - v).wrapping_neg();
+        match self.width() {
+            64 => neg,
+            w => neg & ((1 << w) - 1),
+        }
+    }
+}
+
+/// The identifier of signal `i`, in the printable characters VCD allows.
+fn code(mut i: usize) -> String {
+    let mut code = String::new();
+    loop {
+        code.push((b'!' + (i % 94) as u8) as char);
+        i /= 94;
+        if i == 0 {
+            return code;
+        }
+    }
+}
+
+/// Write `signals` over `rows` as a VCD.
+pub fn write_vcd(w: &mut impl Write, signals: &[Signal], rows: Range<usize>) -> Result<()> {
+    writeln!(w, "$version pilcom-rust $end")?;
+    writeln!(w, "$timescale 1 ns $end")?;
+
+    // scopes in the order of their first signal
+    let mut scopes: Vec<&str> = vec![];
+    for s in signals {
+        let scope = s.name.split_once('.').map_or("", |(ns, _)| ns);
+        if !scopes.contains(&scope) {
+            scopes.push(scope);
+        }
+    }
+    for scope in scopes {
+        if !scope.is_empty() {
+            writeln!(w, "$scope module {} $end", scope)?;
+        }
+        for (i, s) in signals.iter().enumerate() {
+            match s.name.split_once('.') {
+                Some((ns, name)) if ns == scope => {
+                    writeln!(w, "$var wire {} {} {} $end", s.width(), code(i), name)?
+                }
+                None if scope.is_empty() => {
+                    writeln!(w, "$var wire {} {} {} $end", s.width(), code(i), s.name)?
+                }
+                _ => {}
+            }
+        }
+        if !scope.is_empty() {
+            writeln!(w, "$upscope $end")?;
+        }
+    }
+    writeln!(w, "$enddefinitions $end")?;
+
+    for j in rows.clone() {
+        let mut changes = vec![];
+        for (i, s) in signals.iter().enumerate() {
+            match s.values.get(j) {
+                Some(v) if j == rows.start || s.values[j - 1] != *v => {
+                    changes.push(format!("b{:b} {}", s.bits(*v), code(i)))
+                }
+                None if j == rows.start || j == s.values.len() => {
+                    changes.push(format!("bx {}", code(i)))
+                }
+                _ => {}
+            }
+        }
+        if j == rows.start {
+            writeln!(w, "#{}\n$dumpvars", j)?;
+            changes.push("$end".to_string());
+        } else if !changes.is_empty() {
+            writeln!(w, "#{}", j)?;
+        }
+        for c in changes {
+            writeln!(w, "{}", c)?;
+        }
+    }
+    writeln!(w, "#{}", rows.end)?;
+    Ok(())
+}
+
+impl PolsArray {
+    /// The polynomials `names` select, as in `Selection::pols`, as signals.
+    pub fn signals(&self, names: &[String]) -> Result<Vec<Signal<'_>>> {
+        Ok(self
+            .select(names)?
+            .into_iter()
+            .map(|id| Signal {
+                name: pol_name(&self.defArray[id]),
+                elementType: self.element_type(id),
+                values: self.column(id),
+            })
+            .collect())
+    }
+
+    pub fn export_vcd(&self, w: &mut impl Write, selection: &Selection) -> Result<()> {
+        let signals = self.signals(&selection.pols)?;
+        let n = signals.iter().map(|s| s.values.len()).max().unwrap_or(0);
+        let rows = match &selection.rows {
+            Some(rows) => rows.start.min(n)..rows.end.min(n),
+            None => 0..n,
+        };
+        write_vcd(w, &signals, rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::pil_verify::{PilVerify, Pols};
+    use crate::utils::polarray::PolKind;
+    use crate::utils::types::{read_json, PIL};
+
+    // namespace Main(4);
+    //     pol constant L;
+    //     pol commit s8 d;
+    //     pol commit a[2];
+    //     pol s = a[0] + a[1];
+    // namespace Small(2);
+    //     pol commit u8 c;
+    const PIL_JSON: &str = r#"{
+        "nCommitments": 4, "nQ": 0, "nIm": 1, "nConstants": 1, "publics": [],
+        "references": {
+            "Main.L": { "type": "constP", "id": 0, "polDeg": 4, "isArray": false },
+            "Main.d": { "type": "cmP", "id": 0, "polDeg": 4, "isArray": false,
+                "elementType": "s8" },
+            "Main.a": { "type": "cmP", "id": 1, "polDeg": 4, "isArray": true, "len": 2 },
+            "Small.c": { "type": "cmP", "id": 3, "polDeg": 2, "isArray": false,
+                "elementType": "u8" },
+            "Main.s": { "type": "imP", "id": 0, "polDeg": 4, "isArray": false }
+        },
+        "expressions": [
+            { "op": "add", "deg": 1, "values": [
+                { "op": "cm", "deg": 1, "id": 1 },
+                { "op": "cm", "deg": 1, "id": 2 }
+            ] }
+        ],
+        "polIdentities": [], "plookupIdentities": []
+    }"#;
+
+    #[test]
+    fn test_vcd() {
+        let pil = read_json::<PIL>(PIL_JSON.to_string()).unwrap();
+        let const_pols = PolsArray::new(&pil, PolKind::Constant).unwrap();
+        let mut cm_pols = PolsArray::new(&pil, PolKind::Commit).unwrap();
+        for (j, d) in [0u64, 1, 1, 3].into_iter().enumerate() {
+            cm_pols.set_value(0, j, -FGL::from(d));
+            cm_pols.set_value(1, j, FGL::from(j as u64));
+            cm_pols.set_value(2, j, FGL::from(2u64));
+        }
+        cm_pols.set_value(3, 0, FGL::from(255u64));
+        cm_pols.set_value(3, 1, FGL::from(255u64));
+
+        let mut pols = Pols::default();
+        let mut verifier = PilVerify::new(&pil, &cm_pols, &const_pols, &mut pols);
+        let intermediates = verifier.intermediates().unwrap();
+        assert_eq!(intermediates.len(), 1);
+        assert_eq!(intermediates[0].1[3], FGL::from(5u64));
+
+        let mut signals = cm_pols.signals(&[]).unwrap();
+        signals.extend(const_pols.signals(&[]).unwrap());
+        signals.extend(intermediates.into_iter().map(|(name, values)| Signal {
+            name,
+            elementType: ElementType::Field,
+            values: Cow::Owned(values),
+        }));
+        let mut out = vec![];
+        write_vcd(&mut out, &signals, 0..4).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        assert_eq!(
+            vcd,
+            "$version pilcom-rust $end
+$timescale 1 ns $end
+$scope module Main $end
+$var wire 8 ! d $end
+$var wire 64 \" a[0] $end
+$var wire 64 # a[1] $end
+$var wire 64 % L $end
+$var wire 64 & s $end
+$upscope $end
+$scope module Small $end
+$var wire 8 $ c $end
+$upscope $end
+$enddefinitions $end
+#0
+$dumpvars
+b0 !
+b0 \"
+b10 #
+b11111111 $
+b0 %
+b10 &
+$end
+#1
+b11111111 !
+b1 \"
+b11 &
+#2
+b10 \"
+bx $
+b100 &
+#3
+b11111101 !
+b11 \"
+b101 &
+#4
+"
+        );
+
+        // a window, which dumps every signal at its first row
+        let mut out = vec![];
+        let selection = Selection {
+            pols: vec!["Small".to_string()],
+            rows: Some(1..10),
+        };
+        cm_pols.export_vcd(&mut out, &selection).unwrap();
+        let vcd = String::from_utf8(out).unwrap();
+        assert!(vcd.ends_with("#1\n$dumpvars\nb11111111 !\n$end\n#2\n"));
+        assert_eq!(code(93), "~");
+        assert_eq!(code(94), "!\"");
+    }
+}
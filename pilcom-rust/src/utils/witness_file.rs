@@ -0,0 +1,241 @@
+#![allow(non_snake_case)]
+//! The versioned witness file, which describes the polynomials it holds so that it can be
+//! checked against the PIL it is loaded with. All integers are little endian:
+//!
+//! ```text
+//! magic        b"PILW\xff\xff\xff\xff"
+//! version      u32
+//! kind         u32          0 for committed, 1 for constant polynomials
+//! encoding     u32          see `Encoding`
+//! n            u64          largest polDeg
+//! nPols        u64
+//! pilHash      [u8; 32]     SHA-256 of the PIL JSON
+//! contentHash  [u8; 32]     SHA-256 of the values
+//! columns      nPols times: polDeg u64, name length u32, name
+//! padding      zeros up to a multiple of 8 bytes
+//! values       as set by `encoding`
+//! ```
+//!
+//! As a little-endian u64 the magic is above the Goldilocks modulus, so a raw file, whose
+//! first value is a field element, never starts with it.
+//!
+//! The content hash is always the one of the values in the raw format, so that it also
+//! checks the decoding of the others.
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::{Read, Write};
+
+use super::error::{PilError, Result};
+use super::polarray::PolKind;
+use super::types::PIL;
+
+pub const MAGIC: &[u8; 8] = b"PILW\xff\xff\xff\xff";
+pub const VERSION: u32 = 1;
+
+/// How the values follow the header.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Encoding {
+    /// u64 each, in the order of the raw format.
+    #[default]
+    Plain,
+    /// Every polynomial in turn, bit-width packed by `packing::write_column`.
+    Packed,
+    /// `Packed`, compressed as a zstd stream.
+    PackedZstd,
+}
+
+/// A polynomial of the file: its reference name, with the index for arrays, and its degree.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Column {
+    pub name: String,
+    pub polDeg: usize,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WitnessHeader {
+    pub version: u32,
+    pub kind: PolKind,
+    pub encoding: Encoding,
+    pub n: usize,
+    pub nPols: usize,
+    pub pilHash: [u8; 32],
+    pub contentHash: [u8; 32],
+    pub columns: Vec<Column>,
+}
+
+/// Fingerprint of `pil`: the SHA-256 of its JSON, references sorted by name.
+pub fn pil_hash(pil: &PIL) -> [u8; 32] {
+    let json = serde_json::to_vec(pil).expect("a PIL always serializes");
+    Sha256::digest(&json).into()
+}
+
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn corrupt(what: &str) -> PilError {
+    PilError::CorruptFile(format!("truncated header, reading {}", what))
+}
+
+fn read_array<const N: usize>(r: &mut impl Read, what: &str) -> Result<[u8; N]> {
+    let mut buff = [0u8; N];
+    r.read_exact(&mut buff).map_err(|_| corrupt(what))?;
+    Ok(buff)
+}
+
+fn read_u32(r: &mut impl Read, what: &str) -> Result<u32> {
+    Ok(u32::from_le_bytes(read_array(r, what)?))
+}
+
+fn read_u64(r: &mut impl Read, what: &str) -> Result<usize> {
+    Ok(u64::from_le_bytes(read_array(r, what)?) as usize)
+}
+
+impl WitnessHeader {
+    /// Read the header at the start of `r`, or `None` when `r` does not start with `MAGIC`,
+    /// i.e. holds a raw file. The magic is consumed either way.
+    pub fn read(r: &mut impl Read) -> Result<Option<Self>> {
+        let mut magic = vec![];
+        r.take(MAGIC.len() as u64).read_to_end(&mut magic)?;
+        if magic != MAGIC {
+            return Ok(None);
+        }
+        let version = read_u32(r, "version")?;
+        if version == 0 || version > VERSION {
+            return Err(PilError::CorruptFile(format!(
+                "unsupported version {}, expected up to {}",
+                version, VERSION
+            )));
+        }
+        let kind = match read_u32(r, "kind")? {
+            0 => PolKind::Commit,
+            1 => PolKind::Constant,
+            k => return Err(PilError::CorruptFile(format!("unknown kind {}", k))),
+        };
+        let encoding = match read_u32(r, "encoding")? {
+            0 => Encoding::Plain,
+            1 => Encoding::Packed,
+            2 => Encoding::PackedZstd,
+            e => return Err(PilError::CorruptFile(format!("unknown encoding {}", e))),
+        };
+        let n = read_u64(r, "n")?;
+        let nPols = read_u64(r, "nPols")?;
+        let pilHash = read_array(r, "pilHash")?;
+        let contentHash = read_array(r, "contentHash")?;
+        let mut columns = Vec::with_capacity(nPols.min(1 << 20));
+        for _ in 0..nPols {
+            let polDeg = read_u64(r, "polDeg")?;
+            let len = read_u32(r, "name length")? as u64;
+            let mut name = vec![];
+            r.take(len).read_to_end(&mut name)?;
+            if name.len() as u64 != len {
+                return Err(corrupt("name"));
+            }
+            let name = String::from_utf8(name)
+                .map_err(|_| PilError::CorruptFile("column name is not UTF-8".to_string()))?;
+            columns.push(Column { name, polDeg });
+        }
+        let header = WitnessHeader {
+            version,
+            kind,
+            encoding,
+            n,
+            nPols,
+            pilHash,
+            contentHash,
+            columns,
+        };
+        let mut padding = vec![0u8; header.size() - header.unpadded_size()];
+        r.read_exact(&mut padding).map_err(|_| corrupt("padding"))?;
+        Ok(Some(header))
+    }
+
+    /// The header of `fileName`, `None` for a raw file.
+    pub fn load(fileName: &str) -> Result<Option<Self>> {
+        Self::read(&mut File::open(fileName)?)
+    }
+
+    pub fn write(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(MAGIC)?;
+        w.write_all(&self.version.to_le_bytes())?;
+        let kind: u32 = match self.kind {
+            PolKind::Commit => 0,
+            PolKind::Constant => 1,
+        };
+        w.write_all(&kind.to_le_bytes())?;
+        let encoding: u32 = match self.encoding {
+            Encoding::Plain => 0,
+            Encoding::Packed => 1,
+            Encoding::PackedZstd => 2,
+        };
+        w.write_all(&encoding.to_le_bytes())?;
+        w.write_all(&(self.n as u64).to_le_bytes())?;
+        w.write_all(&(self.nPols as u64).to_le_bytes())?;
+        w.write_all(&self.pilHash)?;
+        w.write_all(&self.contentHash)?;
+        for c in self.columns.iter() {
+            w.write_all(&(c.polDeg as u64).to_le_bytes())?;
+            w.write_all(&(c.name.len() as u32).to_le_bytes())?;
+            w.write_all(c.name.as_bytes())?;
+        }
+        w.write_all(&vec![0u8; self.size() - self.unpadded_size()])?;
+        Ok(())
+    }
+
+    fn unpadded_size(&self) -> usize {
+        let names: usize = self.columns.iter().map(|c| 12 + c.name.len()).sum();
+        MAGIC.len() + 4 + 4 + 4 + 8 + 8 + 32 + 32 + names
+    }
+
+    /// Size of the header in bytes, which is where the values start.
+    pub fn size(&self) -> usize {
+        self.unpadded_size().next_multiple_of(8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_round_trip() {
+        let header = WitnessHeader {
+            version: VERSION,
+            kind: PolKind::Constant,
+            encoding: Encoding::PackedZstd,
+            n: 8,
+            nPols: 2,
+            pilHash: [1; 32],
+            contentHash: [2; 32],
+            columns: vec![
+                Column {
+                    name: "Main.a".to_string(),
+                    polDeg: 8,
+                },
+                Column {
+                    name: "Main.b[0]".to_string(),
+                    polDeg: 4,
+                },
+            ],
+        };
+        let mut buff = vec![];
+        header.write(&mut buff).unwrap();
+        assert_eq!(buff.len(), header.size());
+        assert_eq!(buff.len() % 8, 0);
+        assert_eq!(WitnessHeader::read(&mut &buff[..]).unwrap(), Some(header));
+
+        assert_eq!(WitnessHeader::read(&mut &[1u8, 2, 3][..]).unwrap(), None);
+        // a raw file whose first value is "PILW" in its low bytes
+        let raw = 0x574c4950u64.to_le_bytes();
+        assert_eq!(WitnessHeader::read(&mut &raw[..]).unwrap(), None);
+        assert!(matches!(
+            WitnessHeader::read(&mut &buff[..40]),
+            Err(PilError::CorruptFile(_))
+        ));
+        buff[8] = 3;
+        assert!(matches!(
+            WitnessHeader::read(&mut &buff[..]),
+            Err(PilError::CorruptFile(_))
+        ));
+    }
+}